@@ -0,0 +1,21 @@
+use arboard::Clipboard;
+use std::sync::{Mutex, OnceLock};
+
+/// Opened once and kept for the life of the app: on X11/Wayland the copied text is only
+/// served to other applications while a clipboard handle is alive.
+static CLIPBOARD: OnceLock<Mutex<Option<Clipboard>>> = OnceLock::new();
+
+pub fn set_text(text: &str) -> Result<(), String> {
+    let mut guard = CLIPBOARD
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        *guard = Some(Clipboard::new().map_err(|e| format!("open clipboard: {e}"))?);
+    }
+    guard
+        .as_mut()
+        .expect("clipboard initialised above")
+        .set_text(text.to_string())
+        .map_err(|e| format!("set clipboard: {e}"))
+}
@@ -21,6 +21,37 @@ pub fn retrieve_password(host: &str, user: &str) -> Result<Option<String>, Strin
     }
 }
 
+fn secret_key(name: &str) -> String {
+    format!("secret:{name}")
+}
+
+/// Stores a named secret (e.g. an API key) referenced by connection env vars.
+pub fn store_secret(name: &str, value: &str) -> Result<(), String> {
+    let entry = Entry::new(SERVICE_NAME, &secret_key(name)).map_err(|e| format!("keyring entry: {e}"))?;
+    entry
+        .set_password(value)
+        .map_err(|e| format!("keyring store: {e}"))?;
+    Ok(())
+}
+
+pub fn retrieve_secret(name: &str) -> Result<Option<String>, String> {
+    let entry = Entry::new(SERVICE_NAME, &secret_key(name)).map_err(|e| format!("keyring entry: {e}"))?;
+    match entry.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("keyring retrieve: {e}")),
+    }
+}
+
+pub fn delete_secret(name: &str) -> Result<(), String> {
+    let entry = Entry::new(SERVICE_NAME, &secret_key(name)).map_err(|e| format!("keyring entry: {e}"))?;
+    match entry.delete_credential() {
+        Ok(()) => Ok(()),
+        Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("keyring delete: {e}")),
+    }
+}
+
 pub fn delete_password(host: &str, user: &str) -> Result<(), String> {
     let key = format!("{}@{}", user, host);
     let entry = Entry::new(SERVICE_NAME, &key).map_err(|e| format!("keyring entry: {e}"))?;
@@ -2,6 +2,9 @@ mod workset;
 mod ssh;
 mod settings;
 mod keystore;
+mod clipboard;
+mod persist;
+mod tasks;
 
 use serde::Serialize;
 use ssh2_config::{ParseRule, SshConfig};
@@ -9,9 +12,12 @@ use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
 use uuid::Uuid;
-use workset::{AuthMethod, CreateWorksetInput, UpdateWorksetInput, Workset, WorksetStore, WorksetSummary};
-use ssh::{FileEntry, ReadFileResult, SshConnectionManager, SshSessionConfig};
-use settings::{AppSettings, SettingsStore};
+use workset::{AgentRules, AuthMethod, CreateWorksetInput, EnvValue, UpdateWorksetInput, Workset, WorksetStore, WorksetSummary};
+use ssh::ai_cli::{self, AiCliCheck};
+use ssh::session::shell_escape;
+use ssh::{AgentStatus, BroadcastState, BroadcastTarget, DispatchResult, DownloadStarted, ExecOutput, FileEntry, GitCommit, GitDashboardEntry, GitDiff, GitStatus, HostExecResult, JobInfo, MarkdownAssets, PushHostReport, ReadFileResult, RemoteFileDiff, SearchOptions, SessionSearchResult, ShellState, SshConnectionManager, SshSessionConfig};
+use settings::{AiCliProfile, AppSettings, SettingsStore};
+use tasks::{Task, TaskStore};
 
 // ── Return type for activate_workset ──
 
@@ -73,6 +79,19 @@ fn set_theme(theme: String, store: tauri::State<'_, SettingsStore>) -> Result<Ap
     store.set_theme_result(parsed).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn set_ai_cli_profiles(
+    profiles: Vec<AiCliProfile>,
+    store: tauri::State<'_, SettingsStore>,
+) -> Result<AppSettings, String> {
+    store.set_ai_cli_profiles_result(profiles).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_ai_cli_presets() -> Vec<AiCliProfile> {
+    settings::ai_cli_presets()
+}
+
 // ── OS Keystore Commands (SSH password storage) ──
 
 #[tauri::command]
@@ -85,6 +104,25 @@ fn retrieve_ssh_password(host: String, user: String) -> Result<Option<String>, S
     keystore::retrieve_password(&host, &user)
 }
 
+#[tauri::command]
+fn store_secret(name: String, value: String) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("secret name must not be empty".to_string());
+    }
+    keystore::store_secret(&name, &value)
+}
+
+/// Reports whether a secret exists without sending its value to the frontend.
+#[tauri::command]
+fn has_secret(name: String) -> Result<bool, String> {
+    Ok(keystore::retrieve_secret(&name)?.is_some())
+}
+
+#[tauri::command]
+fn delete_secret(name: String) -> Result<(), String> {
+    keystore::delete_secret(&name)
+}
+
 #[tauri::command]
 fn delete_ssh_password(host: String, user: String) -> Result<(), String> {
     keystore::delete_password(&host, &user)
@@ -98,12 +136,15 @@ async fn activate_workset(
     passwords: Vec<Option<String>>,
     app: tauri::AppHandle,
     store: tauri::State<'_, WorksetStore>,
+    settings_store: tauri::State<'_, SettingsStore>,
+    task_store: tauri::State<'_, TaskStore>,
     ssh_manager: tauri::State<'_, SshConnectionManager>,
 ) -> Result<Vec<SessionInfo>, String> {
     let workset = store.get(&workset_id).ok_or_else(|| format!("workset not found: {workset_id}"))?;
 
     // Prevent double activation
-    ssh_manager.disconnect_all();
+    task_store.stop_scheduler();
+    ssh_manager.disconnect_all(&app);
 
     let mut configs: Vec<SshSessionConfig> = Vec::new();
     let mut parsed_ssh_config: Option<SshConfig> = None;
@@ -223,6 +264,51 @@ async fn activate_workset(
             }
         };
 
+        let mut env = Vec::with_capacity(conn.env.len());
+        for var in &conn.env {
+            let value = match &var.value {
+                EnvValue::Literal { value } => value.clone(),
+                EnvValue::Secret { secret } => keystore::retrieve_secret(secret)?
+                    .ok_or_else(|| format!("{}: secret '{secret}' for {} is not in the keystore", conn.host, var.name))?,
+            };
+            env.push((var.name.clone(), value));
+        }
+
+        let (ai_cli_command, ai_cli_restart_command, ai_cli_check, agent_rules) = match &conn.ai_cli_profile {
+            Some(profile_id) => {
+                let profile = settings_store
+                    .ai_cli_profile(profile_id)
+                    .ok_or_else(|| format!("{}: AI CLI profile '{profile_id}' does not exist", conn.host))?;
+                let command = std::iter::once(profile.command.trim().to_string())
+                    .chain(profile.args.iter().map(|a| shell_escape(a)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let binary = profile
+                    .binary
+                    .clone()
+                    .filter(|b| !b.trim().is_empty())
+                    .or_else(|| ai_cli::program_name(&profile.command).map(str::to_string))
+                    .unwrap_or_default();
+                let check = AiCliCheck {
+                    binary,
+                    required_env: profile
+                        .required_env
+                        .iter()
+                        .filter(|name| !env.iter().any(|(n, _)| n == *name))
+                        .cloned()
+                        .collect(),
+                };
+                // The connection's own rules win; the profile supplies them otherwise.
+                let rules = if conn.agent_rules == AgentRules::default() {
+                    profile.agent_rules.clone()
+                } else {
+                    conn.agent_rules.clone()
+                };
+                (Some(command), profile.restart_command.clone(), Some(check), rules)
+            }
+            None => (conn.ai_cli_command.clone(), None, None, conn.agent_rules.clone()),
+        };
+
         let password = if auth_method_str == "password" {
             passwords.get(i).cloned().flatten()
         } else {
@@ -238,13 +324,24 @@ async fn activate_workset(
             key_path,
             password,
             project_path: conn.project_path.clone(),
-            ai_cli_command: conn.ai_cli_command.clone(),
+            ai_cli_command,
+            ai_cli_restart_command,
+            ai_cli_check,
             keepalive_interval_secs: conn.keepalive_interval_secs,
             reconnect_max_retries: conn.reconnect_max_retries,
+            env,
+            startup_steps: conn.startup_steps.clone(),
+            shell_ready_pattern: conn.shell_ready_pattern.clone(),
+            shell_ready_timeout_secs: conn.shell_ready_timeout_secs,
+            shell_integration: conn.shell_integration,
+            clipboard: conn.clipboard,
+            agent_rules,
+            auto_restart: conn.auto_restart,
         });
     }
 
-    let results = ssh_manager.connect_all(configs, app);
+    let results = ssh_manager.connect_all(configs, app.clone());
+    task_store.start_scheduler(workset_id.clone(), app);
 
     let session_infos: Vec<SessionInfo> = results
         .into_iter()
@@ -272,12 +369,53 @@ async fn activate_workset(
 
 #[tauri::command]
 async fn deactivate_workset(
+    app: tauri::AppHandle,
+    task_store: tauri::State<'_, TaskStore>,
     ssh_manager: tauri::State<'_, SshConnectionManager>,
 ) -> Result<(), String> {
-    ssh_manager.disconnect_all();
+    task_store.stop_scheduler();
+    ssh_manager.disconnect_all(&app);
     Ok(())
 }
 
+// ── Task Queue Commands ──
+
+#[tauri::command]
+fn enqueue_tasks(
+    workset_id: String,
+    prompts: Vec<String>,
+    store: tauri::State<'_, WorksetStore>,
+    task_store: tauri::State<'_, TaskStore>,
+) -> Result<Vec<Task>, String> {
+    store.get(&workset_id).ok_or_else(|| format!("workset not found: {workset_id}"))?;
+    task_store.enqueue(&workset_id, prompts).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_tasks(workset_id: String, task_store: tauri::State<'_, TaskStore>) -> Result<Vec<Task>, String> {
+    task_store.list(&workset_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn remove_task(task_id: String, task_store: tauri::State<'_, TaskStore>) -> Result<Vec<Task>, String> {
+    task_store.remove(&task_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn cancel_task(task_id: String, task_store: tauri::State<'_, TaskStore>) -> Result<Vec<Task>, String> {
+    task_store.cancel(&task_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn retry_task(task_id: String, task_store: tauri::State<'_, TaskStore>) -> Result<Vec<Task>, String> {
+    task_store.retry(&task_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn clear_finished_tasks(workset_id: String, task_store: tauri::State<'_, TaskStore>) -> Result<Vec<Task>, String> {
+    task_store.clear_finished(&workset_id).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn terminal_input(
     session_id: String,
@@ -289,6 +427,32 @@ fn terminal_input(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn set_broadcast(
+    target: BroadcastTarget,
+    app: tauri::AppHandle,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<BroadcastState, String> {
+    ssh_manager
+        .set_broadcast(target, &app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn clear_broadcast(
+    app: tauri::AppHandle,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<BroadcastState, String> {
+    ssh_manager
+        .set_broadcast(BroadcastTarget::Off, &app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_broadcast_state(ssh_manager: tauri::State<'_, SshConnectionManager>) -> BroadcastState {
+    ssh_manager.broadcast_state()
+}
+
 #[tauri::command]
 fn terminal_resize(
     session_id: String,
@@ -301,6 +465,22 @@ fn terminal_resize(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn get_agent_state(
+    session_id: String,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<AgentStatus, String> {
+    ssh_manager.agent_status(&session_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_shell_state(
+    session_id: String,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<ShellState, String> {
+    ssh_manager.shell_state(&session_id).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn terminal_reconnect(
     session_id: String,
@@ -311,6 +491,19 @@ fn terminal_reconnect(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn dispatch_prompt(
+    session_ids: Vec<String>,
+    text: String,
+    submit: Option<bool>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<Vec<DispatchResult>, String> {
+    if text.trim().is_empty() {
+        return Err("prompt is empty".to_string());
+    }
+    Ok(ssh_manager.dispatch_prompt(&session_ids, &text, submit.unwrap_or(true)))
+}
+
 #[tauri::command]
 fn restart_ai_cli(
     session_id: String,
@@ -337,13 +530,210 @@ async fn read_file(
     session_id: String,
     path: String,
     max_bytes: Option<u64>,
+    decode_text: Option<bool>,
     ssh_manager: tauri::State<'_, SshConnectionManager>,
 ) -> Result<ReadFileResult, String> {
     ssh_manager
-        .read_file(&session_id, path, max_bytes)
+        .read_file(&session_id, path, max_bytes, decode_text.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn resolve_markdown_assets(
+    session_id: String,
+    path: String,
+    max_image_bytes: Option<u64>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<MarkdownAssets, String> {
+    ssh_manager
+        .resolve_markdown_assets(&session_id, &path, max_image_bytes)
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn search_workset(
+    query: String,
+    options: Option<SearchOptions>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<Vec<SessionSearchResult>, String> {
+    if query.is_empty() {
+        return Err("search query must not be empty".to_string());
+    }
+    Ok(ssh_manager.search_all(&query, &options.unwrap_or_default()))
+}
+
+#[tauri::command]
+async fn diff_remote_files(
+    session_a: String,
+    path_a: String,
+    session_b: String,
+    path_b: String,
+    max_bytes: Option<u64>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<RemoteFileDiff, String> {
+    ssh_manager
+        .diff_remote_files(&session_a, path_a, &session_b, path_b, max_bytes)
+        .map_err(|e| e.to_string())
+}
+
+// ── Transfer Commands ──
+
+#[tauri::command]
+fn copy_between_sessions(
+    src_session_id: String,
+    src_path: String,
+    dst_session_id: String,
+    dst_path: String,
+    app: tauri::AppHandle,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<String, String> {
+    ssh_manager
+        .copy_between_sessions(&src_session_id, src_path, &dst_session_id, dst_path, app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn push_to_workset(
+    local_path: String,
+    dest: Option<String>,
+    dry_run: Option<bool>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<Vec<PushHostReport>, String> {
+    ssh_manager
+        .push_to_all(&local_path, dest.as_deref(), dry_run.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn download_directory(
+    session_id: String,
+    path: String,
+    dest: String,
+    app: tauri::AppHandle,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<DownloadStarted, String> {
+    ssh_manager
+        .download_directory(&session_id, path, dest, app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn cancel_transfer(
+    transfer_id: String,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<(), String> {
+    if ssh_manager.cancel_transfer(&transfer_id) {
+        Ok(())
+    } else {
+        Err(format!("transfer not found: {transfer_id}"))
+    }
+}
+
+// ── Exec Commands ──
+
+#[tauri::command]
+async fn run_on_all(
+    command: String,
+    timeout_secs: Option<u64>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<Vec<HostExecResult>, String> {
+    if command.trim().is_empty() {
+        return Err("command must not be empty".to_string());
+    }
+    Ok(ssh_manager.run_on_all(&command, timeout_secs.map(std::time::Duration::from_secs)))
+}
+
+#[tauri::command]
+async fn exec_command(
+    session_id: String,
+    cmd: String,
+    cwd: Option<String>,
+    env: Option<std::collections::BTreeMap<String, String>>,
+    timeout_secs: Option<u64>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<ExecOutput, String> {
+    if cmd.trim().is_empty() {
+        return Err("command must not be empty".to_string());
+    }
+    ssh_manager
+        .exec_command(
+            &session_id,
+            &cmd,
+            cwd.as_deref(),
+            &env.unwrap_or_default(),
+            timeout_secs.map(std::time::Duration::from_secs),
+        )
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn start_job(
+    session_id: String,
+    cmd: String,
+    cwd: Option<String>,
+    env: Option<std::collections::BTreeMap<String, String>>,
+    job_id: Option<String>,
+    app: tauri::AppHandle,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<JobInfo, String> {
+    if cmd.trim().is_empty() {
+        return Err("command must not be empty".to_string());
+    }
+    ssh_manager
+        .start_job(&session_id, job_id, &cmd, cwd.as_deref(), &env.unwrap_or_default(), app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn kill_job(job_id: String, ssh_manager: tauri::State<'_, SshConnectionManager>) -> Result<bool, String> {
+    Ok(ssh_manager.kill_job(&job_id))
+}
+
+#[tauri::command]
+fn list_jobs(session_id: Option<String>, ssh_manager: tauri::State<'_, SshConnectionManager>) -> Vec<JobInfo> {
+    ssh_manager.list_jobs(session_id.as_deref())
+}
+
+// ── Git Commands ──
+
+#[tauri::command]
+async fn git_status(
+    session_id: String,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<GitStatus, String> {
+    ssh_manager
+        .git_status(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn git_diff(
+    session_id: String,
+    path: Option<String>,
+    staged: Option<bool>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<GitDiff, String> {
+    ssh_manager
+        .git_diff(&session_id, path, staged.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn git_log(
+    session_id: String,
+    limit: Option<u32>,
+    ssh_manager: tauri::State<'_, SshConnectionManager>,
+) -> Result<Vec<GitCommit>, String> {
+    ssh_manager
+        .git_log(&session_id, limit.unwrap_or(50))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_git_dashboard(ssh_manager: tauri::State<'_, SshConnectionManager>) -> Vec<GitDashboardEntry> {
+    ssh_manager.git_dashboard()
+}
+
 // ── App Entry ──
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -351,6 +741,7 @@ pub fn run() {
     tauri::Builder::default()
         .manage(WorksetStore::new())
         .manage(SettingsStore::new())
+        .manage(TaskStore::new())
         .manage(SshConnectionManager::new())
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
@@ -361,17 +752,50 @@ pub fn run() {
             delete_workset,
             get_settings,
             set_theme,
+            set_ai_cli_profiles,
+            list_ai_cli_presets,
             store_ssh_password,
             retrieve_ssh_password,
             delete_ssh_password,
+            store_secret,
+            has_secret,
+            delete_secret,
             activate_workset,
             deactivate_workset,
+            enqueue_tasks,
+            list_tasks,
+            remove_task,
+            cancel_task,
+            retry_task,
+            clear_finished_tasks,
             terminal_input,
+            set_broadcast,
+            clear_broadcast,
+            get_broadcast_state,
             terminal_resize,
+            get_shell_state,
+            get_agent_state,
             terminal_reconnect,
             restart_ai_cli,
+            dispatch_prompt,
             list_directory,
             read_file,
+            resolve_markdown_assets,
+            search_workset,
+            diff_remote_files,
+            copy_between_sessions,
+            push_to_workset,
+            download_directory,
+            cancel_transfer,
+            run_on_all,
+            exec_command,
+            start_job,
+            kill_job,
+            list_jobs,
+            git_status,
+            git_diff,
+            git_log,
+            get_git_dashboard,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,55 @@
+use chrono::{SecondsFormat, Utc};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub fn now_iso8601() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Writes `bytes` to a sibling `.tmp` file and renames it over `path`, so readers never see
+/// a half-written file. If the rename fails, `path` is left untouched.
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&tmp, bytes)?;
+
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_atomically_replaces_existing_file() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("nested").join("state.json");
+        write_atomically(&path, b"[1]").expect("first write");
+        write_atomically(&path, b"[1,2]").expect("second write");
+        assert_eq!(fs::read(&path).expect("read"), b"[1,2]");
+        assert!(!dir.path().join("nested").join("state.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_write_atomically_failed_rename_leaves_no_temp_file() {
+        let dir = tempdir().expect("tempdir");
+        // A non-empty directory in the way makes the rename fail.
+        let path = dir.path().join("state.json");
+        fs::create_dir_all(path.join("child")).expect("blocking dir");
+        assert!(write_atomically(&path, b"[1]").is_err());
+        assert!(path.join("child").is_dir());
+        assert!(!dir.path().join("state.json.tmp").exists());
+    }
+}
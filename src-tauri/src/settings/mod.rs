@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::{fs, io, path::PathBuf, sync::{Mutex, MutexGuard}};
 
+use crate::persist;
+use crate::workset::{is_valid_env_name, AgentPreset, AgentRules};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum Theme {
@@ -11,12 +14,94 @@ pub enum Theme {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AppSettings {
     pub theme: Theme,
+    #[serde(default)]
+    pub ai_cli_profiles: Vec<AiCliProfile>,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
-        Self { theme: Theme::Dark }
+        Self {
+            theme: Theme::Dark,
+            ai_cli_profiles: Vec::new(),
+        }
+    }
+}
+
+/// A named way to launch an AI CLI, referenced from `ConnectionConfig::ai_cli_profile`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AiCliProfile {
+    pub id: String,
+    pub name: String,
+    pub command: String,
+    /// Appended to `command`, each shell-quoted.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Variables that must be set (by the connection's env or the login shell) before launch.
+    #[serde(default)]
+    pub required_env: Vec<String>,
+    /// Checked with `command -v` before launch; defaults to the program name in `command`.
+    #[serde(default)]
+    pub binary: Option<String>,
+    /// Used unless the connection has rules of its own.
+    #[serde(default)]
+    pub agent_rules: AgentRules,
+    /// Command used when restarting, e.g. one that resumes the last conversation.
+    #[serde(default)]
+    pub restart_command: Option<String>,
+}
+
+fn preset_profile(id: &str, name: &str, command: &str, preset: AgentPreset, restart: Option<&str>) -> AiCliProfile {
+    AiCliProfile {
+        id: id.to_string(),
+        name: name.to_string(),
+        command: command.to_string(),
+        args: Vec::new(),
+        required_env: Vec::new(),
+        binary: None,
+        agent_rules: AgentRules {
+            preset: Some(preset),
+            ..AgentRules::default()
+        },
+        restart_command: restart.map(str::to_string),
+    }
+}
+
+/// Starting points offered in the profile editor; not stored until the user saves them.
+pub fn ai_cli_presets() -> Vec<AiCliProfile> {
+    vec![
+        preset_profile("claude-code", "Claude Code", "claude", AgentPreset::ClaudeCode, Some("claude --continue")),
+        preset_profile("opencode", "OpenCode", "opencode", AgentPreset::Opencode, None),
+        preset_profile("aider", "Aider", "aider", AgentPreset::Aider, None),
+    ]
+}
+
+fn validate_profiles(profiles: &[AiCliProfile]) -> Result<(), SettingsError> {
+    for (idx, p) in profiles.iter().enumerate() {
+        if p.id.trim().is_empty() || p.name.trim().is_empty() || p.command.trim().is_empty() {
+            return Err(SettingsError::Validation(format!(
+                "ai_cli_profiles[{idx}]: id, name and command must not be empty"
+            )));
+        }
+        if profiles[..idx].iter().any(|other| other.id == p.id) {
+            return Err(SettingsError::Validation(format!(
+                "ai_cli_profiles[{idx}].id '{}' is duplicated",
+                p.id
+            )));
+        }
+        if let Some(name) = p.required_env.iter().find(|n| !is_valid_env_name(n)) {
+            return Err(SettingsError::Validation(format!(
+                "ai_cli_profiles[{idx}].required_env: '{name}' is not a valid variable name"
+            )));
+        }
+        for pattern in p.agent_rules.patterns() {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(SettingsError::Validation(format!(
+                    "ai_cli_profiles[{idx}].agent_rules pattern '{pattern}' is invalid: {e}"
+                )));
+            }
+        }
     }
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -80,11 +165,25 @@ impl SettingsStore {
 
     pub fn set_theme_result(&self, theme: Theme) -> Result<AppSettings, SettingsError> {
         let _guard = self.lock_guard()?;
-        let settings = AppSettings { theme };
+        let mut settings = self.read_settings()?;
+        settings.theme = theme;
         self.write_settings(&settings)?;
         Ok(settings)
     }
 
+    pub fn set_ai_cli_profiles_result(&self, profiles: Vec<AiCliProfile>) -> Result<AppSettings, SettingsError> {
+        validate_profiles(&profiles)?;
+        let _guard = self.lock_guard()?;
+        let mut settings = self.read_settings()?;
+        settings.ai_cli_profiles = profiles;
+        self.write_settings(&settings)?;
+        Ok(settings)
+    }
+
+    pub fn ai_cli_profile(&self, id: &str) -> Option<AiCliProfile> {
+        self.get().ai_cli_profiles.into_iter().find(|p| p.id == id)
+    }
+
     pub fn parse_theme(theme: &str) -> Result<Theme, SettingsError> {
         match theme.trim().to_lowercase().as_str() {
             "dark" => Ok(Theme::Dark),
@@ -97,6 +196,11 @@ impl SettingsStore {
 
     fn get_result(&self) -> Result<AppSettings, SettingsError> {
         let _guard = self.lock_guard()?;
+        self.read_settings()
+    }
+
+    /// Callers must hold the store lock.
+    fn read_settings(&self) -> Result<AppSettings, SettingsError> {
         let path = self.settings_path()?;
         match fs::read_to_string(&path) {
             Ok(s) => {
@@ -128,16 +232,29 @@ impl SettingsStore {
 
     fn write_settings(&self, settings: &AppSettings) -> Result<(), SettingsError> {
         let path = self.settings_path()?;
-        let tmp = path.with_extension("json.tmp");
         let json = serde_json::to_string_pretty(settings)?;
-        fs::write(&tmp, json.as_bytes())?;
-
-        if fs::rename(&tmp, &path).is_err() {
-            // If rename fails (commonly on Windows if dest exists), fall back to overwrite.
-            let bytes = fs::read(&tmp)?;
-            fs::write(&path, bytes)?;
-            let _ = fs::remove_file(&tmp);
-        }
+        persist::write_atomically(&path, json.as_bytes())?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_valid() {
+        assert!(validate_profiles(&ai_cli_presets()).is_ok());
+    }
+
+    #[test]
+    fn test_validate_profiles_rejects_duplicates_and_bad_env() {
+        let mut profiles = ai_cli_presets();
+        profiles.push(profiles[0].clone());
+        assert!(matches!(validate_profiles(&profiles), Err(SettingsError::Validation(_))));
+
+        let mut profile = ai_cli_presets().remove(0);
+        profile.required_env = vec!["ANTHROPIC-KEY".into()];
+        assert!(matches!(validate_profiles(&[profile]), Err(SettingsError::Validation(_))));
+    }
+}
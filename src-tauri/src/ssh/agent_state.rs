@@ -0,0 +1,248 @@
+use regex::Regex;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use super::osc::strip_escapes;
+use crate::workset::{AgentPreset, AgentRules};
+
+/// Raw output kept for matching; TUIs redraw often, so only the recent screen matters.
+const WINDOW_BYTES: usize = 8 * 1024;
+
+/// A working agent that has printed nothing for this long is treated as idle.
+const QUIET_IDLE_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentState {
+    #[default]
+    Unknown,
+    Working,
+    Idle,
+    WaitingForApproval,
+    Errored,
+    Exited,
+}
+
+/// Payload of `agent-state-{id}` and `get_agent_state`.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct AgentStatus {
+    pub state: AgentState,
+    pub since_epoch: u64,
+    pub preset: Option<AgentPreset>,
+    /// The output that triggered the state, when a pattern did.
+    pub matched: Option<String>,
+    /// False when the connection has no rules, so `state` stays `unknown`.
+    pub detecting: bool,
+}
+
+struct Rule {
+    state: AgentState,
+    re: Regex,
+}
+
+fn preset_patterns(preset: AgentPreset) -> &'static [(AgentState, &'static str)] {
+    use AgentState::*;
+    match preset {
+        AgentPreset::ClaudeCode => &[
+            (Working, r"(?i)esc to interrupt"),
+            (Idle, r"\? for shortcuts"),
+            (WaitingForApproval, r"Do you want to (?:proceed|make this edit|create|run|allow)"),
+            (WaitingForApproval, r"❯ 1\. Yes"),
+            (Errored, r"API Error|overloaded_error|rate_limit_error"),
+        ],
+        AgentPreset::Opencode => &[
+            (Working, r"(?i)esc (?:to )?interrupt|working\.\.\."),
+            (Idle, r"(?i)ctrl\+p commands|enter send"),
+            (WaitingForApproval, r"(?i)permission required|allow once|allow always"),
+            (Errored, r"(?i)\b(?:api|provider) ?error\b"),
+        ],
+        AgentPreset::Aider => &[
+            (Working, r"Waiting for \S+|Tokens: .*sent"),
+            (Idle, r"(?m)^(?:architect|ask|code|help|multi)?> ?$"),
+            (WaitingForApproval, r"\(Y\)es/\(N\)o"),
+            (Errored, r"litellm\.\w+Error|\bAPIError\b"),
+        ],
+        AgentPreset::Custom => &[],
+    }
+}
+
+/// Picks a preset from the program name of the AI CLI command (`claude`, `opencode`, `aider`).
+pub fn detect_preset(ai_cli_command: &str) -> Option<AgentPreset> {
+    let program = super::ai_cli::program_name(ai_cli_command)?.rsplit('/').next()?;
+    match program {
+        "claude" => Some(AgentPreset::ClaudeCode),
+        "opencode" => Some(AgentPreset::Opencode),
+        "aider" => Some(AgentPreset::Aider),
+        _ => None,
+    }
+}
+
+/// Classifies an AI CLI from its PTY output with the connection's rules.
+pub struct AgentDetector {
+    preset: Option<AgentPreset>,
+    rules: Vec<Rule>,
+    window: Vec<u8>,
+    status: AgentStatus,
+    last_output: Option<Instant>,
+}
+
+impl AgentDetector {
+    pub fn new(rules: &AgentRules, ai_cli_command: Option<&str>) -> Self {
+        let preset = rules.preset.or_else(|| ai_cli_command.and_then(detect_preset));
+        let custom = [
+            (AgentState::Working, &rules.working),
+            (AgentState::Idle, &rules.idle),
+            (AgentState::WaitingForApproval, &rules.waiting_for_approval),
+            (AgentState::Errored, &rules.errored),
+        ];
+        // Patterns are validated when the workset is saved; a bad one is simply skipped here.
+        let mut compiled: Vec<Rule> = custom
+            .iter()
+            .flat_map(|(state, patterns)| patterns.iter().map(move |p| (*state, p.as_str())))
+            .filter_map(|(state, p)| Regex::new(p).ok().map(|re| Rule { state, re }))
+            .collect();
+        if let Some(preset) = preset {
+            compiled.extend(
+                preset_patterns(preset)
+                    .iter()
+                    .map(|(state, p)| Rule { state: *state, re: Regex::new(p).expect("preset pattern") }),
+            );
+        }
+        let detecting = !compiled.is_empty();
+        Self {
+            preset,
+            rules: compiled,
+            window: Vec::new(),
+            status: AgentStatus {
+                preset,
+                detecting,
+                ..AgentStatus::default()
+            },
+            last_output: None,
+        }
+    }
+
+    /// False when there is nothing to detect (no preset and no custom patterns).
+    pub fn is_active(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    pub fn status(&self) -> &AgentStatus {
+        &self.status
+    }
+
+    fn transition(&mut self, state: AgentState, matched: Option<String>, epoch: u64) -> bool {
+        if self.status.state == state {
+            return false;
+        }
+        self.status = AgentStatus {
+            state,
+            since_epoch: epoch,
+            preset: self.preset,
+            matched,
+            detecting: true,
+        };
+        true
+    }
+
+    /// Feeds output; returns true when the state changed.
+    pub fn observe(&mut self, data: &[u8], now: Instant, epoch: u64) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        self.last_output = Some(now);
+        self.window.extend_from_slice(data);
+        if self.window.len() > WINDOW_BYTES {
+            let excess = self.window.len() - WINDOW_BYTES;
+            self.window.drain(..excess);
+        }
+
+        let text = strip_escapes(&String::from_utf8_lossy(&self.window)).replace('\r', "");
+        // The match that ends latest describes what is on screen now; earlier rules win ties.
+        let mut best: Option<(usize, AgentState, &str)> = None;
+        for rule in &self.rules {
+            if let Some(m) = rule.re.find_iter(&text).last() {
+                if best.is_none_or(|(end, _, _)| m.end() > end) {
+                    best = Some((m.end(), rule.state, m.as_str()));
+                }
+            }
+        }
+        match best {
+            Some((_, state, matched)) => {
+                let matched = matched.trim().to_string();
+                self.transition(state, Some(matched), epoch)
+            }
+            None => false,
+        }
+    }
+
+    /// Time-based transitions; call regularly. Returns true when the state changed.
+    pub fn tick(&mut self, now: Instant, epoch: u64) -> bool {
+        let quiet = self
+            .last_output
+            .is_some_and(|at| now.duration_since(at) >= QUIET_IDLE_AFTER);
+        if self.status.state == AgentState::Working && quiet {
+            return self.transition(AgentState::Idle, None, epoch);
+        }
+        false
+    }
+
+    /// The foreground command (or the whole channel) ended while the agent was running.
+    pub fn exited(&mut self, epoch: u64) -> bool {
+        if !self.is_active() || matches!(self.status.state, AgentState::Unknown | AgentState::Exited) {
+            return false;
+        }
+        self.window.clear();
+        self.transition(AgentState::Exited, None, epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_preset() {
+        assert_eq!(detect_preset("claude --continue"), Some(AgentPreset::ClaudeCode));
+        assert_eq!(detect_preset("FOO=1 /usr/local/bin/aider --model x"), Some(AgentPreset::Aider));
+        assert_eq!(detect_preset("opencode"), Some(AgentPreset::Opencode));
+        assert_eq!(detect_preset("bash"), None);
+    }
+
+    #[test]
+    fn test_latest_match_wins_and_quiet_means_idle() {
+        let mut d = AgentDetector::new(&AgentRules::default(), Some("claude"));
+        let now = Instant::now();
+        assert!(d.observe(b"\x1b[2m\xe2\x9c\xbb Thinking\xe2\x80\xa6 (esc to interrupt)\x1b[0m", now, 1));
+        assert_eq!(d.status().state, AgentState::Working);
+
+        assert!(d.observe(b"\r\nDo you want to make this edit to main.rs?\r\n\xe2\x9d\xaf 1. Yes\r\n", now, 2));
+        assert_eq!(d.status().state, AgentState::WaitingForApproval);
+        assert!(!d.tick(now + QUIET_IDLE_AFTER, 3), "approval prompts don't time out");
+
+        assert!(d.observe(b"\r\n* Editing\xe2\x80\xa6 (esc to interrupt)", now, 4));
+        assert!(d.tick(now + QUIET_IDLE_AFTER, 5));
+        assert_eq!(d.status().state, AgentState::Idle);
+
+        assert!(d.exited(6));
+        assert_eq!(d.status().state, AgentState::Exited);
+    }
+
+    #[test]
+    fn test_custom_rules_without_preset() {
+        let rules = AgentRules {
+            preset: Some(AgentPreset::Custom),
+            errored: vec![r"FATAL: \w+".into()],
+            ..AgentRules::default()
+        };
+        let mut d = AgentDetector::new(&rules, Some("claude"));
+        assert!(d.is_active());
+        assert!(!d.observe(b"esc to interrupt", Instant::now(), 1));
+        assert!(d.observe(b"FATAL: quota\r\n", Instant::now(), 2));
+        assert_eq!(d.status().matched.as_deref(), Some("FATAL: quota"));
+
+        let none = AgentDetector::new(&AgentRules::default(), Some("vim"));
+        assert!(!none.is_active());
+        assert_eq!(serde_json::from_str::<AgentPreset>("\"none\"").unwrap(), AgentPreset::Custom);
+    }
+}
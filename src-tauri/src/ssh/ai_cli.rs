@@ -0,0 +1,102 @@
+use serde::Serialize;
+use ssh2::Session;
+use std::time::Duration;
+
+use super::exec::run_exec;
+use super::session::shell_escape;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const BINARY_MISSING: &str = "mvwt-ai-cli:binary-missing";
+const ENV_MISSING: &str = "mvwt-ai-cli:env-missing:";
+const CHECK_DONE: &str = "mvwt-ai-cli:done";
+
+/// What has to be present on the VM before a profile's AI CLI is launched.
+#[derive(Clone, Debug)]
+pub struct AiCliCheck {
+    pub binary: String,
+    /// Names already supplied by the connection's env are left out.
+    pub required_env: Vec<String>,
+}
+
+/// Payload of `ai-cli-unavailable-{id}`.
+#[derive(Serialize, Clone, Debug)]
+pub struct AiCliUnavailable {
+    pub session_id: String,
+    pub host: String,
+    pub message: String,
+}
+
+/// The program a command line runs: the first word that isn't a `NAME=value` assignment.
+pub fn program_name(command: &str) -> Option<&str> {
+    command.split_whitespace().find(|word| !word.contains('='))
+}
+
+fn check_script(check: &AiCliCheck) -> String {
+    let mut script = format!(
+        "command -v {} >/dev/null 2>&1 || echo {BINARY_MISSING}\n",
+        shell_escape(&check.binary)
+    );
+    // Names are validated as `[A-Za-z_][A-Za-z0-9_]*` when profiles are saved.
+    for name in &check.required_env {
+        script.push_str(&format!("[ -n \"${{{name}:-}}\" ] || echo {ENV_MISSING}{name}\n"));
+    }
+    script.push_str(&format!("echo {CHECK_DONE}\n"));
+    script
+}
+
+/// `None` when everything was found, or when the output is inconclusive (a shell that
+/// didn't run the script, a timeout); the launch then goes ahead as before.
+fn parse_check_output(stdout: &str, check: &AiCliCheck, host: &str) -> Option<String> {
+    let lines: Vec<&str> = stdout.lines().map(str::trim).collect();
+    if !lines.contains(&CHECK_DONE) {
+        return None;
+    }
+    let mut problems = Vec::new();
+    if lines.contains(&BINARY_MISSING) {
+        problems.push(format!("'{}' was not found on PATH", check.binary));
+    }
+    let missing_env: Vec<&str> = lines.iter().filter_map(|l| l.strip_prefix(ENV_MISSING)).collect();
+    if !missing_env.is_empty() {
+        problems.push(format!("required environment not set: {}", missing_env.join(", ")));
+    }
+    if problems.is_empty() {
+        None
+    } else {
+        Some(format!("{host}: AI CLI not launched — {}", problems.join("; ")))
+    }
+}
+
+/// Runs the check in the user's login shell over exec, so PATH matches what the PTY shell sees.
+/// Returns a message for the user when the AI CLI can't be launched.
+pub fn check_launch(sess: &Session, check: &AiCliCheck, host: &str) -> Option<String> {
+    let cmd = format!("${{SHELL:-/bin/sh}} -lic {} 2>/dev/null", shell_escape(&check_script(check)));
+    let output = run_exec(sess, &cmd, CHECK_TIMEOUT).ok()?;
+    parse_check_output(&output.stdout, check, host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_check_output() {
+        let check = AiCliCheck {
+            binary: "claude".into(),
+            required_env: vec!["ANTHROPIC_API_KEY".into()],
+        };
+        assert!(check_script(&check).contains("[ -n \"${ANTHROPIC_API_KEY:-}\" ]"));
+        assert_eq!(parse_check_output("motd\nmvwt-ai-cli:done\n", &check, "vm1"), None);
+        assert_eq!(parse_check_output("", &check, "vm1"), None, "inconclusive runs don't block");
+        assert_eq!(
+            parse_check_output(
+                "mvwt-ai-cli:binary-missing\nmvwt-ai-cli:env-missing:ANTHROPIC_API_KEY\nmvwt-ai-cli:done\n",
+                &check,
+                "vm1"
+            )
+            .as_deref(),
+            Some("vm1: AI CLI not launched — 'claude' was not found on PATH; required environment not set: ANTHROPIC_API_KEY")
+        );
+        assert_eq!(program_name("FOO=1 /opt/bin/aider --yes"), Some("/opt/bin/aider"));
+    }
+}
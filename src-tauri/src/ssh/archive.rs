@@ -0,0 +1,271 @@
+use serde::Serialize;
+use ssh2::Session;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::exec;
+use super::session::{shell_escape, SessionClient};
+use super::transfer::{self, ProgressReporter, RemoteFs, TransferRegistry};
+
+/// No output from tar for this long means the remote side is stuck.
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadFormat {
+    /// A gzip-compressed tarball streamed from `tar czf -` on the VM.
+    TarGz,
+    /// A plain directory mirrored over SFTP because tar is not available on the VM.
+    Directory,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DownloadStarted {
+    pub transfer_id: String,
+    pub format: DownloadFormat,
+    /// Local path the archive (or mirrored directory) is written to.
+    pub local_path: String,
+}
+
+fn tar_available(sess: &Session) -> bool {
+    exec::run_exec(sess, "command -v tar >/dev/null 2>&1", Duration::from_secs(5))
+        .is_ok_and(|o| o.exit_status == Some(0))
+}
+
+fn tar_command(remote: &Path) -> String {
+    let parent = remote.parent().filter(|p| !p.as_os_str().is_empty());
+    match (parent, remote.file_name()) {
+        (Some(parent), Some(name)) => format!(
+            "tar czf - -C {} {}",
+            shell_escape(&parent.to_string_lossy()),
+            shell_escape(&name.to_string_lossy())
+        ),
+        // Root-like paths ("/", ".") have no parent/name split; archive their contents instead.
+        _ => format!("tar czf - -C {} .", shell_escape(&remote.to_string_lossy())),
+    }
+}
+
+/// A stream that ended without an exit status was cut off, so the archive is likely truncated.
+fn tar_outcome(exit: Option<i32>, stderr: &[u8]) -> Result<(), String> {
+    match exit {
+        Some(0) => Ok(()),
+        Some(code) => {
+            let msg = String::from_utf8_lossy(stderr).trim().to_string();
+            Err(format!("tar exited with status {code}: {msg}"))
+        }
+        None => Err("tar stream ended without an exit status; the archive may be incomplete".to_string()),
+    }
+}
+
+fn stream_tar(
+    sess: &Session,
+    remote: &Path,
+    local: &Path,
+    reporter: &mut ProgressReporter,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let mut part = local.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let mut out = fs::File::create(&part).map_err(|e| format!("create {}: {e}", part.display()))?;
+    let mut channel = exec::open_exec_channel(sess, &tar_command(remote))?;
+
+    let mut buf = vec![0u8; 32 * 1024];
+    let mut stderr_text = Vec::new();
+    let mut last_data = Instant::now();
+
+    let result = (|| loop {
+        transfer::check_cancelled(cancel)?;
+        let mut progressed = false;
+
+        match channel.read(&mut buf) {
+            Ok(0) => break Ok(()),
+            Ok(n) => {
+                out.write_all(&buf[..n])
+                    .map_err(|e| format!("write {}: {e}", part.display()))?;
+                reporter.add_bytes(n as u64);
+                progressed = true;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => break Err(format!("read tar stream: {e}")),
+        }
+
+        // Drain stderr as we go so a chatty tar cannot fill the window and stall stdout.
+        match channel.stderr().read(&mut buf) {
+            Ok(n) if n > 0 => {
+                stderr_text.extend_from_slice(&buf[..n]);
+                progressed = true;
+            }
+            _ => {}
+        }
+
+        if progressed {
+            last_data = Instant::now();
+        } else if last_data.elapsed() >= STREAM_IDLE_TIMEOUT {
+            break Err("tar stream stalled".to_string());
+        } else {
+            thread::sleep(Duration::from_millis(5));
+        }
+    })();
+
+    let result = match result {
+        Ok(()) => tar_outcome(exec::finish_exec_channel(&mut channel), &stderr_text),
+        Err(e) => {
+            exec::abandon_exec_channel(channel);
+            Err(e)
+        }
+    };
+
+    drop(out);
+    match result {
+        Ok(()) => fs::rename(&part, local).map_err(|e| format!("rename {}: {e}", part.display())),
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            Err(e)
+        }
+    }
+}
+
+fn mirror_over_sftp(
+    sess: &Session,
+    remote: &Path,
+    local: &Path,
+    reporter: &mut ProgressReporter,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let fs_remote = RemoteFs::open(sess)?;
+    let (entries, skipped) = transfer::walk_remote(&fs_remote, remote)?;
+
+    reporter.progress.bytes_total = Some(entries.iter().map(|e| e.size).sum());
+    reporter.progress.files_total = Some(entries.iter().filter(|e| !e.is_dir).count() as u32);
+    reporter.progress.skipped = skipped;
+    reporter.emit_now();
+
+    let mut buf = vec![0u8; 32 * 1024];
+    for entry in &entries {
+        transfer::check_cancelled(cancel)?;
+        let src = transfer::remote_join(remote, &entry.rel);
+        let dst = transfer::join_rel(local, &entry.rel);
+        if entry.is_dir {
+            fs::create_dir_all(&dst).map_err(|e| format!("create {}: {e}", dst.display()))?;
+            continue;
+        }
+
+        reporter.start_file(&entry.rel);
+        let mut reader = fs_remote.open_read(&src)?;
+        let mut out = fs::File::create(&dst).map_err(|e| format!("create {}: {e}", dst.display()))?;
+        let label = src.display().to_string();
+        let copied = (|| loop {
+            transfer::check_cancelled(cancel)?;
+            let n = transfer::read_chunk(&mut reader, &mut buf, &label)?;
+            if n == 0 {
+                return Ok(());
+            }
+            out.write_all(&buf[..n])
+                .map_err(|e| format!("write {}: {e}", dst.display()))?;
+            reporter.add_bytes(n as u64);
+        })();
+        transfer::close_file(&mut reader);
+        if let Err(e) = copied {
+            drop(out);
+            let _ = fs::remove_file(&dst);
+            return Err(e);
+        }
+        reporter.progress.files_done += 1;
+    }
+    Ok(())
+}
+
+fn strip_archive_suffix(dest: &Path) -> PathBuf {
+    let s = dest.to_string_lossy();
+    for suffix in [".tar.gz", ".tgz"] {
+        if let Some(stem) = s.strip_suffix(suffix) {
+            return PathBuf::from(stem);
+        }
+    }
+    dest.to_path_buf()
+}
+
+/// Starts downloading a remote directory (or file) to `dest` in the background.
+/// Uses a streamed `tar czf -` when tar exists on the VM, else mirrors the tree over SFTP
+/// into a directory named after `dest` without its archive suffix.
+pub fn start_download_directory(
+    registry: &TransferRegistry,
+    client: &SessionClient,
+    remote_path: String,
+    dest: String,
+    app_handle: tauri::AppHandle,
+) -> Result<DownloadStarted, String> {
+    if remote_path.trim().is_empty() || dest.trim().is_empty() {
+        return Err("remote path and destination must not be empty".to_string());
+    }
+    let dest_path = PathBuf::from(&dest);
+    if let Some(parent) = dest_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if !parent.is_dir() {
+            return Err(format!("destination directory does not exist: {}", parent.display()));
+        }
+    }
+
+    let sess = client.lease_session().map_err(|e| e.to_string())?;
+    let (format, local_path) = if tar_available(&sess) {
+        (DownloadFormat::TarGz, dest_path)
+    } else {
+        (DownloadFormat::Directory, strip_archive_suffix(&dest_path))
+    };
+
+    let (transfer_id, cancel) = registry.register();
+    let registry = registry.clone();
+    let id = transfer_id.clone();
+    let started = DownloadStarted {
+        transfer_id,
+        format: format.clone(),
+        local_path: local_path.display().to_string(),
+    };
+
+    thread::spawn(move || {
+        let mut reporter = ProgressReporter::new(app_handle, &id);
+        reporter.emit_now();
+        let remote = PathBuf::from(remote_path.trim_end_matches('/'));
+        let remote = if remote.as_os_str().is_empty() {
+            PathBuf::from("/")
+        } else {
+            remote
+        };
+        let result = match format {
+            DownloadFormat::TarGz => stream_tar(&sess, &remote, &local_path, &mut reporter, &cancel),
+            DownloadFormat::Directory => mirror_over_sftp(&sess, &remote, &local_path, &mut reporter, &cancel),
+        };
+        reporter.finish(result, &cancel);
+        registry.finish(&id);
+    });
+
+    Ok(started)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_outcome() {
+        assert_eq!(tar_outcome(Some(0), b""), Ok(()));
+        assert_eq!(
+            tar_outcome(Some(2), b"tar: src: Cannot open\n"),
+            Err("tar exited with status 2: tar: src: Cannot open".to_string())
+        );
+        assert!(tar_outcome(None, b"").is_err(), "a cut-off stream is not a finished download");
+    }
+
+    #[test]
+    fn test_tar_command_and_local_names() {
+        assert_eq!(tar_command(Path::new("/srv/app")), "tar czf - -C '/srv' 'app'");
+        assert_eq!(tar_command(Path::new("/")), "tar czf - -C '/' .");
+        assert_eq!(strip_archive_suffix(Path::new("/tmp/app.tar.gz")), PathBuf::from("/tmp/app"));
+        assert_eq!(strip_archive_suffix(Path::new("/tmp/app.tgz")), PathBuf::from("/tmp/app"));
+        assert_eq!(strip_archive_suffix(Path::new("/tmp/app")), PathBuf::from("/tmp/app"));
+    }
+}
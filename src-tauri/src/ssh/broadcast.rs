@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+pub const BROADCAST_STATE_EVENT: &str = "broadcast-state";
+
+/// Which sessions are linked for broadcast input.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum BroadcastTarget {
+    #[default]
+    Off,
+    /// Every session in the active workset, including ones connected later.
+    All,
+    Sessions { session_ids: BTreeSet<String> },
+}
+
+impl BroadcastTarget {
+    pub fn includes(&self, session_id: &str) -> bool {
+        match self {
+            BroadcastTarget::Off => false,
+            BroadcastTarget::All => true,
+            BroadcastTarget::Sessions { session_ids } => session_ids.contains(session_id),
+        }
+    }
+
+    /// Resolves the target against the sessions that currently exist.
+    pub fn state<'a>(&self, active: impl Iterator<Item = &'a String>) -> BroadcastState {
+        let mut session_ids: Vec<String> = active.filter(|id| self.includes(id)).cloned().collect();
+        session_ids.sort();
+        BroadcastState {
+            enabled: !session_ids.is_empty(),
+            all: *self == BroadcastTarget::All,
+            session_ids,
+        }
+    }
+}
+
+/// Payload of the `broadcast-state` event: the panes whose input is currently linked.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct BroadcastState {
+    pub enabled: bool,
+    pub all: bool,
+    pub session_ids: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broadcast_state_resolves_against_active_sessions() {
+        let active = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let target = BroadcastTarget::Sessions {
+            session_ids: ["c", "a", "gone"].iter().map(|s| s.to_string()).collect(),
+        };
+        let state = target.state(active.iter());
+        assert!(state.enabled);
+        assert!(!state.all);
+        assert_eq!(state.session_ids, vec!["a".to_string(), "c".to_string()]);
+
+        let state = BroadcastTarget::All.state(active.iter());
+        assert!(state.all);
+        assert_eq!(state.session_ids.len(), 3);
+
+        assert!(!BroadcastTarget::Off.state(active.iter()).enabled);
+    }
+}
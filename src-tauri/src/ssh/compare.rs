@@ -0,0 +1,271 @@
+use serde::Serialize;
+
+/// Edit distance beyond which the middle section is reported as a bulk replace
+/// instead of a minimal diff. The trace holds about D^2 isizes, so this caps it near 8 MB.
+const MAX_EDIT_DISTANCE: usize = 1000;
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffLineKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// 1-based line number in file A (absent for inserts).
+    pub old_line: Option<u32>,
+    /// 1-based line number in file B (absent for deletes).
+    pub new_line: Option<u32>,
+    pub text: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RemoteFileSide {
+    pub session_id: String,
+    pub host: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub truncated: bool,
+    pub binary: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RemoteFileDiff {
+    pub a: RemoteFileSide,
+    pub b: RemoteFileSide,
+    /// Byte-for-byte equal (line diff ignores CRLF vs LF, this does not).
+    pub identical: bool,
+    /// Either side is binary; `lines` is empty in that case.
+    pub binary: bool,
+    pub lines: Vec<DiffLine>,
+    pub inserted: u32,
+    pub deleted: u32,
+    /// The files differed too much for a minimal diff; the changed region is shown as delete + insert.
+    pub approximate: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Equal(usize, usize),
+    Insert(usize),
+    Delete(usize),
+}
+
+/// Myers' O(ND) shortest edit script. Returns None if the distance exceeds `max_d`.
+fn myers(a: &[&str], b: &[&str], max_d: usize) -> Option<Vec<Op>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = None;
+    'search: for d in 0..=max.min(max_d) as isize {
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                found = Some(d);
+                break 'search;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+    let total_d = found?;
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..=total_d).rev() {
+        let k = x - y;
+        let prev = &trace[(d - 1) as usize];
+        let get = |k: isize| prev[(k + d - 1) as usize];
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if x == prev_x {
+            ops.push(Op::Insert((y - 1) as usize));
+        } else {
+            ops.push(Op::Delete((x - 1) as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    while x > 0 && y > 0 {
+        ops.push(Op::Equal((x - 1) as usize, (y - 1) as usize));
+        x -= 1;
+        y -= 1;
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+pub struct LineDiff {
+    pub lines: Vec<DiffLine>,
+    pub inserted: u32,
+    pub deleted: u32,
+    pub approximate: bool,
+}
+
+pub fn diff_lines(old: &str, new: &str) -> LineDiff {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // Common prefix/suffix are cheap to strip and keep the Myers search small.
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    let (mid_ops, approximate) = match myers(a_mid, b_mid, MAX_EDIT_DISTANCE) {
+        Some(ops) => (ops, false),
+        None => {
+            let mut ops: Vec<Op> = (0..a_mid.len()).map(Op::Delete).collect();
+            ops.extend((0..b_mid.len()).map(Op::Insert));
+            (ops, true)
+        }
+    };
+
+    let mut out = LineDiff {
+        lines: Vec::with_capacity(a.len().max(b.len())),
+        inserted: 0,
+        deleted: 0,
+        approximate,
+    };
+    let equal = |i: usize, j: usize| DiffLine {
+        kind: DiffLineKind::Equal,
+        old_line: Some(i as u32 + 1),
+        new_line: Some(j as u32 + 1),
+        text: a[i].to_string(),
+    };
+
+    for i in 0..prefix {
+        out.lines.push(equal(i, i));
+    }
+    for op in mid_ops {
+        match op {
+            Op::Equal(i, j) => out.lines.push(equal(prefix + i, prefix + j)),
+            Op::Delete(i) => {
+                out.deleted += 1;
+                out.lines.push(DiffLine {
+                    kind: DiffLineKind::Delete,
+                    old_line: Some((prefix + i) as u32 + 1),
+                    new_line: None,
+                    text: a[prefix + i].to_string(),
+                });
+            }
+            Op::Insert(j) => {
+                out.inserted += 1;
+                out.lines.push(DiffLine {
+                    kind: DiffLineKind::Insert,
+                    old_line: None,
+                    new_line: Some((prefix + j) as u32 + 1),
+                    text: b[prefix + j].to_string(),
+                });
+            }
+        }
+    }
+    for s in 0..suffix {
+        out.lines.push(equal(a.len() - suffix + s, b.len() - suffix + s));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(diff: &LineDiff) -> Vec<DiffLineKind> {
+        diff.lines.iter().map(|l| l.kind.clone()).collect()
+    }
+
+    #[test]
+    fn test_diff_lines_identical() {
+        let diff = diff_lines("a\nb\nc\n", "a\nb\nc\n");
+        assert_eq!(diff.inserted, 0);
+        assert_eq!(diff.deleted, 0);
+        assert!(diff.lines.iter().all(|l| l.kind == DiffLineKind::Equal));
+    }
+
+    #[test]
+    fn test_diff_lines_replace_middle() {
+        let diff = diff_lines("a\nb\nc\n", "a\nx\nc\n");
+        assert_eq!(
+            kinds(&diff),
+            vec![DiffLineKind::Equal, DiffLineKind::Delete, DiffLineKind::Insert, DiffLineKind::Equal]
+        );
+        assert_eq!(diff.lines[1].old_line, Some(2));
+        assert_eq!(diff.lines[2].new_line, Some(2));
+        assert_eq!(diff.lines[3].old_line, Some(3));
+        assert_eq!(diff.lines[3].new_line, Some(3));
+    }
+
+    #[test]
+    fn test_diff_lines_is_minimal() {
+        let old = "a\nb\nc\na\nb\nb\na\n";
+        let new = "c\nb\na\nb\na\nc\n";
+        let diff = diff_lines(old, new);
+        // Classic Myers example: shortest edit script has length 5.
+        assert_eq!(diff.inserted + diff.deleted, 5);
+
+        let rebuilt_new: Vec<&str> = diff
+            .lines
+            .iter()
+            .filter(|l| l.kind != DiffLineKind::Delete)
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(rebuilt_new, new.lines().collect::<Vec<_>>());
+        let rebuilt_old: Vec<&str> = diff
+            .lines
+            .iter()
+            .filter(|l| l.kind != DiffLineKind::Insert)
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(rebuilt_old, old.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_diff_lines_empty_sides() {
+        let diff = diff_lines("", "x\ny\n");
+        assert_eq!(kinds(&diff), vec![DiffLineKind::Insert, DiffLineKind::Insert]);
+        let diff = diff_lines("x\n", "");
+        assert_eq!(kinds(&diff), vec![DiffLineKind::Delete]);
+    }
+
+    #[test]
+    fn test_diff_lines_falls_back_past_max_distance() {
+        let old: String = (0..600).map(|i| format!("a{i}\n")).collect();
+        let new: String = (0..600).map(|i| format!("b{i}\n")).collect();
+        let diff = diff_lines(&old, &new);
+        assert!(diff.approximate);
+        assert_eq!((diff.deleted, diff.inserted), (600, 600));
+    }
+}
@@ -0,0 +1,320 @@
+use serde::Serialize;
+
+/// Bytes inspected when deciding whether content is binary.
+const SNIFF_BYTES: usize = 8000;
+
+/// Share of control bytes above which non-UTF-8 content is treated as binary rather than Latin-1.
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+    Utf8,
+    Utf16le,
+    Utf16be,
+    Latin1,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+    Mixed,
+    /// Text without any line break.
+    None,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContentInfo {
+    pub binary: bool,
+    /// Detected text encoding; absent for binary content.
+    pub encoding: Option<TextEncoding>,
+    /// The content starts with a byte order mark (stripped from decoded text).
+    pub has_bom: bool,
+    /// Absent for binary content.
+    pub line_ending: Option<LineEnding>,
+    /// Best guess from magic bytes, then the file extension.
+    pub mime: String,
+}
+
+fn detect_bom(bytes: &[u8]) -> Option<(TextEncoding, usize)> {
+    match bytes {
+        [0xef, 0xbb, 0xbf, ..] => Some((TextEncoding::Utf8, 3)),
+        [0xff, 0xfe, ..] => Some((TextEncoding::Utf16le, 2)),
+        [0xfe, 0xff, ..] => Some((TextEncoding::Utf16be, 2)),
+        _ => None,
+    }
+}
+
+/// BOM-less UTF-16 shows up as ASCII interleaved with NULs on one side.
+fn detect_utf16_without_bom(sniff: &[u8]) -> Option<TextEncoding> {
+    if sniff.len() < 4 {
+        return None;
+    }
+    let pairs = sniff.len() / 2;
+    let zero_even = sniff.iter().step_by(2).take(pairs).filter(|&&b| b == 0).count();
+    let zero_odd = sniff.iter().skip(1).step_by(2).take(pairs).filter(|&&b| b == 0).count();
+    let threshold = pairs * 9 / 10;
+    if zero_odd >= threshold && zero_even == 0 {
+        Some(TextEncoding::Utf16le)
+    } else if zero_even >= threshold && zero_odd == 0 {
+        Some(TextEncoding::Utf16be)
+    } else {
+        None
+    }
+}
+
+fn is_valid_utf8(bytes: &[u8], truncated: bool) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        // A multi-byte sequence cut by the read limit is not evidence of another encoding.
+        Err(e) => truncated && e.error_len().is_none(),
+    }
+}
+
+fn looks_like_latin1(sniff: &[u8]) -> bool {
+    if sniff.is_empty() {
+        return true;
+    }
+    let controls = sniff
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || b == 0x7f)
+        .count();
+    (controls as f64) / (sniff.len() as f64) <= MAX_CONTROL_RATIO
+}
+
+fn detect_line_ending(units: impl Iterator<Item = u16>) -> LineEnding {
+    let (mut lf, mut crlf, mut cr) = (0usize, 0usize, 0usize);
+    let mut prev_cr = false;
+    for u in units {
+        match u {
+            0x0a if prev_cr => {
+                crlf += 1;
+                cr -= 1;
+            }
+            0x0a => lf += 1,
+            0x0d => cr += 1,
+            _ => {}
+        }
+        prev_cr = u == 0x0d;
+    }
+    match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => LineEnding::None,
+        (true, false, false) => LineEnding::Lf,
+        (false, true, false) => LineEnding::Crlf,
+        (false, false, true) => LineEnding::Cr,
+        _ => LineEnding::Mixed,
+    }
+}
+
+fn utf16_units(bytes: &[u8], encoding: TextEncoding) -> impl Iterator<Item = u16> + '_ {
+    bytes.chunks_exact(2).map(move |pair| match encoding {
+        TextEncoding::Utf16be => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]]),
+    })
+}
+
+fn mime_from_magic(bytes: &[u8]) -> Option<&'static str> {
+    let mime = match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [0x00, 0x00, 0x01, 0x00, ..] => "image/x-icon",
+        [b'%', b'P', b'D', b'F', b'-', ..] => "application/pdf",
+        [b'P', b'K', 0x03, 0x04, ..] => "application/zip",
+        [0x1f, 0x8b, ..] => "application/gzip",
+        [b'B', b'Z', b'h', b'1'..=b'9', ..] => "application/x-bzip2",
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => "application/x-xz",
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => "application/zstd",
+        [0x7f, b'E', b'L', b'F', ..] => "application/x-executable",
+        [0x00, b'a', b's', b'm', ..] => "application/wasm",
+        [b'S', b'Q', b'L', b'i', b't', b'e', b' ', b'f', b'o', b'r', b'm', b'a', b't', ..] => "application/vnd.sqlite3",
+        _ if bytes.len() > 262 && &bytes[257..262] == b"ustar" => "application/x-tar",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn mime_from_extension(path: &str) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name {
+        "Dockerfile" | "Makefile" | "LICENSE" => return Some("text/plain"),
+        _ => {}
+    }
+    let ext = name.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase())?;
+    let mime = match ext.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "txt" | "log" | "conf" | "cfg" | "ini" | "env" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" | "mjs" | "cjs" => "text/javascript",
+        "ts" | "tsx" => "text/typescript",
+        "rs" => "text/x-rust",
+        "py" => "text/x-python",
+        "go" => "text/x-go",
+        "c" | "h" => "text/x-c",
+        "cpp" | "cc" | "hpp" => "text/x-c++",
+        "java" => "text/x-java",
+        "sh" | "bash" | "zsh" => "application/x-sh",
+        "json" => "application/json",
+        "toml" => "application/toml",
+        "yaml" | "yml" => "application/yaml",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "wasm" => "application/wasm",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Classifies file content read from a VM. `truncated` relaxes checks that a cut-off tail would fail.
+pub fn classify(path: &str, bytes: &[u8], truncated: bool) -> ContentInfo {
+    let sniff = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    let magic = mime_from_magic(bytes);
+
+    let (encoding, has_bom) = match detect_bom(bytes) {
+        Some((encoding, _)) => (Some(encoding), true),
+        None => {
+            let encoding = if let Some(utf16) = detect_utf16_without_bom(sniff) {
+                Some(utf16)
+            } else if sniff.contains(&0) || magic.is_some() {
+                None
+            } else if is_valid_utf8(bytes, truncated) {
+                Some(TextEncoding::Utf8)
+            } else if looks_like_latin1(sniff) {
+                Some(TextEncoding::Latin1)
+            } else {
+                None
+            };
+            (encoding, false)
+        }
+    };
+
+    let line_ending = encoding.map(|encoding| match encoding {
+        TextEncoding::Utf16le | TextEncoding::Utf16be => detect_line_ending(utf16_units(bytes, encoding)),
+        TextEncoding::Utf8 | TextEncoding::Latin1 => detect_line_ending(bytes.iter().map(|&b| u16::from(b))),
+    });
+
+    let mime = magic
+        .or_else(|| mime_from_extension(path))
+        .unwrap_or(if encoding.is_some() {
+            "text/plain"
+        } else {
+            "application/octet-stream"
+        });
+
+    ContentInfo {
+        binary: encoding.is_none(),
+        encoding,
+        has_bom,
+        line_ending,
+        mime: mime.to_string(),
+    }
+}
+
+/// Decodes text content per `info`, dropping the BOM and any partial character left by truncation.
+/// Returns None for binary content.
+pub fn decode_text(bytes: &[u8], info: &ContentInfo) -> Option<String> {
+    let encoding = info.encoding?;
+    let body = match (info.has_bom, detect_bom(bytes)) {
+        (true, Some((_, len))) => &bytes[len..],
+        _ => bytes,
+    };
+    let text = match encoding {
+        TextEncoding::Utf8 => match std::str::from_utf8(body) {
+            Ok(s) => s.to_string(),
+            Err(e) if e.error_len().is_none() => String::from_utf8_lossy(&body[..e.valid_up_to()]).into_owned(),
+            Err(_) => String::from_utf8_lossy(body).into_owned(),
+        },
+        TextEncoding::Utf16le | TextEncoding::Utf16be => char::decode_utf16(utf16_units(body, encoding))
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect(),
+        TextEncoding::Latin1 => body.iter().map(|&b| char::from(b)).collect(),
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_utf8_text() {
+        let info = classify("README.md", b"# Title\nbody\n", false);
+        assert!(!info.binary);
+        assert_eq!(info.encoding, Some(TextEncoding::Utf8));
+        assert_eq!(info.line_ending, Some(LineEnding::Lf));
+        assert_eq!(info.mime, "text/markdown");
+        assert!(!info.has_bom);
+    }
+
+    #[test]
+    fn test_classify_binary() {
+        let info = classify("logo", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", false);
+        assert!(info.binary);
+        assert_eq!(info.encoding, None);
+        assert_eq!(info.line_ending, None);
+        assert_eq!(info.mime, "image/png");
+
+        let info = classify("blob.bin", &[0x01, 0x02, 0x00, 0x03, 0x04, 0x05, 0x06], false);
+        assert!(info.binary);
+        assert_eq!(info.mime, "application/octet-stream");
+    }
+
+    #[test]
+    fn test_classify_truncated_utf8_tail() {
+        // "é" cut in half by the read limit.
+        let info = classify("a.txt", &[b'a', 0xc3], true);
+        assert_eq!(info.encoding, Some(TextEncoding::Utf8));
+        assert_eq!(decode_text(&[b'a', 0xc3], &info).as_deref(), Some("a"));
+
+        let info = classify("a.txt", &[b'a', 0xc3], false);
+        assert_eq!(info.encoding, Some(TextEncoding::Latin1));
+    }
+
+    #[test]
+    fn test_classify_utf16_with_and_without_bom() {
+        let le: Vec<u8> = [0xff, 0xfe]
+            .into_iter()
+            .chain("hi\r\nthere".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let info = classify("notes.txt", &le, false);
+        assert_eq!(info.encoding, Some(TextEncoding::Utf16le));
+        assert!(info.has_bom);
+        assert_eq!(info.line_ending, Some(LineEnding::Crlf));
+        assert_eq!(decode_text(&le, &info).as_deref(), Some("hi\r\nthere"));
+
+        let be: Vec<u8> = "plain text".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let info = classify("notes.txt", &be, false);
+        assert_eq!(info.encoding, Some(TextEncoding::Utf16be));
+        assert!(!info.has_bom);
+        assert_eq!(decode_text(&be, &info).as_deref(), Some("plain text"));
+    }
+
+    #[test]
+    fn test_classify_latin1_and_utf8_bom() {
+        let info = classify("old.txt", b"caf\xe9\r\n", false);
+        assert_eq!(info.encoding, Some(TextEncoding::Latin1));
+        assert_eq!(decode_text(b"caf\xe9\r\n", &info).as_deref(), Some("café\r\n"));
+
+        let info = classify("bom.csv", b"\xef\xbb\xbfa,b\rc,d\n", false);
+        assert_eq!(info.encoding, Some(TextEncoding::Utf8));
+        assert!(info.has_bom);
+        assert_eq!(info.line_ending, Some(LineEnding::Mixed));
+        assert_eq!(info.mime, "text/csv");
+        assert_eq!(decode_text(b"\xef\xbb\xbfa,b\rc,d\n", &info).as_deref(), Some("a,b\rc,d\n"));
+    }
+}
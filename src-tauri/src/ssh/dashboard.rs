@@ -0,0 +1,139 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tauri::Emitter;
+
+use super::git::{self, GitSummary};
+use super::session::{now_epoch, SessionClient, RESOURCE_POLL_INTERVAL};
+
+pub const GIT_DASHBOARD_EVENT: &str = "git-dashboard-update";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitDashboardEntry {
+    pub session_id: String,
+    pub host: String,
+    pub project_path: String,
+    pub summary: Option<GitSummary>,
+    pub error: Option<String>,
+    pub updated_epoch: u64,
+}
+
+#[derive(Default)]
+struct DashboardState {
+    /// Sessions to summarize, from spawn until their worker exits.
+    clients: HashMap<String, SessionClient>,
+    entries: HashMap<String, GitDashboardEntry>,
+    dirty: bool,
+}
+
+/// Latest git summary per session. A single emitter thread collects the summaries over
+/// leased sessions on the resource poll cadence and publishes the aggregate, so the
+/// frontend receives one event per cadence rather than one per VM.
+#[derive(Clone, Default)]
+pub struct GitDashboard {
+    state: Arc<Mutex<DashboardState>>,
+    /// Bumped on every start/stop; an emitter thread exits once it no longer owns the current generation.
+    emitter_generation: Arc<AtomicU64>,
+}
+
+impl GitDashboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(&self, client: SessionClient) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.clients.insert(client.id.clone(), client);
+    }
+
+    /// Ignores sessions removed while their summary was being collected.
+    fn update(&self, entry: GitDashboardEntry) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.clients.contains_key(&entry.session_id) {
+            state.entries.insert(entry.session_id.clone(), entry);
+            state.dirty = true;
+        }
+    }
+
+    pub fn remove(&self, session_id: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.clients.remove(session_id);
+        if state.entries.remove(session_id).is_some() {
+            state.dirty = true;
+        }
+    }
+
+    /// Summarizes every tracked session concurrently. Sessions that are not connected
+    /// right now keep their previous entry.
+    fn refresh(&self) {
+        let clients: Vec<SessionClient> = {
+            let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.clients.values().cloned().collect()
+        };
+        let entries: Vec<GitDashboardEntry> = thread::scope(|s| {
+            let handles: Vec<_> = clients.iter().map(|client| s.spawn(move || collect_entry(client))).collect();
+            handles.into_iter().filter_map(|h| h.join().ok().flatten()).collect()
+        });
+        for entry in entries {
+            self.update(entry);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<GitDashboardEntry> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries: Vec<GitDashboardEntry> = state.entries.values().cloned().collect();
+        entries.sort_by(|a, b| a.host.cmp(&b.host));
+        entries
+    }
+
+    fn take_if_dirty(&self) -> Option<Vec<GitDashboardEntry>> {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if !state.dirty {
+                return None;
+            }
+            state.dirty = false;
+        }
+        Some(self.snapshot())
+    }
+
+    /// Starts the aggregate emitter, replacing any previous one.
+    pub fn start_emitter(&self, app_handle: tauri::AppHandle) {
+        let generation = self.emitter_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let dashboard = self.clone();
+        thread::spawn(move || {
+            while dashboard.emitter_generation.load(Ordering::SeqCst) == generation {
+                dashboard.refresh();
+                if let Some(entries) = dashboard.take_if_dirty() {
+                    let _ = app_handle.emit(GIT_DASHBOARD_EVENT, entries);
+                }
+                thread::sleep(RESOURCE_POLL_INTERVAL);
+            }
+        });
+    }
+
+    pub fn stop_emitter(&self) {
+        self.emitter_generation.fetch_add(1, Ordering::SeqCst);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.entries.clear();
+        state.dirty = false;
+    }
+}
+
+fn collect_entry(client: &SessionClient) -> Option<GitDashboardEntry> {
+    let sess = client.lease_session().ok()?;
+    let (summary, error) = match git::summary(&sess, &client.project_path) {
+        Ok(s) => (Some(s), None),
+        Err(e) => (None, Some(e)),
+    };
+    Some(GitDashboardEntry {
+        session_id: client.id.clone(),
+        host: client.host_display.clone(),
+        project_path: client.project_path.clone(),
+        summary,
+        error,
+        updated_epoch: now_epoch(),
+    })
+}
@@ -0,0 +1,67 @@
+use serde::Serialize;
+
+use super::agent_state::{AgentState, AgentStatus};
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DispatchResult {
+    pub session_id: String,
+    pub host: Option<String>,
+    pub delivered: bool,
+    /// The agent state the decision was based on, when detection is available.
+    pub agent_state: Option<AgentState>,
+    pub error: Option<String>,
+}
+
+/// Wraps `text` in bracketed paste so the agent takes it as one input, newlines included.
+/// An embedded end marker would let the rest of the text be read as keystrokes, so it is dropped.
+pub fn bracketed_paste(text: &str, submit: bool) -> Vec<u8> {
+    let mut out = String::with_capacity(text.len() + 16);
+    out.push_str(PASTE_START);
+    out.push_str(&text.replace(PASTE_END, ""));
+    out.push_str(PASTE_END);
+    if submit {
+        out.push('\r');
+    }
+    out.into_bytes()
+}
+
+/// Why a prompt should not be sent to this agent right now, if anything.
+pub fn refusal(status: &AgentStatus) -> Option<String> {
+    if !status.detecting {
+        return None;
+    }
+    let reason = match status.state {
+        AgentState::Idle => return None,
+        AgentState::Unknown => "agent state not detected yet",
+        AgentState::Working => "agent is working",
+        AgentState::WaitingForApproval => "agent is waiting for approval",
+        AgentState::Errored => "agent reported an error",
+        AgentState::Exited => "agent is not running",
+    };
+    Some(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste_and_idle_gate() {
+        assert_eq!(
+            bracketed_paste("fix the build\nthen run tests\x1b[201~rm -rf", true),
+            b"\x1b[200~fix the build\nthen run testsrm -rf\x1b[201~\r"
+        );
+        let status = |state, detecting| AgentStatus {
+            state,
+            detecting,
+            ..AgentStatus::default()
+        };
+        assert_eq!(refusal(&status(AgentState::Idle, true)), None);
+        assert_eq!(refusal(&status(AgentState::Unknown, false)), None);
+        assert_eq!(refusal(&status(AgentState::Working, true)).as_deref(), Some("agent is working"));
+        assert!(refusal(&status(AgentState::Unknown, true)).is_some());
+    }
+}
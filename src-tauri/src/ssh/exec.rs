@@ -0,0 +1,285 @@
+use serde::Serialize;
+use ssh2::{Channel, Session};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use super::session::{shell_escape, SessionClient};
+use super::transfer::retry_would_block;
+use crate::workset::is_valid_env_name;
+
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A channel whose command already finished closes right away; don't hold the caller longer.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub const DEFAULT_EXEC_TIMEOUT: Duration = Duration::from_secs(30);
+pub const MAX_EXEC_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Per-stream cap on captured output; the rest is read and discarded so the command can finish.
+const MAX_EXEC_OUTPUT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Opens a session channel and starts `cmd` on it. Works on leased (non-blocking) sessions.
+pub(crate) fn open_exec_channel(sess: &Session, cmd: &str) -> Result<Channel, String> {
+    let mut channel = retry_would_block(OPEN_TIMEOUT, || sess.channel_session())
+        .map_err(|e| format!("channel_session: {e}"))?;
+    retry_would_block(OPEN_TIMEOUT, || channel.exec(cmd)).map_err(|e| format!("exec {cmd}: {e}"))?;
+    Ok(channel)
+}
+
+/// Waits briefly for the remote side to close and returns the exit status, if one was sent.
+pub(crate) fn finish_exec_channel(channel: &mut Channel) -> Option<i32> {
+    let _ = retry_would_block(CLOSE_TIMEOUT, || channel.close());
+    let _ = retry_would_block(CLOSE_TIMEOUT, || channel.wait_close());
+    channel.exit_status().ok()
+}
+
+/// Gives up on a command that is still running: sends EOF and a close request without
+/// waiting for either, so a stuck command can't hold the session's thread.
+pub(crate) fn abandon_exec_channel(mut channel: Channel) {
+    let _ = channel.send_eof();
+    let _ = channel.close();
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// Absent when the command timed out or the server sent no exit status.
+    pub exit_status: Option<i32>,
+    pub duration_ms: u64,
+    pub timed_out: bool,
+    /// stdout or stderr exceeded the capture limit and was cut.
+    pub truncated: bool,
+}
+
+/// `~` and `~/…` keep working as home-relative paths; everything else is quoted literally.
+pub(crate) fn cd_target(cwd: &str) -> String {
+    match cwd {
+        "~" => "~".to_string(),
+        _ => match cwd.strip_prefix("~/") {
+            Some(rest) => format!("~/{}", shell_escape(rest)),
+            None => shell_escape(cwd),
+        },
+    }
+}
+
+/// Wraps `command` so it runs in `cwd` with `env` exported. Env is applied in the command
+/// itself because most sshd configs reject `setenv` requests outside `AcceptEnv`.
+pub fn build_exec_command(
+    command: &str,
+    cwd: Option<&str>,
+    env: &BTreeMap<String, String>,
+) -> Result<String, String> {
+    let mut out = String::new();
+    if !env.is_empty() {
+        out.push_str("export");
+        for (name, value) in env {
+            if !is_valid_env_name(name) {
+                return Err(format!("invalid environment variable name: {name}"));
+            }
+            out.push_str(&format!(" {name}={}", shell_escape(value)));
+        }
+        out.push_str("; ");
+    }
+    if let Some(cwd) = cwd.map(str::trim).filter(|c| !c.is_empty()) {
+        out.push_str(&format!("cd {} && ", cd_target(cwd)));
+    }
+    out.push_str("{\n");
+    out.push_str(command);
+    out.push_str("\n}");
+    Ok(out)
+}
+
+fn capture(into: &mut Vec<u8>, data: &[u8], truncated: &mut bool) {
+    let room = MAX_EXEC_OUTPUT_BYTES.saturating_sub(into.len());
+    if data.len() > room {
+        *truncated = true;
+    }
+    into.extend_from_slice(&data[..data.len().min(room)]);
+}
+
+/// Runs `cmd` on its own exec channel, keeping stdout and stderr apart and reporting the exit status.
+/// On timeout the channel is closed and whatever output arrived so far is returned.
+pub(crate) fn run_exec(sess: &Session, cmd: &str, timeout: Duration) -> Result<ExecOutput, String> {
+    let started = Instant::now();
+    let deadline = started + timeout;
+    let mut channel = open_exec_channel(sess, cmd)?;
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut stdout_open = true;
+    let mut stderr_open = true;
+    let mut buf = [0u8; 8192];
+    let mut timed_out = false;
+    let mut truncated = false;
+
+    while stdout_open || stderr_open {
+        let mut progressed = false;
+        if stdout_open {
+            match channel.read(&mut buf) {
+                Ok(0) => stdout_open = false,
+                Ok(n) => {
+                    capture(&mut stdout, &buf[..n], &mut truncated);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("read stdout: {e}")),
+            }
+        }
+        if stderr_open {
+            match channel.stderr().read(&mut buf) {
+                Ok(0) => stderr_open = false,
+                Ok(n) => {
+                    capture(&mut stderr, &buf[..n], &mut truncated);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("read stderr: {e}")),
+            }
+        }
+        if Instant::now() >= deadline {
+            timed_out = stdout_open || stderr_open;
+            break;
+        }
+        if !progressed {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    let exit_status = if timed_out {
+        abandon_exec_channel(channel);
+        None
+    } else {
+        finish_exec_channel(&mut channel)
+    };
+    Ok(ExecOutput {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_status,
+        duration_ms: started.elapsed().as_millis() as u64,
+        timed_out,
+        truncated,
+    })
+}
+
+/// Runs `cmd` and returns its stdout when it exits with status 0. A timeout or any other exit
+/// is an error carrying the command's stderr, or its exit status when stderr is empty.
+pub(crate) fn run_checked(sess: &Session, cmd: &str, timeout: Duration) -> Result<String, String> {
+    let output = run_exec(sess, cmd, timeout)?;
+    if output.timed_out {
+        return Err(format!("command timed out after {}s", timeout.as_secs()));
+    }
+    match output.exit_status {
+        Some(0) => Ok(output.stdout),
+        _ if !output.stderr.trim().is_empty() => Err(output.stderr.trim().to_string()),
+        Some(code) => Err(format!("command exited with status {code}")),
+        None => Err("command ended without an exit status".to_string()),
+    }
+}
+
+/// Runs one command on a single session over a leased exec channel, off the session's worker thread.
+pub fn exec_on_session(
+    client: &SessionClient,
+    command: &str,
+    cwd: Option<&str>,
+    env: &BTreeMap<String, String>,
+    timeout: Duration,
+) -> Result<ExecOutput, String> {
+    let wrapped = build_exec_command(command, cwd, env)?;
+    let sess = client.lease_session().map_err(|e| e.to_string())?;
+    run_exec(&sess, &wrapped, timeout)
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HostExecResult {
+    pub session_id: String,
+    pub host: String,
+    pub output: Option<ExecOutput>,
+    /// Set when the command could not be started on this host.
+    pub error: Option<String>,
+}
+
+/// Runs the same command on every session concurrently over separate exec channels.
+pub fn run_on_sessions(clients: &[SessionClient], command: &str, timeout: Duration) -> Vec<HostExecResult> {
+    let mut results: Vec<HostExecResult> = std::thread::scope(|s| {
+        let handles: Vec<_> = clients
+            .iter()
+            .map(|client| {
+                let handle = s.spawn(move || {
+                    client
+                        .lease_session()
+                        .map_err(|e| e.to_string())
+                        .and_then(|sess| run_exec(&sess, command, timeout))
+                });
+                (client, handle)
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|(client, h)| {
+                let result = h.join().unwrap_or_else(|_| Err("exec thread panicked".to_string()));
+                let (output, error) = match result {
+                    Ok(output) => (Some(output), None),
+                    Err(e) => (None, Some(e)),
+                };
+                HostExecResult {
+                    session_id: client.id.clone(),
+                    host: client.host_display.clone(),
+                    output,
+                    error,
+                }
+            })
+            .collect()
+    });
+
+    results.sort_by(|a, b| a.host.cmp(&b.host));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_exec_command_plain() {
+        let cmd = build_exec_command("ls -la", None, &BTreeMap::new()).unwrap();
+        assert_eq!(cmd, "{\nls -la\n}");
+    }
+
+    #[test]
+    fn test_build_exec_command_cwd_and_env() {
+        let env: BTreeMap<String, String> = [
+            ("B".to_string(), "two words".to_string()),
+            ("A_1".to_string(), "it's".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let cmd = build_exec_command("make test", Some("~/my app"), &env).unwrap();
+        assert_eq!(
+            cmd,
+            "export A_1='it'\\''s' B='two words'; cd ~/'my app' && {\nmake test\n}"
+        );
+        let cmd = build_exec_command("pwd", Some("/srv/x"), &BTreeMap::new()).unwrap();
+        assert_eq!(cmd, "cd '/srv/x' && {\npwd\n}");
+    }
+
+    #[test]
+    fn test_build_exec_command_rejects_bad_env_name() {
+        let env: BTreeMap<String, String> = [("1BAD".to_string(), "x".to_string())].into_iter().collect();
+        assert!(build_exec_command("true", None, &env).is_err());
+        let env: BTreeMap<String, String> = [("A;rm".to_string(), "x".to_string())].into_iter().collect();
+        assert!(build_exec_command("true", None, &env).is_err());
+    }
+
+    #[test]
+    fn test_capture_truncates() {
+        let mut out = vec![0u8; MAX_EXEC_OUTPUT_BYTES - 2];
+        let mut truncated = false;
+        capture(&mut out, b"abcd", &mut truncated);
+        assert!(truncated);
+        assert_eq!(out.len(), MAX_EXEC_OUTPUT_BYTES);
+        assert_eq!(&out[out.len() - 2..], b"ab");
+    }
+}
@@ -0,0 +1,717 @@
+use serde::Serialize;
+use ssh2::Session;
+use std::time::Duration;
+
+use super::exec;
+use super::session::{shell_escape, FileEntry};
+
+/// Upper bound on `git diff` output pulled over exec; larger diffs are cut and flagged as truncated.
+pub const MAX_DIFF_BYTES: usize = 2 * 1024 * 1024;
+
+const FIELD_SEP: char = '\u{1f}';
+const RECORD_SEP: char = '\u{1e}';
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GitChangeKind {
+    Modified,
+    Added,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+    Conflicted,
+    Untracked,
+    Ignored,
+}
+
+impl GitChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitChangeKind::Modified => "modified",
+            GitChangeKind::Added => "added",
+            GitChangeKind::Deleted => "deleted",
+            GitChangeKind::Renamed => "renamed",
+            GitChangeKind::Copied => "copied",
+            GitChangeKind::TypeChanged => "type_changed",
+            GitChangeKind::Conflicted => "conflicted",
+            GitChangeKind::Untracked => "untracked",
+            GitChangeKind::Ignored => "ignored",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GitBranchInfo {
+    /// Branch name, or None when HEAD is detached.
+    pub head: Option<String>,
+    /// HEAD commit, or None before the first commit.
+    pub oid: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GitFileChange {
+    pub path: String,
+    pub orig_path: Option<String>,
+    pub kind: GitChangeKind,
+    /// Porcelain XY codes ('.' means unchanged on that side).
+    pub index_status: String,
+    pub worktree_status: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitStatus {
+    pub repo_root: String,
+    pub branch: GitBranchInfo,
+    pub files: Vec<GitFileChange>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GitDiffLineKind {
+    Context,
+    Add,
+    Delete,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitDiffLine {
+    pub kind: GitDiffLineKind,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    pub text: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitDiffHunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Trailing context git prints after the @@ range (usually the enclosing function).
+    pub section: String,
+    pub lines: Vec<GitDiffLine>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitDiffFile {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub kind: GitChangeKind,
+    pub binary: bool,
+    pub hunks: Vec<GitDiffHunk>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitDiff {
+    pub files: Vec<GitDiffFile>,
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GitCommit {
+    pub hash: String,
+    pub short_hash: String,
+    pub author_name: String,
+    pub author_email: String,
+    pub timestamp_epoch: u64,
+    pub subject: String,
+}
+
+/// Compact per-repo state for the cross-VM dashboard.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GitSummary {
+    /// Branch name, or None when HEAD is detached.
+    pub branch: Option<String>,
+    pub head_commit: Option<String>,
+    pub dirty_files: u32,
+    pub last_commit_epoch: Option<u64>,
+}
+
+// ── Command builders ──
+
+pub fn status_command(repo_path: &str) -> String {
+    let p = exec::cd_target(repo_path);
+    format!("git -C {p} rev-parse --show-toplevel && git -C {p} status --porcelain=v2 --branch -z")
+}
+
+pub fn diff_command(repo_path: &str, path: Option<&str>, staged: bool) -> String {
+    let p = exec::cd_target(repo_path);
+    let cached = if staged { " --cached" } else { "" };
+    let filter = match path {
+        Some(f) if !f.trim().is_empty() => format!(" -- {}", shell_escape(f)),
+        _ => String::new(),
+    };
+    format!("git -C {p} --no-pager diff --no-color --no-ext-diff -M{cached}{filter}")
+}
+
+pub fn log_command(repo_path: &str, limit: u32) -> String {
+    format!(
+        "git -C {} --no-pager log -n {} --format='%H%x1f%h%x1f%an%x1f%ae%x1f%at%x1f%s%x1e'",
+        exec::cd_target(repo_path),
+        limit.clamp(1, 500)
+    )
+}
+
+/// Prints the directory's prefix inside its repo on the first line, followed by
+/// NUL-separated porcelain entries limited to that directory. Prints nothing outside a repo.
+pub fn directory_status_command(dir: &str) -> String {
+    format!(
+        "cd {} 2>/dev/null && git rev-parse --show-prefix 2>/dev/null && git status --porcelain=v2 -z --untracked-files=normal -- . 2>/dev/null",
+        exec::cd_target(dir)
+    )
+}
+
+pub fn summary_command(repo_path: &str) -> String {
+    format!(
+        "cd {} 2>/dev/null || {{ echo 'no such directory' >&2; exit 1; }}; \
+         git rev-parse --is-inside-work-tree >/dev/null 2>&1 || {{ echo 'not a git repository' >&2; exit 1; }}; \
+         printf 'branch=%s\\n' \"$(git symbolic-ref --short -q HEAD)\"; \
+         printf 'head=%s\\n' \"$(git rev-parse --short HEAD 2>/dev/null)\"; \
+         printf 'dirty=%s\\n' \"$(git status --porcelain 2>/dev/null | wc -l)\"; \
+         printf 'last_commit=%s\\n' \"$(git log -1 --format=%ct 2>/dev/null)\"",
+        exec::cd_target(repo_path)
+    )
+}
+
+// ── Parsers ──
+
+fn change_kind(x: char, y: char) -> GitChangeKind {
+    if x == 'A' || y == 'A' {
+        GitChangeKind::Added
+    } else if x == 'D' || y == 'D' {
+        GitChangeKind::Deleted
+    } else if x == 'T' || y == 'T' {
+        GitChangeKind::TypeChanged
+    } else {
+        GitChangeKind::Modified
+    }
+}
+
+/// Parses `git status --porcelain=v2 --branch -z` output.
+pub fn parse_porcelain_v2(output: &str) -> (GitBranchInfo, Vec<GitFileChange>) {
+    let mut branch = GitBranchInfo::default();
+    let mut files = Vec::new();
+    let mut records = output.split('\0').filter(|r| !r.is_empty());
+
+    while let Some(rec) = records.next() {
+        if let Some(header) = rec.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.oid" if value != "(initial)" => branch.oid = Some(value.to_string()),
+                "branch.head" if value != "(detached)" => branch.head = Some(value.to_string()),
+                "branch.upstream" => branch.upstream = Some(value.to_string()),
+                "branch.ab" => {
+                    for part in value.split_whitespace() {
+                        if let Some(n) = part.strip_prefix('+') {
+                            branch.ahead = n.parse().unwrap_or(0);
+                        } else if let Some(n) = part.strip_prefix('-') {
+                            branch.behind = n.parse().unwrap_or(0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let (tag, rest) = rec.split_at(rec.chars().next().map_or(0, char::len_utf8));
+        let rest = rest.strip_prefix(' ').unwrap_or(rest);
+        match tag {
+            "1" | "2" | "u" => {
+                // Fixed field counts before the path: 7 for ordinary, 8 for rename/copy, 9 for unmerged.
+                let fixed = match tag {
+                    "1" => 7,
+                    "2" => 8,
+                    _ => 9,
+                };
+                let fields: Vec<&str> = rest.splitn(fixed + 1, ' ').collect();
+                if fields.len() != fixed + 1 {
+                    continue;
+                }
+                let mut xy = fields[0].chars();
+                let x = xy.next().unwrap_or('.');
+                let y = xy.next().unwrap_or('.');
+                let path = fields[fixed].to_string();
+
+                let (kind, orig_path) = match tag {
+                    "1" => (change_kind(x, y), None),
+                    "2" => {
+                        let kind = if fields[7].starts_with('C') {
+                            GitChangeKind::Copied
+                        } else {
+                            GitChangeKind::Renamed
+                        };
+                        (kind, records.next().map(str::to_string))
+                    }
+                    _ => (GitChangeKind::Conflicted, None),
+                };
+
+                files.push(GitFileChange {
+                    path,
+                    orig_path,
+                    kind,
+                    index_status: x.to_string(),
+                    worktree_status: y.to_string(),
+                });
+            }
+            "?" | "!" => {
+                let kind = if tag == "?" {
+                    GitChangeKind::Untracked
+                } else {
+                    GitChangeKind::Ignored
+                };
+                files.push(GitFileChange {
+                    path: rest.to_string(),
+                    orig_path: None,
+                    kind,
+                    index_status: tag.to_string(),
+                    worktree_status: tag.to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    (branch, files)
+}
+
+pub fn parse_summary_output(output: &str) -> GitSummary {
+    let non_empty = |v: &str| Some(v.trim().to_string()).filter(|s| !s.is_empty());
+    let mut summary = GitSummary::default();
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key {
+            "branch" => summary.branch = non_empty(value),
+            "head" => summary.head_commit = non_empty(value),
+            "dirty" => summary.dirty_files = value.trim().parse().unwrap_or(0),
+            "last_commit" => summary.last_commit_epoch = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+    summary
+}
+
+pub fn parse_status_output(output: &str) -> GitStatus {
+    let (root, rest) = output.split_once('\n').unwrap_or((output, ""));
+    let (branch, files) = parse_porcelain_v2(rest);
+    GitStatus {
+        repo_root: root.trim().to_string(),
+        branch,
+        files,
+    }
+}
+
+fn parse_range(spec: &str) -> (u32, u32) {
+    match spec.split_once(',') {
+        Some((start, len)) => (start.parse().unwrap_or(0), len.parse().unwrap_or(0)),
+        None => (spec.parse().unwrap_or(0), 1),
+    }
+}
+
+fn parse_hunk_header(line: &str) -> Option<GitDiffHunk> {
+    let body = line.strip_prefix("@@ -")?;
+    let (ranges, section) = body.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let (old_start, old_lines) = parse_range(old);
+    let (new_start, new_lines) = parse_range(new);
+    Some(GitDiffHunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        section: section.trim().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+fn diff_path(raw: &str, prefix: &str) -> Option<String> {
+    let raw = raw.trim_end();
+    if raw == "/dev/null" {
+        return None;
+    }
+    Some(raw.strip_prefix(prefix).unwrap_or(raw).to_string())
+}
+
+/// Parses unified `git diff` output into files and hunks.
+pub fn parse_unified_diff(output: &str) -> Vec<GitDiffFile> {
+    let mut files: Vec<GitDiffFile> = Vec::new();
+    let mut old_no: u32 = 0;
+    let mut new_no: u32 = 0;
+
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            // Fallback paths from the header; refined by ---/+++ and rename lines when present.
+            let (a, b) = header.split_once(" b/").unwrap_or((header, ""));
+            files.push(GitDiffFile {
+                old_path: Some(a.strip_prefix("a/").unwrap_or(a).to_string()),
+                new_path: Some(b.to_string()),
+                kind: GitChangeKind::Modified,
+                binary: false,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        if let Some(hunk) = file.hunks.last_mut() {
+            if let Some(text) = line.strip_prefix('+') {
+                hunk.lines.push(GitDiffLine {
+                    kind: GitDiffLineKind::Add,
+                    old_line: None,
+                    new_line: Some(new_no),
+                    text: text.to_string(),
+                });
+                new_no += 1;
+                continue;
+            }
+            if let Some(text) = line.strip_prefix('-') {
+                hunk.lines.push(GitDiffLine {
+                    kind: GitDiffLineKind::Delete,
+                    old_line: Some(old_no),
+                    new_line: None,
+                    text: text.to_string(),
+                });
+                old_no += 1;
+                continue;
+            }
+            if let Some(text) = line.strip_prefix(' ') {
+                hunk.lines.push(GitDiffLine {
+                    kind: GitDiffLineKind::Context,
+                    old_line: Some(old_no),
+                    new_line: Some(new_no),
+                    text: text.to_string(),
+                });
+                old_no += 1;
+                new_no += 1;
+                continue;
+            }
+            if line.starts_with('\\') {
+                // "\ No newline at end of file"
+                continue;
+            }
+        }
+
+        if line.starts_with("@@ ") {
+            if let Some(hunk) = parse_hunk_header(line) {
+                old_no = hunk.old_start;
+                new_no = hunk.new_start;
+                file.hunks.push(hunk);
+            }
+        } else if let Some(p) = line.strip_prefix("--- ") {
+            file.old_path = diff_path(p, "a/");
+        } else if let Some(p) = line.strip_prefix("+++ ") {
+            file.new_path = diff_path(p, "b/");
+        } else if line.starts_with("new file mode") {
+            file.kind = GitChangeKind::Added;
+            file.old_path = None;
+        } else if line.starts_with("deleted file mode") {
+            file.kind = GitChangeKind::Deleted;
+            file.new_path = None;
+        } else if let Some(p) = line.strip_prefix("rename from ") {
+            file.kind = GitChangeKind::Renamed;
+            file.old_path = Some(p.to_string());
+        } else if let Some(p) = line.strip_prefix("rename to ") {
+            file.new_path = Some(p.to_string());
+        } else if let Some(p) = line.strip_prefix("copy from ") {
+            file.kind = GitChangeKind::Copied;
+            file.old_path = Some(p.to_string());
+        } else if let Some(p) = line.strip_prefix("copy to ") {
+            file.new_path = Some(p.to_string());
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        }
+    }
+
+    files
+}
+
+pub fn parse_diff_output(output: &str) -> GitDiff {
+    let truncated = output.len() > MAX_DIFF_BYTES;
+    let body = if truncated {
+        // Drop the partial last line so a cut hunk does not produce a garbage entry.
+        let mut end = MAX_DIFF_BYTES;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        let cut = &output[..end];
+        cut.rsplit_once('\n').map(|(head, _)| head).unwrap_or(cut)
+    } else {
+        output
+    };
+    GitDiff {
+        files: parse_unified_diff(body),
+        truncated,
+    }
+}
+
+pub fn parse_log_output(output: &str) -> Vec<GitCommit> {
+    let mut commits = Vec::new();
+    for record in output.split(RECORD_SEP) {
+        let record = record.trim_start_matches('\n');
+        if record.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = record.splitn(6, FIELD_SEP).collect();
+        if fields.len() != 6 {
+            continue;
+        }
+        commits.push(GitCommit {
+            hash: fields[0].to_string(),
+            short_hash: fields[1].to_string(),
+            author_name: fields[2].to_string(),
+            author_email: fields[3].to_string(),
+            timestamp_epoch: fields[4].parse().unwrap_or(0),
+            subject: fields[5].to_string(),
+        });
+    }
+    commits
+}
+
+/// Turns the outcome of `log_command` into commits. An empty repository has no log;
+/// report it as such rather than as an error.
+pub fn log_result(result: Result<String, String>) -> Result<Vec<GitCommit>, String> {
+    match result {
+        Err(e) if e.contains("does not have any commits") => Ok(Vec::new()),
+        other => other.map(|out| parse_log_output(&out)),
+    }
+}
+
+/// Sets `git_status` on directory listing entries from `directory_status_command` output.
+/// Directories that contain changes are reported as modified unless git reports the directory itself.
+pub fn annotate_entries(entries: &mut [FileEntry], output: &str) {
+    let Some((prefix, rest)) = output.split_once('\n') else {
+        return;
+    };
+    let prefix = prefix.trim();
+    let (_, changes) = parse_porcelain_v2(rest);
+
+    for change in changes {
+        let Some(rel) = change.path.strip_prefix(prefix) else {
+            continue;
+        };
+        let rel = rel.trim_end_matches('/');
+        let (name, nested) = match rel.split_once('/') {
+            Some((first, _)) => (first, true),
+            None => (rel, false),
+        };
+
+        let Some(entry) = entries.iter_mut().find(|e| e.name == name) else {
+            continue;
+        };
+        if nested && entry.is_dir {
+            if entry.git_status.is_none() {
+                entry.git_status = Some(GitChangeKind::Modified.as_str().to_string());
+            }
+        } else {
+            entry.git_status = Some(change.kind.as_str().to_string());
+        }
+    }
+}
+
+// ── Remote runners ──
+// Each runs over its own exec channel on a leased session, so a slow or locked repo
+// never stalls the terminal it belongs to.
+
+pub fn status(sess: &Session, repo_path: &str) -> Result<GitStatus, String> {
+    exec::run_checked(sess, &status_command(repo_path), Duration::from_secs(10)).map(|o| parse_status_output(&o))
+}
+
+pub fn diff(sess: &Session, repo_path: &str, path: Option<&str>, staged: bool) -> Result<GitDiff, String> {
+    exec::run_checked(sess, &diff_command(repo_path, path, staged), Duration::from_secs(20))
+        .map(|o| parse_diff_output(&o))
+}
+
+pub fn log(sess: &Session, repo_path: &str, limit: u32) -> Result<Vec<GitCommit>, String> {
+    log_result(exec::run_checked(sess, &log_command(repo_path, limit), Duration::from_secs(10)))
+}
+
+pub fn summary(sess: &Session, repo_path: &str) -> Result<GitSummary, String> {
+    exec::run_checked(sess, &summary_command(repo_path), Duration::from_secs(5)).map(|o| parse_summary_output(&o))
+}
+
+/// Best effort: listings outside a repo or on hosts without git stay unannotated.
+pub fn annotate_directory(sess: &Session, dir: &str, entries: &mut [FileEntry]) {
+    if let Ok(output) = exec::run_checked(sess, &directory_status_command(dir), Duration::from_secs(5)) {
+        annotate_entries(entries, &output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain_v2_branch_headers() {
+        let out = "# branch.oid 1234abcd\0# branch.head main\0# branch.upstream origin/main\0# branch.ab +2 -1\0";
+        let (branch, files) = parse_porcelain_v2(out);
+        assert_eq!(branch.head.as_deref(), Some("main"));
+        assert_eq!(branch.oid.as_deref(), Some("1234abcd"));
+        assert_eq!(branch.upstream.as_deref(), Some("origin/main"));
+        assert_eq!((branch.ahead, branch.behind), (2, 1));
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn test_parse_porcelain_v2_detached_initial() {
+        let (branch, _) = parse_porcelain_v2("# branch.oid (initial)\0# branch.head (detached)\0");
+        assert_eq!(branch.head, None);
+        assert_eq!(branch.oid, None);
+    }
+
+    #[test]
+    fn test_parse_porcelain_v2_entries() {
+        let out = concat!(
+            "1 .M N... 100644 100644 100644 aaa bbb src/main.rs\0",
+            "1 A. N... 000000 100644 100644 000 ccc new file.txt\0",
+            "2 R. N... 100644 100644 100644 ddd eee R100 lib/new.rs\0lib/old.rs\0",
+            "u UU N... 100644 100644 100644 100644 f1 f2 f3 conflict.rs\0",
+            "? scratch/\0",
+        );
+        let (_, files) = parse_porcelain_v2(out);
+        assert_eq!(files.len(), 5);
+        assert_eq!(files[0].path, "src/main.rs");
+        assert_eq!(files[0].kind, GitChangeKind::Modified);
+        assert_eq!(files[0].worktree_status, "M");
+        assert_eq!(files[1].path, "new file.txt");
+        assert_eq!(files[1].kind, GitChangeKind::Added);
+        assert_eq!(files[2].kind, GitChangeKind::Renamed);
+        assert_eq!(files[2].orig_path.as_deref(), Some("lib/old.rs"));
+        assert_eq!(files[3].kind, GitChangeKind::Conflicted);
+        assert_eq!(files[4].kind, GitChangeKind::Untracked);
+        assert_eq!(files[4].path, "scratch/");
+    }
+
+    #[test]
+    fn test_parse_status_output_root_and_entries() {
+        let status = parse_status_output("/srv/app\n# branch.head main\0? notes.txt\0");
+        assert_eq!(status.repo_root, "/srv/app");
+        assert_eq!(status.branch.head.as_deref(), Some("main"));
+        assert_eq!(status.files.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_summary_output() {
+        let summary = parse_summary_output("branch=feature/x\nhead=abc1234\ndirty=       3\nlast_commit=1700000000\n");
+        assert_eq!(summary.branch.as_deref(), Some("feature/x"));
+        assert_eq!(summary.head_commit.as_deref(), Some("abc1234"));
+        assert_eq!(summary.dirty_files, 3);
+        assert_eq!(summary.last_commit_epoch, Some(1_700_000_000));
+
+        let empty_repo = parse_summary_output("branch=main\nhead=\ndirty=0\nlast_commit=\n");
+        assert_eq!(empty_repo.head_commit, None);
+        assert_eq!(empty_repo.last_commit_epoch, None);
+    }
+
+    #[test]
+    fn test_parse_unified_diff_hunks_and_line_numbers() {
+        let out = "\
+diff --git a/src/a.rs b/src/a.rs
+index 111..222 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -3,3 +3,4 @@ fn main() {
+ let a = 1;
+-let b = 2;
++let b = 3;
++let c = 4;
+ }
+diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+";
+        let files = parse_unified_diff(out);
+        assert_eq!(files.len(), 2);
+
+        let a = &files[0];
+        assert_eq!(a.kind, GitChangeKind::Modified);
+        assert_eq!(a.hunks.len(), 1);
+        let h = &a.hunks[0];
+        assert_eq!((h.old_start, h.old_lines, h.new_start, h.new_lines), (3, 3, 3, 4));
+        assert_eq!(h.section, "fn main() {");
+        assert_eq!(h.lines.len(), 5);
+        assert_eq!(h.lines[1].kind, GitDiffLineKind::Delete);
+        assert_eq!(h.lines[1].old_line, Some(4));
+        assert_eq!(h.lines[3].kind, GitDiffLineKind::Add);
+        assert_eq!(h.lines[3].new_line, Some(5));
+        assert_eq!(h.lines[4].old_line, Some(5));
+        assert_eq!(h.lines[4].new_line, Some(6));
+
+        let n = &files[1];
+        assert_eq!(n.kind, GitChangeKind::Added);
+        assert_eq!(n.old_path, None);
+        assert_eq!(n.new_path.as_deref(), Some("new.txt"));
+        assert_eq!(n.hunks[0].lines[0].new_line, Some(1));
+    }
+
+    #[test]
+    fn test_parse_unified_diff_binary_and_rename() {
+        let out = "\
+diff --git a/img.png b/img.png
+index 111..222 100644
+Binary files a/img.png and b/img.png differ
+diff --git a/old.rs b/new.rs
+similarity index 100%
+rename from old.rs
+rename to new.rs
+";
+        let files = parse_unified_diff(out);
+        assert!(files[0].binary);
+        assert_eq!(files[1].kind, GitChangeKind::Renamed);
+        assert_eq!(files[1].old_path.as_deref(), Some("old.rs"));
+        assert_eq!(files[1].new_path.as_deref(), Some("new.rs"));
+    }
+
+    #[test]
+    fn test_parse_log_output() {
+        let out = "abc123\u{1f}abc\u{1f}Dev\u{1f}dev@example.com\u{1f}1700000000\u{1f}Fix bug\u{1e}\ndef456\u{1f}def\u{1f}Dev\u{1f}dev@example.com\u{1f}1690000000\u{1f}Initial\u{1e}\n";
+        let commits = parse_log_output(out);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].short_hash, "abc");
+        assert_eq!(commits[0].timestamp_epoch, 1_700_000_000);
+        assert_eq!(commits[1].subject, "Initial");
+    }
+
+    #[test]
+    fn test_commands_keep_home_relative_repo_paths() {
+        assert!(status_command("~/proj").starts_with("git -C ~/'proj' rev-parse"));
+        assert!(summary_command("~").starts_with("cd ~ 2>/dev/null"));
+        assert!(log_command("/srv/my app", 5).starts_with("git -C '/srv/my app' --no-pager log -n 5"));
+    }
+
+    #[test]
+    fn test_log_result_empty_repo_is_not_an_error() {
+        let empty = log_result(Err("fatal: your current branch 'main' does not have any commits yet".into()));
+        assert_eq!(empty, Ok(Vec::new()));
+        assert!(log_result(Err("fatal: not a git repository (or any of the parent directories): .git".into())).is_err());
+    }
+
+    #[test]
+    fn test_annotate_entries_marks_files_and_parent_dirs() {
+        let entry = |name: &str, is_dir: bool| FileEntry {
+            path: format!("/repo/src/{name}"),
+            name: name.to_string(),
+            is_dir,
+            size_bytes: 0,
+            mtime_epoch: None,
+            git_status: None,
+        };
+        let mut entries = vec![entry("a.rs", false), entry("nested", true), entry("clean.rs", false)];
+        let out = "src/\n1 .M N... 100644 100644 100644 a b src/a.rs\x001 .M N... 100644 100644 100644 a b src/nested/deep.rs\0";
+        annotate_entries(&mut entries, out);
+        assert_eq!(entries[0].git_status.as_deref(), Some("modified"));
+        assert_eq!(entries[1].git_status.as_deref(), Some("modified"));
+        assert_eq!(entries[2].git_status, None);
+    }
+}
@@ -0,0 +1,397 @@
+use serde::Serialize;
+use ssh2::Channel;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::Emitter;
+use uuid::Uuid;
+
+use super::exec;
+use super::session::{now_epoch, shell_escape, SessionClient};
+
+/// First stderr line of every job; carries the remote pid so the job can be killed.
+const PID_MARKER: &str = "mvwt-job-pid:";
+
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Exited,
+    Killed,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JobInfo {
+    pub job_id: String,
+    pub session_id: String,
+    pub host: String,
+    pub command: String,
+    pub started_epoch: u64,
+    pub state: JobState,
+    /// Remote pid of the job's shell, once reported.
+    pub pid: Option<u32>,
+    pub exit_status: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStream {
+    Stdout,
+    Stderr,
+}
+
+/// Payload of `job-output-{job_id}` events.
+#[derive(Serialize, Clone, Debug)]
+pub struct JobOutputChunk {
+    pub job_id: String,
+    pub stream: JobStream,
+    pub data: String,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    client: SessionClient,
+    kill: Arc<AtomicBool>,
+}
+
+/// Running jobs keyed by job id. Finished jobs are dropped; their final state goes out as `job-exited-{job_id}`.
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, JobEntry>>>,
+}
+
+/// Holds back a trailing partial UTF-8 sequence so multi-byte characters split across reads
+/// are not turned into replacement characters.
+#[derive(Default)]
+struct Utf8Carry {
+    pending: Vec<u8>,
+}
+
+impl Utf8Carry {
+    fn push(&mut self, data: &[u8]) -> String {
+        self.pending.extend_from_slice(data);
+        let keep = match std::str::from_utf8(&self.pending) {
+            Ok(_) => 0,
+            Err(e) if e.error_len().is_none() => self.pending.len() - e.valid_up_to(),
+            Err(_) => 0,
+        };
+        let rest = self.pending.split_off(self.pending.len() - keep);
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+        text
+    }
+
+    fn flush(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
+/// Splits the pid marker line off the start of the stderr stream.
+enum PidLine {
+    /// Still waiting for the first newline.
+    Pending,
+    Found(Option<u32>, Vec<u8>),
+}
+
+fn take_pid_line(buffer: &mut Vec<u8>) -> PidLine {
+    let Some(newline) = buffer.iter().position(|&b| b == b'\n') else {
+        return PidLine::Pending;
+    };
+    let line = String::from_utf8_lossy(&buffer[..newline]).into_owned();
+    match line.strip_prefix(PID_MARKER) {
+        Some(pid) => {
+            let rest = buffer.split_off(newline + 1);
+            buffer.clear();
+            PidLine::Found(pid.trim().parse().ok(), rest)
+        }
+        // No marker (e.g. a login banner got in first); pass everything through.
+        None => PidLine::Found(None, std::mem::take(buffer)),
+    }
+}
+
+fn job_command(command: &str, cwd: Option<&str>, env: &BTreeMap<String, String>) -> Result<String, String> {
+    let wrapped = exec::build_exec_command(command, cwd, env)?;
+    // `exec` keeps the reported pid as the pid of the shell that runs the job.
+    Ok(format!(
+        "printf '{PID_MARKER}%s\\n' $$ >&2; exec sh -c {}",
+        shell_escape(&wrapped)
+    ))
+}
+
+fn kill_command(pid: u32) -> String {
+    format!("pkill -TERM -P {pid} 2>/dev/null; kill -TERM {pid} 2>/dev/null; true")
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn set_pid(&self, job_id: &str, pid: u32) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = jobs.get_mut(job_id) {
+            entry.info.pid = Some(pid);
+        }
+    }
+
+    fn finish(&self, job_id: &str) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.remove(job_id);
+    }
+
+    /// Running jobs, optionally limited to one session, oldest first.
+    pub fn list(&self, session_id: Option<&str>) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<JobInfo> = jobs
+            .values()
+            .filter(|e| session_id.is_none_or(|id| e.info.session_id == id))
+            .map(|e| e.info.clone())
+            .collect();
+        out.sort_by(|a, b| a.started_epoch.cmp(&b.started_epoch).then_with(|| a.job_id.cmp(&b.job_id)));
+        out
+    }
+
+    /// Signals the job's remote process tree and stops streaming. Returns false for unknown jobs.
+    pub fn kill(&self, job_id: &str) -> bool {
+        let (client, pid, flag) = {
+            let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            match jobs.get(job_id) {
+                Some(e) => (e.client.clone(), e.info.pid, e.kill.clone()),
+                None => return false,
+            }
+        };
+        flag.store(true, Ordering::SeqCst);
+        if let Some(pid) = pid {
+            // Best effort: the channel is closed either way once the reader sees the flag.
+            if let Ok(sess) = client.lease_session() {
+                let _ = exec::run_exec(&sess, &kill_command(pid), KILL_TIMEOUT);
+            }
+        }
+        true
+    }
+
+    pub fn kill_all(&self) {
+        let ids: Vec<String> = {
+            let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            jobs.keys().cloned().collect()
+        };
+        for id in ids {
+            self.kill(&id);
+        }
+    }
+
+    /// Starts `command` on its own exec channel and streams its output as events until it exits.
+    /// Callers may pick the job id so they can subscribe to its events before any output arrives.
+    pub fn start(
+        &self,
+        client: &SessionClient,
+        job_id: Option<String>,
+        command: &str,
+        cwd: Option<&str>,
+        env: &BTreeMap<String, String>,
+        app_handle: tauri::AppHandle,
+    ) -> Result<JobInfo, String> {
+        let job_id = job_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+        if job_id.trim().is_empty() {
+            return Err("job id must not be empty".to_string());
+        }
+        if self.jobs.lock().unwrap_or_else(|e| e.into_inner()).contains_key(&job_id) {
+            return Err(format!("job already running: {job_id}"));
+        }
+        let full_command = job_command(command, cwd, env)?;
+        let sess = client.lease_session().map_err(|e| e.to_string())?;
+        let channel = exec::open_exec_channel(&sess, &full_command)?;
+
+        let info = JobInfo {
+            job_id,
+            session_id: client.id.clone(),
+            host: client.host_display.clone(),
+            command: command.to_string(),
+            started_epoch: now_epoch(),
+            state: JobState::Running,
+            pid: None,
+            exit_status: None,
+            error: None,
+        };
+        let kill = Arc::new(AtomicBool::new(false));
+        {
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            jobs.insert(
+                info.job_id.clone(),
+                JobEntry {
+                    info: info.clone(),
+                    client: client.clone(),
+                    kill: kill.clone(),
+                },
+            );
+        }
+
+        let registry = self.clone();
+        let mut final_info = info.clone();
+        thread::spawn(move || {
+            let result = stream_job(
+                &registry,
+                channel,
+                &final_info.job_id,
+                &mut final_info.pid,
+                &kill,
+                &app_handle,
+            );
+            match result {
+                _ if kill.load(Ordering::SeqCst) => final_info.state = JobState::Killed,
+                Ok(status) => {
+                    final_info.state = JobState::Exited;
+                    final_info.exit_status = status;
+                }
+                Err(e) => {
+                    final_info.state = JobState::Failed;
+                    final_info.error = Some(e);
+                }
+            }
+            registry.finish(&final_info.job_id);
+            let _ = app_handle.emit(&format!("job-exited-{}", final_info.job_id), final_info);
+        });
+
+        Ok(info)
+    }
+}
+
+fn stream_job(
+    registry: &JobRegistry,
+    mut channel: Channel,
+    job_id: &str,
+    pid_out: &mut Option<u32>,
+    kill: &AtomicBool,
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<i32>, String> {
+    let event = format!("job-output-{job_id}");
+    let emit = |stream: JobStream, data: String| {
+        if !data.is_empty() {
+            let _ = app_handle.emit(
+                &event,
+                JobOutputChunk {
+                    job_id: job_id.to_string(),
+                    stream,
+                    data,
+                },
+            );
+        }
+    };
+
+    let mut stdout_carry = Utf8Carry::default();
+    let mut stderr_carry = Utf8Carry::default();
+    let mut pid_buffer: Option<Vec<u8>> = Some(Vec::new());
+    let mut stdout_open = true;
+    let mut stderr_open = true;
+    let mut buf = vec![0u8; 16 * 1024];
+
+    while stdout_open || stderr_open {
+        if kill.load(Ordering::SeqCst) {
+            exec::abandon_exec_channel(channel);
+            return Err("killed".to_string());
+        }
+        let mut progressed = false;
+
+        if stdout_open {
+            match channel.read(&mut buf) {
+                Ok(0) => stdout_open = false,
+                Ok(n) => {
+                    emit(JobStream::Stdout, stdout_carry.push(&buf[..n]));
+                    progressed = true;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("read stdout: {e}")),
+            }
+        }
+
+        if stderr_open {
+            match channel.stderr().read(&mut buf) {
+                Ok(0) => stderr_open = false,
+                Ok(n) => {
+                    progressed = true;
+                    match pid_buffer.as_mut() {
+                        Some(pending) => {
+                            pending.extend_from_slice(&buf[..n]);
+                            if let PidLine::Found(pid, rest) = take_pid_line(pending) {
+                                if let Some(pid) = pid {
+                                    registry.set_pid(job_id, pid);
+                                }
+                                *pid_out = pid;
+                                pid_buffer = None;
+                                emit(JobStream::Stderr, stderr_carry.push(&rest));
+                            }
+                        }
+                        None => emit(JobStream::Stderr, stderr_carry.push(&buf[..n])),
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("read stderr: {e}")),
+            }
+        }
+
+        if !progressed {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    if let Some(pending) = pid_buffer.take() {
+        emit(JobStream::Stderr, stderr_carry.push(&pending));
+    }
+    emit(JobStream::Stdout, stdout_carry.flush());
+    emit(JobStream::Stderr, stderr_carry.flush());
+    Ok(exec::finish_exec_channel(&mut channel))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_carry_holds_split_sequence() {
+        let mut carry = Utf8Carry::default();
+        let bytes = "héllo".as_bytes();
+        assert_eq!(carry.push(&bytes[..2]), "h");
+        assert_eq!(carry.push(&bytes[2..]), "éllo");
+        assert_eq!(carry.flush(), "");
+    }
+
+    #[test]
+    fn test_take_pid_line() {
+        let mut buffer = b"mvwt-job-pid:4242\nwarning: x\n".to_vec();
+        match take_pid_line(&mut buffer) {
+            PidLine::Found(pid, rest) => {
+                assert_eq!(pid, Some(4242));
+                assert_eq!(rest, b"warning: x\n");
+            }
+            PidLine::Pending => panic!("expected pid line"),
+        }
+
+        let mut partial = b"mvwt-job-pid:12".to_vec();
+        assert!(matches!(take_pid_line(&mut partial), PidLine::Pending));
+
+        let mut other = b"banner\n".to_vec();
+        match take_pid_line(&mut other) {
+            PidLine::Found(pid, rest) => {
+                assert_eq!(pid, None);
+                assert_eq!(rest, b"banner\n");
+            }
+            PidLine::Pending => panic!("expected passthrough"),
+        }
+    }
+
+    #[test]
+    fn test_job_command_reports_pid_and_quotes_wrapped_command() {
+        let cmd = job_command("echo 'hi'", Some("/srv"), &BTreeMap::new()).unwrap();
+        assert!(cmd.starts_with("printf 'mvwt-job-pid:%s\\n' $$ >&2; exec sh -c '"));
+        assert!(cmd.contains("cd '\\''/srv'\\''"));
+    }
+}
@@ -0,0 +1,507 @@
+use base64::Engine;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::content;
+use super::session::SessionClient;
+use super::transfer::{self, RemoteFs};
+
+/// Markdown files larger than this are not scanned for assets.
+const MAX_MARKDOWN_BYTES: u64 = 1024 * 1024;
+
+const DEFAULT_MAX_IMAGE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Upper bound on image bytes inlined for one preview, across all images.
+const MAX_TOTAL_IMAGE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Upper bound on distinct targets resolved for one preview.
+const MAX_ASSETS: usize = 200;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetKind {
+    Image,
+    Link,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AssetRef {
+    target: String,
+    kind: AssetKind,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MarkdownAsset {
+    /// The target exactly as written in the markdown source, for matching rendered `src`/`href`.
+    pub target: String,
+    pub kind: AssetKind,
+    /// Path on the VM the target resolves to (fragment and query removed).
+    pub remote_path: String,
+    /// `#fragment` of a link target, without the `#`.
+    pub fragment: Option<String>,
+    pub exists: bool,
+    pub is_dir: bool,
+    pub size_bytes: Option<u64>,
+    /// Inlined image content; only set for images that were fetched.
+    pub data_uri: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MarkdownAssets {
+    pub path: String,
+    pub assets: Vec<MarkdownAsset>,
+    /// More relative targets were found than `MAX_ASSETS`; the rest were not resolved.
+    pub truncated: bool,
+}
+
+/// Absolute URLs, protocol-relative URLs, in-page anchors and data URIs need no resolving.
+fn is_relative_target(target: &str) -> bool {
+    if target.is_empty() || target.starts_with('#') || target.starts_with("//") {
+        return false;
+    }
+    let scheme_len = target
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')))
+        .unwrap_or(target.len());
+    let has_scheme = scheme_len > 0
+        && target.as_bytes()[0].is_ascii_alphabetic()
+        && target[scheme_len..].starts_with(':');
+    !has_scheme
+}
+
+/// Parses a link destination starting at `s` (just after the opening parenthesis).
+fn parse_destination(s: &str) -> Option<&str> {
+    let s = s.trim_start();
+    if let Some(rest) = s.strip_prefix('<') {
+        return rest.find('>').map(|end| &rest[..end]);
+    }
+    let mut depth = 0usize;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(&s[..i]),
+            ')' => depth -= 1,
+            c if c.is_whitespace() => return Some(&s[..i]),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the byte index of the `]` closing the bracket opened at `open`.
+fn matching_bracket(line: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (i, c) in line[open..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn html_img_sources(line: &str, out: &mut Vec<AssetRef>) {
+    let lower = line.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(start) = lower[from..].find("<img").map(|i| from + i) {
+        let end = lower[start..].find('>').map_or(lower.len(), |i| start + i);
+        let tag = &lower[start..end];
+        if let Some(src_at) = tag.find("src=") {
+            let value_start = start + src_at + 4;
+            let value = &line[value_start..end];
+            let target = match value.chars().next() {
+                Some(q @ ('"' | '\'')) => value[1..].split(q).next(),
+                _ => value.split_whitespace().next().map(|v| v.trim_end_matches('/')),
+            };
+            if let Some(target) = target {
+                out.push(AssetRef {
+                    target: target.to_string(),
+                    kind: AssetKind::Image,
+                });
+            }
+        }
+        from = end.max(start + 1);
+    }
+}
+
+/// Blanks out inline code spans so their contents are not mistaken for links.
+fn strip_code_spans(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut in_code = false;
+    for c in line.chars() {
+        if c == '`' {
+            in_code = !in_code;
+            out.push(' ');
+        } else if in_code {
+            out.push(' ');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Collects image and link targets from inline links, reference links and `<img>` tags,
+/// skipping fenced code blocks. Duplicates are removed; order follows first appearance.
+fn extract_asset_refs(markdown: &str) -> Vec<AssetRef> {
+    let mut refs: Vec<AssetRef> = Vec::new();
+    let mut definitions: Vec<(String, String)> = Vec::new();
+    let mut image_labels: Vec<String> = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for raw_line in markdown.lines() {
+        let trimmed = raw_line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            continue;
+        }
+
+        let line = strip_code_spans(raw_line);
+
+        // Reference definition: `[label]: destination "title"`.
+        let indent = raw_line.len() - trimmed.len();
+        if indent <= 3 && trimmed.starts_with('[') {
+            if let Some(close) = matching_bracket(&line, indent) {
+                if let Some(rest) = line[close + 1..].strip_prefix(':') {
+                    let label = line[indent + 1..close].to_lowercase();
+                    let rest = rest.trim();
+                    let dest = match rest.strip_prefix('<') {
+                        Some(r) => r.split('>').next(),
+                        None => rest.split_whitespace().next(),
+                    };
+                    if let Some(dest) = dest {
+                        definitions.push((label, dest.to_string()));
+                    }
+                    continue;
+                }
+            }
+        }
+
+        html_img_sources(&line, &mut refs);
+
+        for (open, _) in line.match_indices('[') {
+            let Some(close) = matching_bracket(&line, open) else {
+                continue;
+            };
+            let is_image = open > 0 && line.as_bytes()[open - 1] == b'!';
+            let kind = if is_image { AssetKind::Image } else { AssetKind::Link };
+            let after = &line[close + 1..];
+            if let Some(rest) = after.strip_prefix('(') {
+                if let Some(dest) = parse_destination(rest) {
+                    refs.push(AssetRef {
+                        target: dest.to_string(),
+                        kind,
+                    });
+                }
+            } else if is_image {
+                // `![alt][label]`, `![alt][]` and `![label]` all resolve through a definition.
+                let label = match after.strip_prefix('[').and_then(|r| r.find(']').map(|e| &r[..e])) {
+                    Some(l) if !l.is_empty() => l,
+                    _ => &line[open + 1..close],
+                };
+                image_labels.push(label.to_lowercase());
+            }
+        }
+    }
+
+    for (label, dest) in definitions {
+        let kind = if image_labels.contains(&label) {
+            AssetKind::Image
+        } else {
+            AssetKind::Link
+        };
+        refs.push(AssetRef { target: dest, kind });
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut out: Vec<AssetRef> = Vec::new();
+    for r in refs.into_iter().filter(|r| is_relative_target(&r.target)) {
+        match seen.get(&r.target) {
+            // The same file used as both image and link is fetched as an image.
+            Some(&i) if r.kind == AssetKind::Image => out[i].kind = AssetKind::Image,
+            Some(_) => {}
+            None => {
+                seen.insert(r.target.clone(), out.len());
+                out.push(r);
+            }
+        }
+    }
+    out
+}
+
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hi = char::from(bytes[i + 1]).to_digit(16);
+            let lo = char::from(bytes[i + 2]).to_digit(16);
+            if let (Some(hi), Some(lo)) = (hi, lo) {
+                out.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Resolves `target` against the directory of `markdown_path`. A leading `/` is relative to
+/// the project root, as on GitHub. Returns the path and the link fragment, if any.
+fn resolve_target(markdown_path: &str, project_root: &str, target: &str) -> Result<(String, Option<String>), String> {
+    let (without_fragment, fragment) = match target.split_once('#') {
+        Some((p, f)) => (p, Some(f.to_string()).filter(|f| !f.is_empty())),
+        None => (target, None),
+    };
+    let without_query = without_fragment.split('?').next().unwrap_or_default();
+    let decoded = percent_decode(without_query);
+
+    let base = match decoded.strip_prefix('/') {
+        Some(_) => project_root,
+        None => markdown_path.rsplit_once('/').map_or("", |(dir, _)| if dir.is_empty() { "/" } else { dir }),
+    };
+
+    // Remote paths are resolved as `/`-separated strings; `Path` would use `\` on Windows.
+    let joined = transfer::remote_join(Path::new(base), decoded.trim_start_matches('/'));
+    let joined = joined.to_string_lossy();
+    let absolute = joined.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => match parts.last() {
+                Some(&last) if last != ".." => {
+                    parts.pop();
+                }
+                _ if absolute => return Err(format!("{target} points outside the filesystem root")),
+                // Relative paths may climb above their starting point (e.g. out of the home directory).
+                _ => parts.push(".."),
+            },
+            other => parts.push(other),
+        }
+    }
+    let resolved = match (absolute, parts.is_empty()) {
+        (true, _) => format!("/{}", parts.join("/")),
+        (false, true) => ".".to_string(),
+        (false, false) => parts.join("/"),
+    };
+    Ok((resolved, fragment))
+}
+
+fn fetch_image(fs: &RemoteFs, path: &Path, size: u64, budget: &mut u64, max_bytes: u64) -> Result<String, String> {
+    if size > max_bytes {
+        return Err(format!("image is larger than the {max_bytes} byte preview limit"));
+    }
+    if size > *budget {
+        return Err("preview image budget exhausted".to_string());
+    }
+
+    let mut file = fs.open_read(path)?;
+    let label = path.display().to_string();
+    let mut bytes = Vec::with_capacity(size as usize);
+    let mut buf = vec![0u8; 32 * 1024];
+    let read = loop {
+        match transfer::read_chunk(&mut file, &mut buf, &label) {
+            Ok(0) => break Ok(()),
+            Ok(n) if (bytes.len() + n) as u64 > max_bytes => {
+                break Err(format!("image is larger than the {max_bytes} byte preview limit"))
+            }
+            Ok(n) => bytes.extend_from_slice(&buf[..n]),
+            Err(e) => break Err(e),
+        }
+    };
+    transfer::close_file(&mut file);
+    read?;
+
+    let info = content::classify(&label, &bytes, false);
+    if !info.mime.starts_with("image/") {
+        return Err(format!("not an image ({})", info.mime));
+    }
+    *budget = budget.saturating_sub(bytes.len() as u64);
+    let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    Ok(format!("data:{};base64,{encoded}", info.mime))
+}
+
+/// Reads a markdown file from the session and resolves its relative image and link targets.
+/// Images are fetched over SFTP and returned as data URIs; links report whether the target exists.
+pub fn resolve_markdown_assets(
+    client: &SessionClient,
+    path: &str,
+    max_image_bytes: Option<u64>,
+) -> Result<MarkdownAssets, String> {
+    let file = client
+        .read_file(path.to_string(), Some(MAX_MARKDOWN_BYTES))
+        .map_err(|e| e.to_string())?
+        .into_decoded();
+    let text = file.text.ok_or_else(|| format!("{path} is not a text file"))?;
+
+    let mut refs = extract_asset_refs(&text);
+    let truncated = refs.len() > MAX_ASSETS;
+    refs.truncate(MAX_ASSETS);
+    if refs.is_empty() {
+        return Ok(MarkdownAssets {
+            path: path.to_string(),
+            assets: Vec::new(),
+            truncated,
+        });
+    }
+
+    let sess = client.lease_session().map_err(|e| e.to_string())?;
+    let fs = RemoteFs::open(&sess)?;
+    let project_root = match client.project_path.trim() {
+        "~" | "" => ".",
+        p => p.strip_prefix("~/").unwrap_or(p),
+    };
+    let max_image_bytes = max_image_bytes.unwrap_or(DEFAULT_MAX_IMAGE_BYTES);
+    let mut budget = MAX_TOTAL_IMAGE_BYTES;
+
+    let assets = refs
+        .into_iter()
+        .map(|r| {
+            let mut asset = MarkdownAsset {
+                target: r.target.clone(),
+                kind: r.kind,
+                remote_path: String::new(),
+                fragment: None,
+                exists: false,
+                is_dir: false,
+                size_bytes: None,
+                data_uri: None,
+                error: None,
+            };
+            let (remote_path, fragment) = match resolve_target(path, project_root, &r.target) {
+                Ok(v) => v,
+                Err(e) => {
+                    asset.error = Some(e);
+                    return asset;
+                }
+            };
+            asset.remote_path = remote_path;
+            asset.fragment = fragment;
+
+            let remote = Path::new(&asset.remote_path);
+            match fs.stat(remote) {
+                Ok(Some(stat)) => {
+                    asset.exists = true;
+                    asset.is_dir = stat.is_dir();
+                    asset.size_bytes = stat.size;
+                }
+                Ok(None) => return asset,
+                Err(e) => {
+                    asset.error = Some(e);
+                    return asset;
+                }
+            }
+
+            if asset.kind == AssetKind::Image && !asset.is_dir {
+                let size = asset.size_bytes.unwrap_or(0);
+                match fetch_image(&fs, remote, size, &mut budget, max_image_bytes) {
+                    Ok(uri) => asset.data_uri = Some(uri),
+                    Err(e) => asset.error = Some(e),
+                }
+            }
+            asset
+        })
+        .collect();
+
+    Ok(MarkdownAssets {
+        path: path.to_string(),
+        assets,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(md: &str) -> Vec<(String, AssetKind)> {
+        extract_asset_refs(md).into_iter().map(|r| (r.target, r.kind)).collect()
+    }
+
+    #[test]
+    fn test_extract_inline_and_html() {
+        let md = "# Demo\n\
+                  ![logo](docs/logo.png \"Logo\") see [guide](./GUIDE.md#setup)\n\
+                  [![badge](img/badge.svg)](https://example.com)\n\
+                  <img src=\"shots/one.png\" width=\"200\">\n\
+                  [site](https://example.com) [top](#top) ![x](data:image/png;base64,AA==)\n";
+        assert_eq!(
+            targets(md),
+            vec![
+                ("docs/logo.png".to_string(), AssetKind::Image),
+                ("./GUIDE.md#setup".to_string(), AssetKind::Link),
+                ("img/badge.svg".to_string(), AssetKind::Image),
+                ("shots/one.png".to_string(), AssetKind::Image),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_skips_code_and_resolves_references() {
+        let md = "```\n![nope](ignored.png)\n```\n\
+                  Inline `![no](code.png)` here.\n\
+                  ![Arch][arch] and [notes][]\n\
+                  \n\
+                  [arch]: <images/arch diagram.png>\n\
+                  [notes]: notes/README.md\n";
+        assert_eq!(
+            targets(md),
+            vec![
+                ("images/arch diagram.png".to_string(), AssetKind::Image),
+                ("notes/README.md".to_string(), AssetKind::Link),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_target() {
+        assert_eq!(
+            resolve_target("/srv/app/docs/README.md", "/srv/app", "../img/a%20b.png?raw=1").unwrap(),
+            ("/srv/app/img/a b.png".to_string(), None)
+        );
+        assert_eq!(
+            resolve_target("/srv/app/docs/README.md", "/srv/app", "/CONTRIBUTING.md#tests").unwrap(),
+            ("/srv/app/CONTRIBUTING.md".to_string(), Some("tests".to_string()))
+        );
+        assert_eq!(
+            resolve_target("README.md", ".", "docs/x.png").unwrap(),
+            ("docs/x.png".to_string(), None)
+        );
+        assert_eq!(
+            resolve_target("/README.md", "/", "./img/../logo.png").unwrap(),
+            ("/logo.png".to_string(), None)
+        );
+        assert_eq!(resolve_target("notes/a.md", "~", "../../x.png").unwrap(), ("../x.png".to_string(), None));
+        assert!(resolve_target("/README.md", "/", "../../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_is_relative_target() {
+        assert!(is_relative_target("img/a.png"));
+        assert!(is_relative_target("../a.md"));
+        assert!(!is_relative_target("https://x.y/a.png"));
+        assert!(!is_relative_target("mailto:a@b.c"));
+        assert!(!is_relative_target("//cdn.example.com/a.png"));
+        assert!(!is_relative_target("#section"));
+    }
+}
@@ -1,18 +1,65 @@
+pub mod agent_state;
+pub mod ai_cli;
+pub mod archive;
+pub mod broadcast;
+pub mod compare;
+pub mod content;
+pub mod dashboard;
+pub mod dispatch;
+pub mod exec;
+pub mod git;
+pub mod jobs;
+pub mod markdown;
+pub mod notify;
+pub mod osc;
+pub mod osc52;
+pub mod push;
+pub mod restart;
+pub mod search;
 pub mod session;
+pub mod shell_env;
+pub mod shell_integration;
+pub mod startup;
+pub mod supervise;
+pub mod transfer;
 
-pub use session::{FileEntry, ReadFileResult, SessionCommand, SessionStatus, SshError, SshSessionConfig, SshSessionHandle};
+pub use agent_state::AgentStatus;
+pub use archive::DownloadStarted;
+pub use broadcast::{BroadcastState, BroadcastTarget};
+pub use compare::RemoteFileDiff;
+pub use dashboard::{GitDashboard, GitDashboardEntry};
+pub use dispatch::DispatchResult;
+pub use exec::{ExecOutput, HostExecResult};
+pub use git::{GitCommit, GitDiff, GitStatus};
+pub use jobs::{JobInfo, JobRegistry};
+pub use markdown::MarkdownAssets;
+pub use push::PushHostReport;
+pub use search::{SearchOptions, SessionSearchResult};
+pub use shell_integration::ShellState;
+pub use session::{FileEntry, ReadFileResult, SessionClient, SessionCommand, SessionStatus, SshError, SshSessionConfig, SshSessionHandle};
+pub use transfer::TransferRegistry;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Emitter;
 
 pub struct SshConnectionManager {
     sessions: Mutex<HashMap<String, SshSessionHandle>>,
+    dashboard: GitDashboard,
+    transfers: TransferRegistry,
+    broadcast: Mutex<BroadcastTarget>,
+    jobs: JobRegistry,
 }
 
 impl SshConnectionManager {
     pub fn new() -> Self {
         Self {
             sessions: Mutex::new(HashMap::new()),
+            dashboard: GitDashboard::new(),
+            transfers: TransferRegistry::new(),
+            broadcast: Mutex::new(BroadcastTarget::Off),
+            jobs: JobRegistry::new(),
         }
     }
 
@@ -21,12 +68,15 @@ impl SshConnectionManager {
         config: SshSessionConfig,
         app_handle: tauri::AppHandle,
     ) -> Result<String, SshError> {
-        let handle = SshSessionHandle::spawn(config, app_handle)?;
+        self.dashboard.start_emitter(app_handle.clone());
+        let handle = SshSessionHandle::spawn(config, app_handle.clone(), self.dashboard.clone())?;
         let id = handle.id.clone();
         let mut sessions = self.sessions.lock().map_err(|_| {
             SshError::Channel("session lock poisoned".to_string())
         })?;
         sessions.insert(id.clone(), handle);
+        drop(sessions);
+        self.emit_broadcast_state(&app_handle);
         Ok(id)
     }
 
@@ -35,13 +85,16 @@ impl SshConnectionManager {
         configs: Vec<SshSessionConfig>,
         app_handle: tauri::AppHandle,
     ) -> Vec<(usize, Result<String, SshError>)> {
+        self.dashboard.start_emitter(app_handle.clone());
+        let dashboard = &self.dashboard;
         let results: Vec<(usize, Result<SshSessionHandle, SshError>)> = std::thread::scope(|s| {
             let handles: Vec<_> = configs
                 .into_iter()
                 .enumerate()
                 .map(|(idx, config)| {
                     let app = app_handle.clone();
-                    s.spawn(move || (idx, SshSessionHandle::spawn(config, app)))
+                    let dashboard = dashboard.clone();
+                    s.spawn(move || (idx, SshSessionHandle::spawn(config, app, dashboard)))
                 })
                 .collect();
 
@@ -72,6 +125,8 @@ impl SshConnectionManager {
                 }
             }
         }
+        drop(sessions);
+        self.emit_broadcast_state(&app_handle);
 
         output
     }
@@ -81,9 +136,103 @@ impl SshConnectionManager {
             SshError::Channel("session lock poisoned".to_string())
         })?;
         let handle = sessions.get(session_id).ok_or(SshError::SessionNotFound)?;
+        let target = self.broadcast.lock().unwrap_or_else(|e| e.into_inner());
+        if target.includes(session_id) {
+            // Input typed into a linked pane is mirrored to every other linked pane;
+            // only a failure on the pane the user is typing in is reported.
+            for (_, other) in sessions
+                .iter()
+                .filter(|(id, _)| id.as_str() != session_id && target.includes(id))
+            {
+                let _ = other.send_input(data.clone());
+            }
+        }
         handle.send_input(data)
     }
 
+    /// Replaces the broadcast link set and emits the resolved state to the frontend.
+    pub fn set_broadcast(
+        &self,
+        target: BroadcastTarget,
+        app_handle: &tauri::AppHandle,
+    ) -> Result<BroadcastState, SshError> {
+        let sessions = self.sessions.lock().map_err(|_| {
+            SshError::Channel("session lock poisoned".to_string())
+        })?;
+        let target = match target {
+            BroadcastTarget::Sessions { session_ids } if session_ids.is_empty() => BroadcastTarget::Off,
+            BroadcastTarget::Sessions { session_ids } => {
+                if session_ids.iter().any(|id| !sessions.contains_key(id)) {
+                    return Err(SshError::SessionNotFound);
+                }
+                BroadcastTarget::Sessions { session_ids }
+            }
+            other => other,
+        };
+        let state = target.state(sessions.keys());
+        *self.broadcast.lock().unwrap_or_else(|e| e.into_inner()) = target;
+        drop(sessions);
+
+        let _ = app_handle.emit(broadcast::BROADCAST_STATE_EVENT, state.clone());
+        Ok(state)
+    }
+
+    pub fn broadcast_state(&self) -> BroadcastState {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let target = self.broadcast.lock().unwrap_or_else(|e| e.into_inner());
+        target.state(sessions.keys())
+    }
+
+    /// Re-emits the resolved link set after sessions come or go.
+    fn emit_broadcast_state(&self, app_handle: &tauri::AppHandle) {
+        let _ = app_handle.emit(broadcast::BROADCAST_STATE_EVENT, self.broadcast_state());
+    }
+
+    pub fn shell_state(&self, session_id: &str) -> Result<ShellState, SshError> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let handle = sessions.get(session_id).ok_or(SshError::SessionNotFound)?;
+        Ok(handle.shell_state())
+    }
+
+    pub fn agent_status(&self, session_id: &str) -> Result<AgentStatus, SshError> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let handle = sessions.get(session_id).ok_or(SshError::SessionNotFound)?;
+        Ok(handle.agent_status())
+    }
+
+    /// Pastes `text` into each session's PTY, skipping agents that aren't idle when their state is known.
+    /// Broadcast links are bypassed so every selected session gets exactly one copy.
+    pub fn dispatch_prompt(&self, session_ids: &[String], text: &str, submit: bool) -> Vec<DispatchResult> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let payload = dispatch::bracketed_paste(text, submit);
+        session_ids
+            .iter()
+            .map(|id| {
+                let Some(handle) = sessions.get(id) else {
+                    return DispatchResult {
+                        session_id: id.clone(),
+                        host: None,
+                        delivered: false,
+                        agent_state: None,
+                        error: Some(SshError::SessionNotFound.to_string()),
+                    };
+                };
+                let status = handle.agent_status();
+                let error = match dispatch::refusal(&status) {
+                    Some(reason) => Some(reason),
+                    None => handle.send_input(payload.clone()).err().map(|e| e.to_string()),
+                };
+                DispatchResult {
+                    session_id: id.clone(),
+                    host: Some(handle.host_display.clone()),
+                    delivered: error.is_none(),
+                    agent_state: status.detecting.then_some(status.state),
+                    error,
+                }
+            })
+            .collect()
+    }
+
     pub fn resize(&self, session_id: &str, cols: u32, rows: u32) -> Result<(), SshError> {
         let sessions = self.sessions.lock().map_err(|_| {
             SshError::Channel("session lock poisoned".to_string())
@@ -109,42 +258,311 @@ impl SshConnectionManager {
     }
 
     pub fn list_directory(&self, session_id: &str, path: String) -> Result<Vec<FileEntry>, SshError> {
+        self.client(session_id)?.list_directory(path)
+    }
+
+    pub fn read_file(
+        &self,
+        session_id: &str,
+        path: String,
+        max_bytes: Option<u64>,
+        decode_text: bool,
+    ) -> Result<ReadFileResult, SshError> {
         let sessions = self.sessions.lock().map_err(|_| {
             SshError::Channel("session lock poisoned".to_string())
         })?;
         let handle = sessions.get(session_id).ok_or(SshError::SessionNotFound)?;
-        handle.list_directory(path)
+        let result = handle.read_file(path, max_bytes)?;
+        Ok(if decode_text { result.into_decoded() } else { result })
     }
 
-    pub fn read_file(
+    /// Resolves relative images and links in a markdown file for previewing.
+    pub fn resolve_markdown_assets(
         &self,
         session_id: &str,
-        path: String,
+        path: &str,
+        max_image_bytes: Option<u64>,
+    ) -> Result<MarkdownAssets, SshError> {
+        let client = self.client(session_id)?;
+        markdown::resolve_markdown_assets(&client, path, max_image_bytes).map_err(SshError::Channel)
+    }
+
+    /// Runs the same content search on every active session concurrently.
+    /// Per-session failures are reported in the result instead of failing the whole query.
+    pub fn search_all(&self, query: &str, options: &SearchOptions) -> Vec<SessionSearchResult> {
+        let clients = self.clients();
+
+        let mut results: Vec<SessionSearchResult> = std::thread::scope(|s| {
+            let handles: Vec<_> = clients
+                .iter()
+                .map(|client| (client, s.spawn(move || client.search(query, options))))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|(client, h)| {
+                    let result = h
+                        .join()
+                        .unwrap_or_else(|_| Err(SshError::Channel("search thread panicked".to_string())));
+                    (client, result)
+                })
+                .map(|(client, result)| match result {
+                    Ok(r) => SessionSearchResult {
+                        session_id: client.id.clone(),
+                        host: client.host_display.clone(),
+                        root: Some(r.root),
+                        matches: r.matches,
+                        truncated: r.truncated,
+                        error: None,
+                    },
+                    Err(e) => SessionSearchResult {
+                        session_id: client.id.clone(),
+                        host: client.host_display.clone(),
+                        root: None,
+                        matches: Vec::new(),
+                        truncated: false,
+                        error: Some(e.to_string()),
+                    },
+                })
+                .collect()
+        });
+
+        results.sort_by(|a, b| a.host.cmp(&b.host));
+        results
+    }
+
+    /// Reads a file from two sessions (possibly the same one) and returns a line diff.
+    pub fn diff_remote_files(
+        &self,
+        session_a: &str,
+        path_a: String,
+        session_b: &str,
+        path_b: String,
         max_bytes: Option<u64>,
-    ) -> Result<ReadFileResult, SshError> {
+    ) -> Result<RemoteFileDiff, SshError> {
+        const DEFAULT_MAX_BYTES: u64 = 2 * 1024 * 1024;
+        let limit = Some(max_bytes.unwrap_or(DEFAULT_MAX_BYTES));
+        let client_a = self.client(session_a)?;
+        let client_b = self.client(session_b)?;
+
+        let (file_a, file_b) = std::thread::scope(|s| {
+            let a = s.spawn(|| client_a.read_file(path_a.clone(), limit));
+            let b = s.spawn(|| client_b.read_file(path_b.clone(), limit));
+            let join = |h: std::thread::ScopedJoinHandle<'_, Result<ReadFileResult, SshError>>| {
+                h.join()
+                    .unwrap_or_else(|_| Err(SshError::Channel("read thread panicked".to_string())))
+            };
+            (join(a), join(b))
+        });
+        let file_a = file_a?;
+        let file_b = file_b?;
+
+        let side = |client: &SessionClient, file: &ReadFileResult| compare::RemoteFileSide {
+            session_id: client.id.clone(),
+            host: client.host_display.clone(),
+            path: file.path.clone(),
+            size_bytes: file.bytes.len() as u64,
+            truncated: file.truncated,
+            binary: file.content.binary,
+        };
+        let a = side(&client_a, &file_a);
+        let b = side(&client_b, &file_b);
+        let identical = !file_a.truncated && !file_b.truncated && file_a.bytes == file_b.bytes;
+
+        if a.binary || b.binary {
+            return Ok(RemoteFileDiff {
+                a,
+                b,
+                identical,
+                binary: true,
+                lines: Vec::new(),
+                inserted: 0,
+                deleted: 0,
+                approximate: false,
+            });
+        }
+
+        let text_a = content::decode_text(&file_a.bytes, &file_a.content).unwrap_or_default();
+        let text_b = content::decode_text(&file_b.bytes, &file_b.content).unwrap_or_default();
+        let diff = compare::diff_lines(&text_a, &text_b);
+        Ok(RemoteFileDiff {
+            a,
+            b,
+            identical,
+            binary: false,
+            lines: diff.lines,
+            inserted: diff.inserted,
+            deleted: diff.deleted,
+            approximate: diff.approximate,
+        })
+    }
+
+    /// Starts a background SFTP copy from one session to another; returns the transfer id.
+    pub fn copy_between_sessions(
+        &self,
+        src_session_id: &str,
+        src_path: String,
+        dst_session_id: &str,
+        dst_path: String,
+        app_handle: tauri::AppHandle,
+    ) -> Result<String, SshError> {
+        let src = self.client(src_session_id)?;
+        let dst = self.client(dst_session_id)?;
+        transfer::start_remote_copy(&self.transfers, &src, src_path, &dst, dst_path, app_handle)
+            .map_err(SshError::Channel)
+    }
+
+    /// Writes a local file or directory into every session's project path (or a relative
+    /// destination under it). With `dry_run`, only reports which hosts differ.
+    pub fn push_to_all(
+        &self,
+        local_path: &str,
+        dest: Option<&str>,
+        dry_run: bool,
+    ) -> Result<Vec<PushHostReport>, SshError> {
+        let clients = self.clients();
+        push::push_to_sessions(&clients, local_path, dest, dry_run).map_err(SshError::Channel)
+    }
+
+    /// Runs a command on every active session over a separate exec channel (not the PTY).
+    /// `timeout` defaults to 30s and is capped at 10 minutes.
+    pub fn run_on_all(&self, command: &str, timeout: Option<Duration>) -> Vec<HostExecResult> {
+        let timeout = timeout
+            .unwrap_or(exec::DEFAULT_EXEC_TIMEOUT)
+            .min(exec::MAX_EXEC_TIMEOUT);
+        exec::run_on_sessions(&self.clients(), command, timeout)
+    }
+
+    /// Runs a command on one session in `cwd` with extra environment variables.
+    /// Same timeout defaults and cap as `run_on_all`.
+    pub fn exec_command(
+        &self,
+        session_id: &str,
+        command: &str,
+        cwd: Option<&str>,
+        env: &BTreeMap<String, String>,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, SshError> {
+        let client = self.client(session_id)?;
+        let timeout = timeout
+            .unwrap_or(exec::DEFAULT_EXEC_TIMEOUT)
+            .min(exec::MAX_EXEC_TIMEOUT);
+        exec::exec_on_session(&client, command, cwd, env, timeout).map_err(SshError::Channel)
+    }
+
+    /// Starts a background exec job on one session; output streams as `job-output-{job_id}` events.
+    pub fn start_job(
+        &self,
+        session_id: &str,
+        job_id: Option<String>,
+        command: &str,
+        cwd: Option<&str>,
+        env: &BTreeMap<String, String>,
+        app_handle: tauri::AppHandle,
+    ) -> Result<JobInfo, SshError> {
+        let client = self.client(session_id)?;
+        self.jobs
+            .start(&client, job_id, command, cwd, env, app_handle)
+            .map_err(SshError::Channel)
+    }
+
+    pub fn kill_job(&self, job_id: &str) -> bool {
+        self.jobs.kill(job_id)
+    }
+
+    pub fn list_jobs(&self, session_id: Option<&str>) -> Vec<JobInfo> {
+        self.jobs.list(session_id)
+    }
+
+    /// Starts downloading a remote directory to a local archive; returns the transfer id and format.
+    pub fn download_directory(
+        &self,
+        session_id: &str,
+        path: String,
+        dest: String,
+        app_handle: tauri::AppHandle,
+    ) -> Result<DownloadStarted, SshError> {
+        let client = self.client(session_id)?;
+        archive::start_download_directory(&self.transfers, &client, path, dest, app_handle)
+            .map_err(SshError::Channel)
+    }
+
+    pub fn cancel_transfer(&self, transfer_id: &str) -> bool {
+        self.transfers.cancel(transfer_id)
+    }
+
+    fn client(&self, session_id: &str) -> Result<SessionClient, SshError> {
         let sessions = self.sessions.lock().map_err(|_| {
             SshError::Channel("session lock poisoned".to_string())
         })?;
         let handle = sessions.get(session_id).ok_or(SshError::SessionNotFound)?;
-        handle.read_file(path, max_bytes)
+        Ok(handle.client())
+    }
+
+    fn clients(&self) -> Vec<SessionClient> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.values().map(SshSessionHandle::client).collect()
     }
 
-    pub fn disconnect(&self, session_id: &str) -> Result<(), SshError> {
+    pub fn git_status(&self, session_id: &str) -> Result<GitStatus, SshError> {
+        self.client(session_id)?.git_status()
+    }
+
+    pub fn git_diff(
+        &self,
+        session_id: &str,
+        path: Option<String>,
+        staged: bool,
+    ) -> Result<GitDiff, SshError> {
+        self.client(session_id)?.git_diff(path.as_deref(), staged)
+    }
+
+    pub fn git_log(&self, session_id: &str, limit: u32) -> Result<Vec<GitCommit>, SshError> {
+        self.client(session_id)?.git_log(limit)
+    }
+
+    pub fn disconnect(&self, session_id: &str, app_handle: &tauri::AppHandle) -> Result<(), SshError> {
         let mut sessions = self.sessions.lock().map_err(|_| {
             SshError::Channel("session lock poisoned".to_string())
         })?;
         let mut handle = sessions.remove(session_id).ok_or(SshError::SessionNotFound)?;
+        drop(sessions);
         handle.shutdown();
+        self.emit_broadcast_state(app_handle);
         Ok(())
     }
 
-    pub fn disconnect_all(&self) {
+    pub fn disconnect_all(&self, app_handle: &tauri::AppHandle) {
+        self.shutdown_all();
+        self.emit_broadcast_state(app_handle);
+    }
+
+    fn shutdown_all(&self) {
+        self.transfers.cancel_all();
+        self.jobs.kill_all();
         let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
         let all: Vec<(String, SshSessionHandle)> = sessions.drain().collect();
+        *self.broadcast.lock().unwrap_or_else(|e| e.into_inner()) = BroadcastTarget::Off;
         drop(sessions);
         for (_, mut handle) in all {
             handle.shutdown();
         }
+        self.dashboard.stop_emitter();
+    }
+
+    pub fn git_dashboard(&self) -> Vec<GitDashboardEntry> {
+        self.dashboard.snapshot()
+    }
+
+    /// `(session_id, host, agent status)` for every session, ordered by host.
+    pub fn agent_statuses(&self) -> Vec<(String, String, AgentStatus)> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<_> = sessions
+            .iter()
+            .map(|(id, handle)| (id.clone(), handle.host_display.clone(), handle.agent_status()))
+            .collect();
+        out.sort_by(|a, b| a.1.cmp(&b.1));
+        out
     }
 
     pub fn active_sessions(&self) -> Vec<(String, String)> {
@@ -158,6 +576,6 @@ impl SshConnectionManager {
 
 impl Drop for SshConnectionManager {
     fn drop(&mut self) {
-        self.disconnect_all();
+        self.shutdown_all();
     }
 }
@@ -0,0 +1,80 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Bells closer together than this (tab completion, `less` at EOF) collapse into one notification.
+const BELL_COOLDOWN: Duration = Duration::from_secs(3);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationSource {
+    Bell,
+    Osc9,
+    Osc777,
+}
+
+/// Payload of `session-notification-{id}`.
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionNotification {
+    pub session_id: String,
+    pub host: String,
+    pub source: NotificationSource,
+    pub title: Option<String>,
+    pub message: String,
+    pub epoch: u64,
+}
+
+/// Reads OSC 9 (`9;message`, iTerm2/Windows Terminal) and OSC 777 (`777;notify;title;body`, urxvt/VTE).
+/// ConEmu's numeric OSC 9 subcommands (progress bars, tab titles) are not notifications.
+pub fn parse_osc_notification(payload: &str) -> Option<(NotificationSource, Option<String>, String)> {
+    if let Some(message) = payload.strip_prefix("9;") {
+        let head = message.split(';').next().unwrap_or_default();
+        if message.is_empty() || (!head.is_empty() && head.bytes().all(|b| b.is_ascii_digit())) {
+            return None;
+        }
+        return Some((NotificationSource::Osc9, None, message.to_string()));
+    }
+    let rest = payload.strip_prefix("777;notify;")?;
+    let (title, body) = rest.split_once(';').unwrap_or((rest, ""));
+    let title = Some(title.to_string()).filter(|t| !t.is_empty());
+    Some((NotificationSource::Osc777, title, body.to_string()))
+}
+
+#[derive(Default)]
+pub struct BellThrottle {
+    last: Option<Instant>,
+}
+
+impl BellThrottle {
+    pub fn allow(&mut self, now: Instant) -> bool {
+        if self.last.is_some_and(|last| now.duration_since(last) < BELL_COOLDOWN) {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osc_notification() {
+        assert_eq!(
+            parse_osc_notification("9;Claude needs your input"),
+            Some((NotificationSource::Osc9, None, "Claude needs your input".to_string()))
+        );
+        assert_eq!(
+            parse_osc_notification("777;notify;Build;done; 0 errors"),
+            Some((NotificationSource::Osc777, Some("Build".to_string()), "done; 0 errors".to_string()))
+        );
+        assert_eq!(parse_osc_notification("9;4;1;50"), None);
+        assert_eq!(parse_osc_notification("7;file://h/tmp"), None);
+
+        let mut bells = BellThrottle::default();
+        let now = Instant::now();
+        assert!(bells.allow(now));
+        assert!(!bells.allow(now + Duration::from_secs(1)));
+        assert!(bells.allow(now + BELL_COOLDOWN));
+    }
+}
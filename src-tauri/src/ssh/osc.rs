@@ -0,0 +1,153 @@
+/// Longest OSC payload kept (room for a 1 MiB OSC 52 copy in base64); anything larger is dropped whole.
+const MAX_OSC_PAYLOAD_BYTES: usize = 2 * 1024 * 1024;
+
+enum ScanState {
+    Ground,
+    Escape,
+    Osc,
+    /// Saw ESC inside an OSC; a following `\` ends it (ST).
+    OscEscape,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScanEvent {
+    /// A complete OSC payload, without the `ESC ]` prefix and terminator.
+    Osc(String),
+    /// A BEL outside any escape sequence.
+    Bell,
+}
+
+/// Pulls complete OSC payloads (`ESC ] payload BEL|ST`) and bare bells out of a PTY byte stream.
+/// Sequences may be split across reads; the terminal still receives the raw bytes untouched.
+pub struct OscScanner {
+    state: ScanState,
+    payload: Vec<u8>,
+    overflow: bool,
+}
+
+impl Default for OscScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OscScanner {
+    pub fn new() -> Self {
+        Self {
+            state: ScanState::Ground,
+            payload: Vec::new(),
+            overflow: false,
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> Vec<ScanEvent> {
+        let mut out = Vec::new();
+        for &b in data {
+            self.state = match self.state {
+                ScanState::Ground => match b {
+                    0x1b => ScanState::Escape,
+                    0x07 => {
+                        out.push(ScanEvent::Bell);
+                        ScanState::Ground
+                    }
+                    _ => ScanState::Ground,
+                },
+                ScanState::Escape => match b {
+                    b']' => {
+                        self.payload.clear();
+                        self.overflow = false;
+                        ScanState::Osc
+                    }
+                    0x1b => ScanState::Escape,
+                    _ => ScanState::Ground,
+                },
+                ScanState::Osc => match b {
+                    0x07 => {
+                        self.finish(&mut out);
+                        ScanState::Ground
+                    }
+                    0x1b => ScanState::OscEscape,
+                    _ => {
+                        self.push(b);
+                        ScanState::Osc
+                    }
+                },
+                ScanState::OscEscape => match b {
+                    b'\\' => {
+                        self.finish(&mut out);
+                        ScanState::Ground
+                    }
+                    // ESC followed by anything else aborts the OSC; treat it as a new escape.
+                    b']' => {
+                        self.payload.clear();
+                        self.overflow = false;
+                        ScanState::Osc
+                    }
+                    _ => ScanState::Ground,
+                },
+            };
+        }
+        out
+    }
+
+    fn push(&mut self, b: u8) {
+        if self.payload.len() < MAX_OSC_PAYLOAD_BYTES {
+            self.payload.push(b);
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<ScanEvent>) {
+        if !self.overflow {
+            out.push(ScanEvent::Osc(String::from_utf8_lossy(&self.payload).into_owned()));
+        }
+        self.payload.clear();
+        self.overflow = false;
+    }
+}
+
+/// Drops CSI and OSC sequences so patterns match what the user sees.
+pub(crate) fn strip_escapes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_handles_split_and_both_terminators() {
+        let osc = |s: &str| ScanEvent::Osc(s.to_string());
+        let mut scanner = OscScanner::new();
+        assert!(scanner.feed(b"ls\r\n\x1b]7;file://h/tm").is_empty());
+        assert_eq!(scanner.feed(b"p\x07\x1b[0m\x1b]133;D;1\x1b\\"), [osc("7;file://h/tmp"), osc("133;D;1")]);
+        // CSI sequences and stray ESCs are ignored; only a BEL outside an OSC is a bell.
+        assert_eq!(scanner.feed(b"\x1b[31mred\x1b\x1b]0;title\x07\x07"), [osc("0;title"), ScanEvent::Bell]);
+    }
+}
@@ -0,0 +1,48 @@
+use base64::Engine;
+use serde::Serialize;
+
+/// Largest decoded clipboard payload accepted from a remote host.
+pub const MAX_CLIPBOARD_BYTES: usize = 1024 * 1024;
+
+/// Payload of `clipboard-{id}`: what a remote OSC 52 copy did.
+#[derive(Serialize, Clone, Debug)]
+pub struct ClipboardEvent {
+    pub bytes: usize,
+    /// False when the connection's clipboard policy is `deny`.
+    pub allowed: bool,
+    pub error: Option<String>,
+}
+
+/// Parses an OSC 52 set request (`52;<selections>;<base64>`). Returns `None` for other
+/// payloads and for clipboard queries (`?`), which are never answered.
+pub fn parse_clipboard_set(payload: &str) -> Option<Result<String, String>> {
+    let rest = payload.strip_prefix("52;")?;
+    let (_selections, data) = rest.split_once(';')?;
+    if data == "?" {
+        return None;
+    }
+    // base64 grows data by 4/3; reject before decoding anything huge.
+    if data.len() > MAX_CLIPBOARD_BYTES / 3 * 4 + 4 {
+        return Some(Err(format!("clipboard data exceeds {MAX_CLIPBOARD_BYTES} bytes")));
+    }
+    let decoded = match base64::engine::general_purpose::STANDARD.decode(data.trim()) {
+        Ok(bytes) => bytes,
+        Err(e) => return Some(Err(format!("invalid clipboard data: {e}"))),
+    };
+    Some(Ok(String::from_utf8_lossy(&decoded).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clipboard_set() {
+        assert_eq!(parse_clipboard_set("52;c;aGVsbG8gd29ybGQ="), Some(Ok("hello world".to_string())));
+        // tmux sends an empty selection list.
+        assert_eq!(parse_clipboard_set("52;;eA=="), Some(Ok("x".to_string())));
+        assert_eq!(parse_clipboard_set("52;c;?"), None);
+        assert_eq!(parse_clipboard_set("7;file://h/tmp"), None);
+        assert!(matches!(parse_clipboard_set("52;c;!!!"), Some(Err(_))));
+    }
+}
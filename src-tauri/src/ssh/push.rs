@@ -0,0 +1,296 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::session::SessionClient;
+use super::transfer::{self, RemoteFs};
+
+/// Pushes are meant for instruction files and templates, not bulk data.
+const MAX_PUSH_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushAction {
+    Created,
+    Updated,
+    Unchanged,
+    WouldCreate,
+    WouldUpdate,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PushFileResult {
+    /// Path relative to the pushed root ("" when a single file was pushed).
+    pub rel_path: String,
+    pub remote_path: String,
+    pub action: PushAction,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PushHostReport {
+    pub session_id: String,
+    pub host: String,
+    pub target: String,
+    pub files: Vec<PushFileResult>,
+    pub error: Option<String>,
+}
+
+struct LocalEntry {
+    rel: String,
+    is_dir: bool,
+    mode: i32,
+    bytes: Vec<u8>,
+}
+
+#[cfg(unix)]
+fn local_mode(meta: &fs::Metadata, _default: i32) -> i32 {
+    use std::os::unix::fs::PermissionsExt;
+    (meta.permissions().mode() & 0o777) as i32
+}
+
+#[cfg(not(unix))]
+fn local_mode(_meta: &fs::Metadata, default: i32) -> i32 {
+    default
+}
+
+/// Reads the local file or directory tree into memory once so every host gets identical bytes.
+fn load_local_tree(root: &Path) -> Result<Vec<LocalEntry>, String> {
+    let meta = fs::metadata(root).map_err(|e| format!("{}: {e}", root.display()))?;
+    if meta.is_file() {
+        if meta.len() > MAX_PUSH_BYTES {
+            return Err(format!("{} is larger than the {MAX_PUSH_BYTES} byte push limit", root.display()));
+        }
+        let bytes = fs::read(root).map_err(|e| format!("read {}: {e}", root.display()))?;
+        return Ok(vec![LocalEntry {
+            rel: String::new(),
+            is_dir: false,
+            mode: local_mode(&meta, 0o644),
+            bytes,
+        }]);
+    }
+
+    let mut out = vec![LocalEntry {
+        rel: String::new(),
+        is_dir: true,
+        mode: local_mode(&meta, 0o755),
+        bytes: Vec::new(),
+    }];
+    let mut total: u64 = 0;
+    let mut pending: Vec<String> = vec![String::new()];
+
+    while let Some(rel_dir) = pending.pop() {
+        let dir = transfer::join_rel(root, &rel_dir);
+        let mut children: Vec<_> = fs::read_dir(&dir)
+            .map_err(|e| format!("read_dir {}: {e}", dir.display()))?
+            .filter_map(Result::ok)
+            .collect();
+        children.sort_by_key(|c| c.file_name());
+
+        for child in children {
+            let name = child.file_name().to_string_lossy().to_string();
+            let rel = if rel_dir.is_empty() {
+                name
+            } else {
+                format!("{rel_dir}/{name}")
+            };
+            // symlink_metadata so links are skipped rather than followed out of the tree.
+            let meta = match fs::symlink_metadata(child.path()) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            if meta.is_dir() {
+                out.push(LocalEntry {
+                    rel: rel.clone(),
+                    is_dir: true,
+                    mode: local_mode(&meta, 0o755),
+                    bytes: Vec::new(),
+                });
+                pending.push(rel);
+            } else if meta.is_file() {
+                total += meta.len();
+                if total > MAX_PUSH_BYTES {
+                    return Err(format!("{} is larger than the {MAX_PUSH_BYTES} byte push limit", root.display()));
+                }
+                let bytes = fs::read(child.path()).map_err(|e| format!("read {}: {e}", child.path().display()))?;
+                out.push(LocalEntry {
+                    rel,
+                    is_dir: false,
+                    mode: local_mode(&meta, 0o644),
+                    bytes,
+                });
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Joins the connection's project path with an optional relative destination.
+/// SFTP does not expand `~`, so home-relative paths are passed on as relative paths.
+fn remote_target_dir(project_path: &str, dest: Option<&str>) -> Result<PathBuf, String> {
+    let base = match project_path.trim() {
+        "~" => ".",
+        p => p.strip_prefix("~/").unwrap_or(p),
+    };
+    let mut dir = PathBuf::from(base);
+    if let Some(rel) = dest.map(str::trim).filter(|d| !d.is_empty() && *d != ".") {
+        if rel.starts_with('/') || rel.split(['/', '\\']).any(|c| c == "..") {
+            return Err(format!("destination must be relative to project_path: {rel}"));
+        }
+        dir = transfer::remote_join(&dir, rel);
+    }
+    Ok(dir)
+}
+
+fn remote_matches(fs: &RemoteFs, path: &Path, expected: &[u8]) -> Result<bool, String> {
+    let mut file = fs.open_read(path)?;
+    let label = path.display().to_string();
+    let mut buf = vec![0u8; 32 * 1024];
+    let mut offset = 0usize;
+    let result = loop {
+        let n = match transfer::read_chunk(&mut file, &mut buf, &label) {
+            Ok(n) => n,
+            Err(e) => break Err(e),
+        };
+        if n == 0 {
+            break Ok(offset == expected.len());
+        }
+        if offset + n > expected.len() || buf[..n] != expected[offset..offset + n] {
+            break Ok(false);
+        }
+        offset += n;
+    };
+    transfer::close_file(&mut file);
+    result
+}
+
+fn push_to_host(
+    client: &SessionClient,
+    local_name: &str,
+    entries: &[LocalEntry],
+    dest: Option<&str>,
+    dry_run: bool,
+) -> Result<(String, Vec<PushFileResult>), String> {
+    let sess = client.lease_session().map_err(|e| e.to_string())?;
+    let fs = RemoteFs::open(&sess)?;
+
+    let target_dir = remote_target_dir(&client.project_path, dest)?;
+    let root = transfer::remote_join(&target_dir, local_name);
+
+    if !dry_run {
+        let target = target_dir.to_string_lossy();
+        let mut partial = PathBuf::from(if target.starts_with('/') { "/" } else { "" });
+        for component in target.split('/').filter(|c| !c.is_empty() && *c != ".") {
+            partial = transfer::remote_join(&partial, component);
+            fs.ensure_dir(&partial, 0o755)?;
+        }
+    }
+
+    let mut results = Vec::new();
+    for entry in entries {
+        let remote = transfer::remote_join(&root, &entry.rel);
+        if entry.is_dir {
+            if !dry_run {
+                fs.ensure_dir(&remote, entry.mode)?;
+            }
+            continue;
+        }
+
+        let existing = fs.stat(&remote)?;
+        let unchanged = match &existing {
+            Some(s) if s.size == Some(entry.bytes.len() as u64) => remote_matches(&fs, &remote, &entry.bytes)?,
+            _ => false,
+        };
+
+        let action = match (existing.is_some(), unchanged, dry_run) {
+            (_, true, _) => PushAction::Unchanged,
+            (false, _, true) => PushAction::WouldCreate,
+            (true, _, true) => PushAction::WouldUpdate,
+            (exists, _, false) => {
+                let mut file = fs.create(&remote, entry.mode)?;
+                let label = remote.display().to_string();
+                let written = transfer::write_all(&mut file, &entry.bytes, &label);
+                transfer::close_file(&mut file);
+                written?;
+                if exists {
+                    PushAction::Updated
+                } else {
+                    PushAction::Created
+                }
+            }
+        };
+
+        results.push(PushFileResult {
+            rel_path: entry.rel.clone(),
+            remote_path: remote.display().to_string(),
+            action,
+        });
+    }
+
+    Ok((root.display().to_string(), results))
+}
+
+/// Writes a local file or directory into every session's project path concurrently.
+/// With `dry_run`, nothing is written and each file reports whether it would change.
+pub fn push_to_sessions(
+    clients: &[SessionClient],
+    local_path: &str,
+    dest: Option<&str>,
+    dry_run: bool,
+) -> Result<Vec<PushHostReport>, String> {
+    let local_root = Path::new(local_path);
+    let local_name = local_root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| format!("cannot derive a file name from {local_path}"))?;
+    let entries = load_local_tree(local_root)?;
+
+    let mut reports: Vec<PushHostReport> = std::thread::scope(|s| {
+        let handles: Vec<_> = clients
+            .iter()
+            .map(|client| {
+                let entries = &entries;
+                let local_name = &local_name;
+                (client, s.spawn(move || push_to_host(client, local_name, entries, dest, dry_run)))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|(client, h)| {
+                let result = h.join().unwrap_or_else(|_| Err("push thread panicked".to_string()));
+                let (target, files, error) = match result {
+                    Ok((target, files)) => (target, files, None),
+                    Err(e) => (String::new(), Vec::new(), Some(e)),
+                };
+                PushHostReport {
+                    session_id: client.id.clone(),
+                    host: client.host_display.clone(),
+                    target,
+                    files,
+                    error,
+                }
+            })
+            .collect()
+    });
+
+    reports.sort_by(|a, b| a.host.cmp(&b.host));
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_target_dir() {
+        assert_eq!(remote_target_dir("/srv/app", Some("config/prod")).unwrap(), PathBuf::from("/srv/app/config/prod"));
+        assert_eq!(remote_target_dir("~/app", Some(" lib ")).unwrap(), PathBuf::from("app/lib"));
+        assert_eq!(remote_target_dir("~", None).unwrap(), PathBuf::from("."));
+        assert_eq!(remote_target_dir("/srv/app", Some("")).unwrap(), PathBuf::from("/srv/app"));
+        assert_eq!(remote_target_dir("/srv/app", Some(".")).unwrap(), PathBuf::from("/srv/app"));
+        assert!(remote_target_dir("/srv/app", Some("/etc")).is_err());
+        assert!(remote_target_dir("/srv/app", Some("../other")).is_err());
+        assert!(remote_target_dir("/srv/app", Some("a/../../b")).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Typed after the shell is ready so the worker learns the PTY shell's pid (the session leader).
+pub const SHELL_PID_REPORT: &str = " printf '\\033]6973;shell-pid;%s\\007' \"$$\"\n";
+
+/// How long each escalation step waits for the shell prompt to come back.
+const PHASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Many TUIs ask for a second Ctrl-C before exiting.
+const SECOND_INTERRUPT_AFTER: Duration = Duration::from_secs(1);
+
+const PROMPT_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Reads the pid out of an OSC payload produced by `SHELL_PID_REPORT`.
+pub fn parse_shell_pid(payload: &str) -> Option<u32> {
+    payload.strip_prefix("6973;shell-pid;")?.trim().parse().ok()
+}
+
+/// Prints the terminal's foreground process group as seen from the shell.
+pub fn foreground_command(shell_pid: u32) -> String {
+    format!("ps -o tpgid= -p {shell_pid}")
+}
+
+/// `Some(true)` when the shell itself is in the foreground (a prompt is showing),
+/// `None` when the shell is gone or `ps` printed nothing useful.
+pub fn parse_foreground(stdout: &str, shell_pid: u32) -> Option<bool> {
+    let tpgid: i64 = stdout.trim().parse().ok()?;
+    Some(tpgid == i64::from(shell_pid))
+}
+
+/// Signals the foreground process group of the PTY, never the shell or background jobs.
+pub fn signal_command(shell_pid: u32, signal: &str) -> String {
+    format!(
+        "fg=$(ps -o tpgid= -p {shell_pid} | tr -d ' '); \
+         [ -n \"$fg\" ] && [ \"$fg\" -gt 0 ] && [ \"$fg\" != {shell_pid} ] && pkill -{signal} -g \"$fg\" -s {shell_pid}"
+    )
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPhase {
+    /// Ctrl-C sent to the running AI CLI.
+    Interrupting,
+    /// SIGTERM sent to the PTY's foreground process group.
+    Terminating,
+    /// SIGKILL sent to the PTY's foreground process group.
+    Killing,
+    /// The prompt is back and the command was typed.
+    Relaunched,
+    Failed,
+}
+
+/// Payload of `ai-cli-restart-{id}`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RestartProgress {
+    pub phase: RestartPhase,
+    pub message: Option<String>,
+}
+
+pub enum RestartAction {
+    /// Bytes to type into the PTY.
+    Send(Vec<u8>),
+    /// Run `signal_command` with this signal over exec.
+    Signal(&'static str),
+    Progress(RestartProgress),
+}
+
+/// Stops the running AI CLI and types its command again once the shell prompt is back.
+/// Escalates Ctrl-C → SIGTERM → SIGKILL, giving each step a few seconds.
+pub struct AiCliRestart {
+    command: String,
+    phase: RestartPhase,
+    started: Instant,
+    deadline: Instant,
+    second_interrupt_sent: bool,
+    next_check: Instant,
+    /// Whether any prompt check gave a definite answer; without one there is nothing to escalate on.
+    prompt_known: bool,
+}
+
+impl AiCliRestart {
+    pub fn start(command: String, now: Instant) -> (Self, Vec<RestartAction>) {
+        let restart = Self {
+            command,
+            phase: RestartPhase::Interrupting,
+            started: now,
+            deadline: now + PHASE_TIMEOUT,
+            second_interrupt_sent: false,
+            next_check: now + PROMPT_CHECK_INTERVAL,
+            prompt_known: false,
+        };
+        let actions = vec![RestartAction::Send(vec![0x03]), restart.progress(None)];
+        (restart, actions)
+    }
+
+    fn progress(&self, message: Option<String>) -> RestartAction {
+        RestartAction::Progress(RestartProgress {
+            phase: self.phase,
+            message,
+        })
+    }
+
+    fn relaunch(&mut self, message: Option<String>, actions: &mut Vec<RestartAction>) {
+        self.phase = RestartPhase::Relaunched;
+        actions.push(RestartAction::Send(format!("{}\n", self.command).into_bytes()));
+        actions.push(self.progress(message));
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.phase, RestartPhase::Relaunched | RestartPhase::Failed)
+    }
+
+    /// The connection dropped mid-restart.
+    pub fn abort(&mut self, reason: &str) -> RestartProgress {
+        self.phase = RestartPhase::Failed;
+        RestartProgress {
+            phase: self.phase,
+            message: Some(reason.to_string()),
+        }
+    }
+
+    /// `at_prompt` is only called when a check is due; it returns `None` when the prompt can't be detected.
+    pub fn poll(&mut self, now: Instant, at_prompt: impl FnOnce() -> Option<bool>) -> Vec<RestartAction> {
+        let mut actions = Vec::new();
+        if self.is_finished() {
+            return actions;
+        }
+        if now >= self.next_check {
+            self.next_check = now + PROMPT_CHECK_INTERVAL;
+            match at_prompt() {
+                Some(true) => {
+                    self.relaunch(None, &mut actions);
+                    return actions;
+                }
+                Some(false) => self.prompt_known = true,
+                None => {}
+            }
+        }
+        if self.phase == RestartPhase::Interrupting
+            && !self.second_interrupt_sent
+            && now >= self.started + SECOND_INTERRUPT_AFTER
+        {
+            self.second_interrupt_sent = true;
+            actions.push(RestartAction::Send(vec![0x03]));
+        }
+        if now < self.deadline {
+            return actions;
+        }
+        if !self.prompt_known {
+            self.relaunch(
+                Some("prompt detection unavailable; relaunched after interrupting".to_string()),
+                &mut actions,
+            );
+            return actions;
+        }
+        let next = match self.phase {
+            RestartPhase::Interrupting => Some((RestartPhase::Terminating, "TERM")),
+            RestartPhase::Terminating => Some((RestartPhase::Killing, "KILL")),
+            _ => None,
+        };
+        match next {
+            Some((phase, signal)) => {
+                self.phase = phase;
+                self.deadline = now + PHASE_TIMEOUT;
+                actions.push(RestartAction::Signal(signal));
+                actions.push(self.progress(None));
+            }
+            None => {
+                self.phase = RestartPhase::Failed;
+                actions.push(self.progress(Some("the previous AI CLI did not exit; not relaunching".to_string())));
+            }
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phases(actions: &[RestartAction]) -> Vec<RestartPhase> {
+        actions
+            .iter()
+            .filter_map(|a| match a {
+                RestartAction::Progress(p) => Some(p.phase),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_escalates_until_prompt_returns() {
+        let now = Instant::now();
+        let (mut r, actions) = AiCliRestart::start("claude --continue".into(), now);
+        assert_eq!(phases(&actions), [RestartPhase::Interrupting]);
+
+        let second = r.poll(now + SECOND_INTERRUPT_AFTER, || Some(false));
+        assert!(matches!(second.as_slice(), [RestartAction::Send(b)] if b == &[0x03]));
+
+        let term = r.poll(now + PHASE_TIMEOUT, || Some(false));
+        assert!(matches!(term.first(), Some(RestartAction::Signal("TERM"))));
+        assert_eq!(phases(&term), [RestartPhase::Terminating]);
+
+        let done = r.poll(now + PHASE_TIMEOUT + PROMPT_CHECK_INTERVAL, || Some(true));
+        assert!(matches!(done.first(), Some(RestartAction::Send(b)) if b == b"claude --continue\n"));
+        assert_eq!(phases(&done), [RestartPhase::Relaunched]);
+        assert!(r.is_finished());
+    }
+
+    #[test]
+    fn test_gives_up_after_kill_and_relaunches_blind_without_detection() {
+        let now = Instant::now();
+        let (mut r, _) = AiCliRestart::start("aider".into(), now);
+        let mut at = now;
+        let mut seen = Vec::new();
+        while !r.is_finished() {
+            at += PROMPT_CHECK_INTERVAL;
+            seen.extend(phases(&r.poll(at, || Some(false))));
+        }
+        assert_eq!(seen, [RestartPhase::Terminating, RestartPhase::Killing, RestartPhase::Failed]);
+
+        let (mut blind, _) = AiCliRestart::start("aider".into(), now);
+        assert!(blind.poll(now + PROMPT_CHECK_INTERVAL, || None).is_empty());
+        let actions = blind.poll(now + PHASE_TIMEOUT, || None);
+        assert_eq!(phases(&actions), [RestartPhase::Relaunched]);
+
+        assert_eq!(parse_shell_pid("6973;shell-pid;4242"), Some(4242));
+        assert_eq!(parse_foreground(" 4242\n", 4242), Some(true));
+        assert_eq!(parse_foreground("4300", 4242), Some(false));
+        assert_eq!(parse_foreground("", 4242), None);
+    }
+}
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_MAX_RESULTS: u32 = 200;
+const HARD_MAX_RESULTS: u32 = 2000;
+const MAX_LINE_CHARS: usize = 500;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct SearchOptions {
+    /// Directory to search in. Defaults to the connection's project_path.
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Treat the query as an extended regex instead of a fixed string.
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub max_results: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchMatch {
+    pub path: String,
+    pub line_number: u64,
+    pub line: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchResult {
+    pub root: String,
+    pub matches: Vec<SearchMatch>,
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionSearchResult {
+    pub session_id: String,
+    pub host: String,
+    pub root: Option<String>,
+    pub matches: Vec<SearchMatch>,
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+pub fn effective_max_results(options: &SearchOptions) -> u32 {
+    options
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .clamp(1, HARD_MAX_RESULTS)
+}
+
+/// Builds a shell command that prefers ripgrep and falls back to grep.
+/// Both tools are asked for NUL-separated paths so file names containing ':' parse correctly.
+pub fn build_search_command(root: &str, query: &str, options: &SearchOptions) -> String {
+    let limit = effective_max_results(options) + 1;
+    let q = super::session::shell_escape(query);
+
+    let mut rg_flags = String::from("--no-heading --line-number --null --color never --no-messages");
+    let mut grep_flags = String::from("-rnIZ --exclude-dir=.git --exclude-dir=node_modules");
+    if !options.case_sensitive {
+        rg_flags.push_str(" --ignore-case");
+        grep_flags.push_str(" -i");
+    }
+    if options.regex {
+        grep_flags.push_str(" -E");
+    } else {
+        rg_flags.push_str(" --fixed-strings");
+        grep_flags.push_str(" -F");
+    }
+
+    format!(
+        "cd {root} 2>/dev/null || {{ echo 'no such directory' >&2; exit 2; }}; \
+         if command -v rg >/dev/null 2>&1; then rg {rg_flags} -e {q} . 2>/dev/null; \
+         else grep {grep_flags} -e {q} . 2>/dev/null; fi | head -n {limit}",
+        root = super::exec::cd_target(root),
+    )
+}
+
+pub fn parse_search_output(root: &str, output: &str, max_results: u32) -> SearchResult {
+    let mut matches = Vec::new();
+    let mut truncated = false;
+
+    for raw in output.lines() {
+        let Some((path, rest)) = raw.split_once('\0') else {
+            continue;
+        };
+        let Some((num, text)) = rest.split_once(':') else {
+            continue;
+        };
+        let Ok(line_number) = num.parse::<u64>() else {
+            continue;
+        };
+
+        if matches.len() as u32 >= max_results {
+            truncated = true;
+            break;
+        }
+
+        let line: String = if text.chars().count() > MAX_LINE_CHARS {
+            text.chars().take(MAX_LINE_CHARS).collect()
+        } else {
+            text.to_string()
+        };
+
+        matches.push(SearchMatch {
+            path: path.strip_prefix("./").unwrap_or(path).to_string(),
+            line_number,
+            line,
+        });
+    }
+
+    SearchResult {
+        root: root.to_string(),
+        matches,
+        truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_search_command_keeps_home_relative_root() {
+        let cmd = build_search_command("~/proj", "needle", &SearchOptions::default());
+        assert!(cmd.starts_with("cd ~/'proj' 2>/dev/null ||"));
+    }
+
+    #[test]
+    fn test_parse_search_output() {
+        let long = "x".repeat(MAX_LINE_CHARS + 10);
+        let output = format!("./src/a:b.rs\x0012:let a = 1;\nnoise\n./c.rs\x003:{long}\n./d.rs\x009:tail\n");
+        let result = parse_search_output("/srv/app", &output, 2);
+        assert_eq!(result.matches.len(), 2);
+        assert_eq!(result.matches[0].path, "src/a:b.rs");
+        assert_eq!(result.matches[0].line_number, 12);
+        assert_eq!(result.matches[1].line.chars().count(), MAX_LINE_CHARS);
+        assert!(result.truncated);
+    }
+}
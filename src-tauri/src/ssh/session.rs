@@ -5,11 +5,32 @@ use std::io::Write;
 use std::net::{TcpStream, ToSocketAddrs};
 use std::path::Path;
 use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::TryRecvError;
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tauri::Emitter;
 
+use super::agent_state::{AgentDetector, AgentStatus};
+use super::ai_cli::{self, AiCliCheck, AiCliUnavailable};
+use super::content::{self, ContentInfo};
+use super::dashboard::GitDashboard;
+use super::exec;
+use super::git::{self, GitCommit, GitDiff, GitStatus};
+use super::search::{self, SearchOptions, SearchResult};
+use super::notify::{self, BellThrottle, NotificationSource, SessionNotification};
+use super::osc::{OscScanner, ScanEvent};
+use super::osc52::{self, ClipboardEvent};
+use super::restart::{self, AiCliRestart, RestartAction};
+use super::shell_env;
+use super::shell_integration::{self, ShellState};
+use super::startup::{StartupAction, StartupSequence};
+use super::supervise::{self, AiCliSupervisor, Marker};
+use crate::workset::{AgentRules, AutoRestart, ClipboardPolicy, StartupStep};
+
+/// How often the connected worker samples resource usage and git state.
+pub const RESOURCE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Serialize, Clone, Debug)]
 pub struct FileEntry {
     pub path: String,
@@ -17,13 +38,29 @@ pub struct FileEntry {
     pub is_dir: bool,
     pub size_bytes: u64,
     pub mtime_epoch: Option<u64>,
+    /// Git change kind when the directory is inside a repository (e.g. "modified", "untracked").
+    pub git_status: Option<String>,
 }
 
 #[derive(Serialize, Clone, Debug)]
 pub struct ReadFileResult {
     pub path: String,
+    /// Raw content; left empty when the text was decoded into `text` instead.
     pub bytes: Vec<u8>,
     pub truncated: bool,
+    pub content: ContentInfo,
+    pub text: Option<String>,
+}
+
+impl ReadFileResult {
+    /// Replaces `bytes` with decoded `text` for text content so it is not shipped twice over IPC.
+    pub fn into_decoded(mut self) -> Self {
+        if let Some(text) = content::decode_text(&self.bytes, &self.content) {
+            self.text = Some(text);
+            self.bytes = Vec::new();
+        }
+        self
+    }
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -80,6 +117,11 @@ pub enum SessionCommand {
         max_bytes: Option<u64>,
         reply_tx: mpsc::Sender<Result<ReadFileResult, String>>,
     },
+    /// Hands out a clone of the live ssh2 session so long-running work (transfers)
+    /// can run on its own thread instead of stalling the PTY loop.
+    LeaseSession {
+        reply_tx: mpsc::Sender<Result<Session, String>>,
+    },
     Shutdown,
 }
 
@@ -92,6 +134,8 @@ pub enum SessionStatus {
     ReconnectFailed,
     Disconnected,
     Error(String),
+    /// Connected, but the shell never signalled it was reading input; startup commands were not sent.
+    ShellNotReady,
 }
 
 pub struct SshSessionConfig {
@@ -103,15 +147,33 @@ pub struct SshSessionConfig {
     pub key_path: Option<String>,
     pub password: Option<String>,
     pub project_path: String,
+    /// Full launch line (profile command plus quoted args, or the connection's own command).
     pub ai_cli_command: Option<String>,
+    /// Typed by `restart_ai_cli` instead of `ai_cli_command` when set.
+    pub ai_cli_restart_command: Option<String>,
+    /// Verified over exec on every connect before the AI CLI is launched (profiles only).
+    pub ai_cli_check: Option<AiCliCheck>,
     pub keepalive_interval_secs: Option<u32>,
     pub reconnect_max_retries: Option<u32>,
+    /// Resolved environment (secrets already read from the keystore).
+    pub env: Vec<(String, String)>,
+    pub startup_steps: Vec<StartupStep>,
+    /// Regex for the prompt; when unset, readiness is detected with an injected marker.
+    pub shell_ready_pattern: Option<String>,
+    pub shell_ready_timeout_secs: Option<u64>,
+    /// Source the OSC 7/133 integration snippet into bash/zsh on connect.
+    pub shell_integration: bool,
+    pub clipboard: ClipboardPolicy,
+    pub agent_rules: AgentRules,
+    pub auto_restart: AutoRestart,
 }
 
 pub struct SshSessionHandle {
     pub id: String,
-    #[allow(dead_code)]
     pub host_display: String,
+    pub project_path: String,
+    shell_state: Arc<Mutex<ShellState>>,
+    agent_status: Arc<Mutex<AgentStatus>>,
     cmd_tx: mpsc::Sender<SessionCommand>,
     worker: Option<JoinHandle<()>>,
 }
@@ -120,18 +182,43 @@ impl SshSessionHandle {
     pub fn spawn(
         config: SshSessionConfig,
         app_handle: tauri::AppHandle,
+        dashboard: GitDashboard,
     ) -> Result<Self, SshError> {
         let (cmd_tx, cmd_rx) = mpsc::channel::<SessionCommand>();
 
         let id = config.id.clone();
         let host_display = format!("{}@{}:{}", config.user, config.host, config.port);
+        let project_path = config.project_path.clone();
         let app_handle_for_thread = app_handle.clone();
+        let shell_state = Arc::new(Mutex::new(ShellState::default()));
+        let worker_shell_state = shell_state.clone();
+        let agent_status = Arc::new(Mutex::new(AgentStatus::default()));
+        let worker_agent_status = agent_status.clone();
+        // The worker removes the session from the dashboard when it exits.
+        dashboard.track(SessionClient {
+            id: id.clone(),
+            host_display: host_display.clone(),
+            project_path: project_path.clone(),
+            cmd_tx: cmd_tx.clone(),
+        });
 
-        let worker = thread::spawn(move || session_worker(config, app_handle_for_thread, cmd_rx));
+        let worker = thread::spawn(move || {
+            session_worker(
+                config,
+                app_handle_for_thread,
+                cmd_rx,
+                dashboard,
+                worker_shell_state,
+                worker_agent_status,
+            )
+        });
 
         Ok(Self {
             id,
             host_display,
+            project_path,
+            shell_state,
+            agent_status,
             cmd_tx,
             worker: Some(worker),
         })
@@ -162,30 +249,82 @@ impl SshSessionHandle {
         }
     }
 
-    pub fn list_directory(&self, path: String) -> Result<Vec<FileEntry>, SshError> {
-        let (reply_tx, reply_rx) = mpsc::channel::<Result<Vec<FileEntry>, String>>();
+    pub fn restart_ai_cli(&self) -> Result<(), SshError> {
+        let (reply_tx, reply_rx) = mpsc::channel::<Result<(), String>>();
         self.cmd_tx
-            .send(SessionCommand::ListDirectory { path, reply_tx })
+            .send(SessionCommand::RestartAiCli { reply_tx })
             .map_err(|e| SshError::Send(e.to_string()))?;
 
         reply_rx
-            .recv_timeout(Duration::from_secs(15))
-            .map_err(|e| SshError::Channel(format!("list_directory response timeout: {e}")))?
+            .recv_timeout(Duration::from_secs(5))
+            .map_err(|e| SshError::Channel(format!("restart_ai_cli response timeout: {e}")))?
             .map_err(SshError::Channel)
     }
 
-    pub fn restart_ai_cli(&self) -> Result<(), SshError> {
-        let (reply_tx, reply_rx) = mpsc::channel::<Result<(), String>>();
+    pub fn read_file(&self, path: String, max_bytes: Option<u64>) -> Result<ReadFileResult, SshError> {
+        self.client().read_file(path, max_bytes)
+    }
+
+    /// Latest cwd and command status reported by the shell; kept across reconnects.
+    pub fn shell_state(&self) -> ShellState {
+        self.shell_state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// AI CLI state as classified from terminal output.
+    pub fn agent_status(&self) -> AgentStatus {
+        self.agent_status.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn client(&self) -> SessionClient {
+        SessionClient {
+            id: self.id.clone(),
+            host_display: self.host_display.clone(),
+            project_path: self.project_path.clone(),
+            cmd_tx: self.cmd_tx.clone(),
+        }
+    }
+}
+
+/// Cloneable request side of a session, used for fan-out operations so the
+/// manager's session map is not held locked while waiting on remote work.
+#[derive(Clone)]
+pub struct SessionClient {
+    pub id: String,
+    pub host_display: String,
+    pub project_path: String,
+    cmd_tx: mpsc::Sender<SessionCommand>,
+}
+
+impl SessionClient {
+    pub fn lease_session(&self) -> Result<Session, SshError> {
+        let (reply_tx, reply_rx) = mpsc::channel::<Result<Session, String>>();
         self.cmd_tx
-            .send(SessionCommand::RestartAiCli { reply_tx })
+            .send(SessionCommand::LeaseSession { reply_tx })
             .map_err(|e| SshError::Send(e.to_string()))?;
 
         reply_rx
             .recv_timeout(Duration::from_secs(5))
-            .map_err(|e| SshError::Channel(format!("restart_ai_cli response timeout: {e}")))?
+            .map_err(|e| SshError::Channel(format!("lease_session response timeout: {e}")))?
             .map_err(SshError::Channel)
     }
 
+    /// Lists over SFTP on the worker, then adds git status over a leased exec channel.
+    pub fn list_directory(&self, path: String) -> Result<Vec<FileEntry>, SshError> {
+        let (reply_tx, reply_rx) = mpsc::channel::<Result<Vec<FileEntry>, String>>();
+        self.cmd_tx
+            .send(SessionCommand::ListDirectory { path: path.clone(), reply_tx })
+            .map_err(|e| SshError::Send(e.to_string()))?;
+
+        let mut entries = reply_rx
+            .recv_timeout(Duration::from_secs(15))
+            .map_err(|e| SshError::Channel(format!("list_directory response timeout: {e}")))?
+            .map_err(SshError::Channel)?;
+        if let Ok(sess) = self.lease_session() {
+            git::annotate_directory(&sess, &path, &mut entries);
+        }
+        Ok(entries)
+    }
+
     pub fn read_file(&self, path: String, max_bytes: Option<u64>) -> Result<ReadFileResult, SshError> {
         let (reply_tx, reply_rx) = mpsc::channel::<Result<ReadFileResult, String>>();
         self.cmd_tx
@@ -201,6 +340,27 @@ impl SshSessionHandle {
             .map_err(|e| SshError::Channel(format!("read_file response timeout: {e}")))?
             .map_err(SshError::Channel)
     }
+
+    /// Runs the search over a leased exec channel so a slow search never stalls terminal output.
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Result<SearchResult, SshError> {
+        let sess = self.lease_session()?;
+        remote_search(&sess, &self.project_path, query, options).map_err(SshError::Channel)
+    }
+
+    pub fn git_status(&self) -> Result<GitStatus, SshError> {
+        let sess = self.lease_session()?;
+        git::status(&sess, &self.project_path).map_err(SshError::Channel)
+    }
+
+    pub fn git_diff(&self, path: Option<&str>, staged: bool) -> Result<GitDiff, SshError> {
+        let sess = self.lease_session()?;
+        git::diff(&sess, &self.project_path, path, staged).map_err(SshError::Channel)
+    }
+
+    pub fn git_log(&self, limit: u32) -> Result<Vec<GitCommit>, SshError> {
+        let sess = self.lease_session()?;
+        git::log(&sess, &self.project_path, limit).map_err(SshError::Channel)
+    }
 }
 
 fn stat_is_dir(perm: Option<u32>) -> bool {
@@ -267,6 +427,7 @@ fn sftp_list_directory(sess: &Session, path: &str) -> Result<Vec<FileEntry>, Str
             is_dir: stat_is_dir(stat.perm),
             size_bytes: stat.size.unwrap_or(0),
             mtime_epoch: stat.mtime,
+            git_status: None,
         });
     }
 
@@ -360,92 +521,18 @@ fn sftp_read_file(sess: &Session, path: &str, max_bytes: Option<u64>) -> Result<
     Ok(ReadFileResult {
         path: path.to_string(),
         truncated,
+        content: content::classify(path, &out, truncated),
         bytes: out,
+        text: None,
     })
 }
 
-fn exec_read_to_string(sess: &Session, cmd: &str) -> Result<String, String> {
-    let deadline = Instant::now() + Duration::from_secs(2);
-    let mut channel = loop {
-        match sess.channel_session() {
-            Ok(c) => break c,
-            Err(e) => {
-                let io_err: std::io::Error = e.into();
-                if io_err.kind() == std::io::ErrorKind::WouldBlock {
-                    if Instant::now() >= deadline {
-                        return Err("channel_session timed out".to_string());
-                    }
-                    std::thread::sleep(Duration::from_millis(5));
-                    continue;
-                }
-                return Err(format!("channel_session: {io_err}"));
-            }
-        }
-    };
+/// Resource probes run every poll; a slow host just skips a sample.
+const RESOURCE_COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
 
-    loop {
-        match channel.exec(cmd) {
-            Ok(()) => break,
-            Err(e) => {
-                let io_err: std::io::Error = e.into();
-                if io_err.kind() == std::io::ErrorKind::WouldBlock {
-                    if Instant::now() >= deadline {
-                        return Err(format!("exec {cmd}: timed out"));
-                    }
-                    std::thread::sleep(Duration::from_millis(5));
-                    continue;
-                }
-                return Err(format!("exec {cmd}: {io_err}"));
-            }
-        }
-    }
-
-    let mut out_bytes: Vec<u8> = Vec::new();
-    let mut buf = [0u8; 8192];
-    loop {
-        match channel.read(&mut buf) {
-            Ok(0) => break,
-            Ok(n) => out_bytes.extend_from_slice(&buf[..n]),
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                if Instant::now() >= deadline {
-                    break;
-                }
-                std::thread::sleep(Duration::from_millis(5));
-            }
-            Err(e) => return Err(format!("read stdout: {e}")),
-        }
-    }
-
-    let mut err_bytes: Vec<u8> = Vec::new();
-    let mut err_buf = [0u8; 8192];
-    loop {
-        match channel.stderr().read(&mut err_buf) {
-            Ok(0) => break,
-            Ok(n) => err_bytes.extend_from_slice(&err_buf[..n]),
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                if Instant::now() >= deadline {
-                    break;
-                }
-                std::thread::sleep(Duration::from_millis(5));
-            }
-            Err(_) => break,
-        }
-    }
-
-    let _ = channel.close();
-    let _ = channel.wait_close();
-
-    let mut out = String::from_utf8_lossy(&out_bytes).to_string();
-    let err_out = String::from_utf8_lossy(&err_bytes).to_string();
-
-    if !err_out.trim().is_empty() {
-        if !out.ends_with('\n') {
-            out.push('\n');
-        }
-        out.push_str(&err_out);
-    }
-
-    Ok(out)
+/// Runs a probe command and returns its stdout if it exited successfully in time.
+fn exec_stdout(sess: &Session, cmd: &str) -> Result<String, String> {
+    exec::run_checked(sess, cmd, RESOURCE_COMMAND_TIMEOUT).map_err(|e| format!("{cmd}: {e}"))
 }
 
 fn parse_cpu_percent_from_top(output: &str) -> Option<f64> {
@@ -564,7 +651,7 @@ fn parse_disk_percent_from_df(output: &str) -> Option<f64> {
     Some(v.max(0.0).min(100.0))
 }
 
-fn now_epoch() -> u64 {
+pub(crate) fn now_epoch() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_else(|_| Duration::from_secs(0))
@@ -572,7 +659,7 @@ fn now_epoch() -> u64 {
 }
 
 fn detect_remote_os(sess: &Session) -> &'static str {
-    match exec_read_to_string(sess, "uname -s") {
+    match exec_stdout(sess, "uname -s") {
         Ok(output) => {
             if output.trim().eq_ignore_ascii_case("darwin") {
                 "darwin"
@@ -586,13 +673,13 @@ fn detect_remote_os(sess: &Session) -> &'static str {
 
 fn collect_resource_snapshot(sess: &Session, project_path: &str, remote_os: &str) -> ResourceSnapshot {
     let (cpu_percent, ram_percent) = if remote_os == "darwin" {
-        let cpu_output = exec_read_to_string(sess, "LANG=C top -l 1 -s 0");
+        let cpu_output = exec_stdout(sess, "LANG=C top -l 1 -s 0");
         let cpu = cpu_output
             .ok()
             .and_then(|o| parse_cpu_percent_from_top_darwin(&o));
 
-        let vm_stat_output = exec_read_to_string(sess, "vm_stat");
-        let sysctl_output = exec_read_to_string(sess, "sysctl -n hw.memsize");
+        let vm_stat_output = exec_stdout(sess, "vm_stat");
+        let sysctl_output = exec_stdout(sess, "sysctl -n hw.memsize");
         let ram = match (vm_stat_output.ok(), sysctl_output.ok()) {
             (Some(vs), Some(sc)) => parse_ram_from_vm_stat(&vs, &sc),
             _ => None,
@@ -600,8 +687,8 @@ fn collect_resource_snapshot(sess: &Session, project_path: &str, remote_os: &str
 
         (cpu, ram)
     } else {
-        let cpu_output = exec_read_to_string(sess, "LANG=C top -bn1");
-        let free_output = exec_read_to_string(sess, "LANG=C free -m");
+        let cpu_output = exec_stdout(sess, "LANG=C top -bn1");
+        let free_output = exec_stdout(sess, "LANG=C free -m");
         let cpu = cpu_output.ok().and_then(|o| parse_cpu_percent_from_top(&o));
         let ram = free_output.ok().and_then(|o| parse_ram_percent_from_free(&o));
         (cpu, ram)
@@ -615,10 +702,10 @@ fn collect_resource_snapshot(sess: &Session, project_path: &str, remote_os: &str
 
     let df_cmd = format!("LANG=C df -P {}", shell_escape(preferred_path));
     let mut disk_path = preferred_path.to_string();
-    let mut df_output = exec_read_to_string(sess, &df_cmd);
+    let mut df_output = exec_stdout(sess, &df_cmd);
     if df_output.is_err() && preferred_path != "/" {
         disk_path = "/".to_string();
-        df_output = exec_read_to_string(sess, "LANG=C df -P /");
+        df_output = exec_stdout(sess, "LANG=C df -P /");
     }
 
     let disk_percent = df_output.ok().and_then(|o| parse_disk_percent_from_df(&o));
@@ -632,7 +719,7 @@ fn collect_resource_snapshot(sess: &Session, project_path: &str, remote_os: &str
     }
 }
 
-fn shell_escape(value: &str) -> String {
+pub(crate) fn shell_escape(value: &str) -> String {
     // Single-quote escaping for /bin/sh -c. Safe for most POSIX shells.
     let mut out = String::new();
     out.push('\'');
@@ -647,6 +734,75 @@ fn shell_escape(value: &str) -> String {
     out
 }
 
+fn remote_search(
+    sess: &Session,
+    project_path: &str,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<SearchResult, String> {
+    if query.is_empty() {
+        return Err("search query must not be empty".to_string());
+    }
+
+    let root = options
+        .path
+        .as_deref()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .unwrap_or(project_path);
+    let cmd = search::build_search_command(root, query, options);
+    let output = exec::run_checked(sess, &cmd, Duration::from_secs(20)).map_err(|e| format!("search {root}: {e}"))?;
+    Ok(search::parse_search_output(root, &output, search::effective_max_results(options)))
+}
+
+/// Answers a request-style command that arrived while the session has no live connection.
+fn reply_not_connected(cmd: SessionCommand) {
+    let msg = "not connected".to_string();
+    match cmd {
+        SessionCommand::RestartAiCli { reply_tx } => {
+            let _ = reply_tx.send(Err(msg));
+        }
+        SessionCommand::ListDirectory { reply_tx, .. } => {
+            let _ = reply_tx.send(Err(msg));
+        }
+        SessionCommand::ReadFile { reply_tx, .. } => {
+            let _ = reply_tx.send(Err(msg));
+        }
+        SessionCommand::LeaseSession { reply_tx } => {
+            let _ = reply_tx.send(Err(msg));
+        }
+        _ => {
+            // Ignore terminal input while disconnected.
+        }
+    }
+}
+
+/// Stages the files a ready shell should source and returns the lines that load them.
+/// Staged names are recorded so leftovers can be removed if the lines never run.
+fn stage_shell_files(
+    sess: &Session,
+    refused_env: &[(String, String)],
+    integration: bool,
+    staged: &mut Vec<String>,
+) -> Result<String, String> {
+    let mut lines = String::new();
+    // Variables the server would not set go through a private file the shell sources.
+    if !refused_env.is_empty() {
+        let vars: Vec<&(String, String)> = refused_env.iter().collect();
+        let script = shell_env::stage_env_file(sess, &vars).map_err(|e| format!("env setup: {e}"))?;
+        staged.push(script.name);
+        lines.push_str(&script.line);
+    }
+    // Best effort: without the snippet the shell still works, it just reports less.
+    if integration {
+        if let Ok(script) = shell_env::stage_script(sess, "shell", shell_integration::INTEGRATION_SCRIPT) {
+            staged.push(script.name);
+            lines.push_str(&script.line);
+        }
+    }
+    Ok(lines)
+}
+
 fn channel_write_all_nonblocking(
     channel: &mut Channel,
     data: &[u8],
@@ -682,6 +838,9 @@ fn session_worker(
     config: SshSessionConfig,
     app_handle: tauri::AppHandle,
     cmd_rx: mpsc::Receiver<SessionCommand>,
+    dashboard: GitDashboard,
+    shell_state: Arc<Mutex<ShellState>>,
+    agent_status: Arc<Mutex<AgentStatus>>,
 ) {
     let max_retries = config.reconnect_max_retries.unwrap_or(3);
     let retry_schedule: &[u64] = &[0, 5, 10, 15, 30];
@@ -690,6 +849,23 @@ fn session_worker(
     let output_event = format!("terminal-output-{}", config.id);
     let resource_event = format!("resource-update-{}", config.id);
     let ai_cli_exit_event = format!("ai-cli-exited-{}", config.id);
+    let startup_event = format!("startup-progress-{}", config.id);
+    let shell_state_event = format!("shell-state-{}", config.id);
+    let notification_event = format!("session-notification-{}", config.id);
+    let clipboard_event = format!("clipboard-{}", config.id);
+    let agent_state_event = format!("agent-state-{}", config.id);
+    let restart_event = format!("ai-cli-restart-{}", config.id);
+    let ai_cli_unavailable_event = format!("ai-cli-unavailable-{}", config.id);
+    let host_display = format!("{}@{}:{}", config.user, config.host, config.port);
+    let mut bells = BellThrottle::default();
+
+    let mut agent = AgentDetector::new(&config.agent_rules, config.ai_cli_command.as_deref());
+    *agent_status.lock().unwrap_or_else(|e| e.into_inner()) = agent.status().clone();
+    let publish_agent = |agent: &AgentDetector| {
+        let status = agent.status().clone();
+        *agent_status.lock().unwrap_or_else(|e| e.into_inner()) = status.clone();
+        let _ = app_handle.emit(&agent_state_event, status);
+    };
 
     let emit_status = |status: SessionStatus| {
         let _ = app_handle.emit(&status_event, status);
@@ -717,9 +893,24 @@ fn session_worker(
     let mut pty_cols: u32 = 80;
     let mut pty_rows: u32 = 24;
 
+    let mut startup = StartupSequence::new(
+        &config.startup_steps,
+        config.ai_cli_command.as_deref(),
+        config.shell_ready_pattern.as_deref(),
+        config.shell_ready_timeout_secs.map(Duration::from_secs),
+    );
+    startup.wrap_ai_cli(supervise::wrap_command);
+    let mut supervisor = AiCliSupervisor::new(config.auto_restart);
+
+    // Files staged for a shell to source; removed on the next connection or at teardown
+    // in case the shell never ran the line that deletes them.
+    let mut staged_files: Vec<String> = Vec::new();
+
     // Helper to establish a fresh SSH session + interactive shell.
-    let connect_shell =
-        |run_ai_cli: bool, pty_cols: u32, pty_rows: u32| -> Result<(Session, Channel), String> {
+    // Returns the lines to type once the shell is ready (`cd`) and the variables the server
+    // refused, which are staged for the shell to source once it is ready.
+    type Connected = (Session, Channel, Vec<String>, Vec<(String, String)>);
+    let connect_shell = |pty_cols: u32, pty_rows: u32, cwd: Option<&str>| -> Result<Connected, String> {
         // DNS resolution
         let addr_str = format!("{}:{}", config.host, config.port);
         let sock_addr = match addr_str.to_socket_addrs() {
@@ -775,30 +966,28 @@ fn session_worker(
         let mut channel: Channel = sess
             .channel_session()
             .map_err(|e| format!("channel_session: {e}"))?;
+        let refused_env: Vec<(String, String)> =
+            shell_env::request_env(&mut channel, &config.env).into_iter().cloned().collect();
         channel
             .request_pty("xterm-256color", None, Some((pty_cols, pty_rows, 0, 0)))
             .map_err(|e| format!("request_pty: {e}"))?;
         channel.shell().map_err(|e| format!("shell: {e}"))?;
 
-        // Restore working directory
-        if !config.project_path.is_empty() {
-            channel
-                .write_all(format!("cd {}\n", config.project_path).as_bytes())
-                .map_err(|e| format!("write cd command: {e}"))?;
+        let mut preamble = Vec::new();
+
+        // Lets a restart tell the AI CLI apart from the shell and signal only the former.
+        if config.ai_cli_command.is_some() {
+            preamble.push(restart::SHELL_PID_REPORT.to_string());
         }
 
-        // Auto-run AI CLI when configured.
-        if run_ai_cli {
-            if let Some(cmd) = config.ai_cli_command.as_deref() {
-                if !cmd.is_empty() {
-                    channel
-                        .write_all(format!("{}\n", cmd).as_bytes())
-                        .map_err(|e| format!("write ai cli command: {e}"))?;
-                }
-            }
+        // Restore working directory: where the shell last reported being, else the project.
+        if let Some(cwd) = cwd {
+            preamble.push(format!("cd {}\n", shell_escape(cwd)));
+        } else if !config.project_path.is_empty() {
+            preamble.push(format!("cd {}\n", config.project_path));
         }
 
-        Ok((sess, channel))
+        Ok((sess, channel, preamble, refused_env))
     };
 
     // Outer loop: connect → run until drop/shutdown → reconnect as needed.
@@ -830,18 +1019,7 @@ fn session_worker(
                     pty_cols = cols;
                     pty_rows = rows;
                 }
-                Ok(SessionCommand::RestartAiCli { reply_tx }) => {
-                    let _ = reply_tx.send(Err("not connected".to_string()));
-                }
-                Ok(SessionCommand::ListDirectory { reply_tx, .. }) => {
-                    let _ = reply_tx.send(Err("not connected".to_string()));
-                }
-                Ok(SessionCommand::ReadFile { reply_tx, .. }) => {
-                    let _ = reply_tx.send(Err("not connected".to_string()));
-                }
-                Ok(_) => {
-                    // Ignore terminal input while disconnected.
-                }
+                Ok(other) => reply_not_connected(other),
                 Err(TryRecvError::Empty) => {
                     // wait
                 }
@@ -851,14 +1029,14 @@ fn session_worker(
         }
 
         // Attempt connection.
-        let run_ai_cli = config
-            .ai_cli_command
-            .as_deref()
-            .map(|s| !s.trim().is_empty())
-            .unwrap_or(false);
-        let connect_result = connect_shell(run_ai_cli, pty_cols, pty_rows);
-
-        let (sess, mut channel) = match connect_result {
+        let last_cwd = {
+            let mut state = shell_state.lock().unwrap_or_else(|e| e.into_inner());
+            state.command_running = false;
+            state.cwd.clone()
+        };
+        let connect_result = connect_shell(pty_cols, pty_rows, last_cwd.as_deref());
+
+        let (sess, mut channel, preamble, refused_env) = match connect_result {
             Ok(v) => v,
             Err(msg) => {
                 emit_status(SessionStatus::Error(msg.clone()));
@@ -883,16 +1061,7 @@ fn session_worker(
                                 pty_cols = cols;
                                 pty_rows = rows;
                             }
-                            Ok(SessionCommand::RestartAiCli { reply_tx }) => {
-                                let _ = reply_tx.send(Err("not connected".to_string()));
-                            }
-                            Ok(SessionCommand::ListDirectory { reply_tx, .. }) => {
-                                let _ = reply_tx.send(Err("not connected".to_string()));
-                            }
-                            Ok(SessionCommand::ReadFile { reply_tx, .. }) => {
-                                let _ = reply_tx.send(Err("not connected".to_string()));
-                            }
-                            Ok(_) => {}
+                            Ok(other) => reply_not_connected(other),
                             Err(TryRecvError::Empty) => {}
                         }
                         std::thread::sleep(Duration::from_millis(80));
@@ -919,16 +1088,7 @@ fn session_worker(
                                 pty_cols = cols;
                                 pty_rows = rows;
                             }
-                            Ok(SessionCommand::RestartAiCli { reply_tx }) => {
-                                let _ = reply_tx.send(Err("not connected".to_string()));
-                            }
-                            Ok(SessionCommand::ListDirectory { reply_tx, .. }) => {
-                                let _ = reply_tx.send(Err("not connected".to_string()));
-                            }
-                            Ok(SessionCommand::ReadFile { reply_tx, .. }) => {
-                                let _ = reply_tx.send(Err("not connected".to_string()));
-                            }
-                            Ok(_) => {}
+                            Ok(other) => reply_not_connected(other),
                             Err(TryRecvError::Empty) => {}
                         }
                         std::thread::sleep(Duration::from_millis(80));
@@ -954,10 +1114,34 @@ fn session_worker(
 
         // Detect remote OS once per connection (before switching to non-blocking).
         let remote_os = detect_remote_os(&sess);
+        shell_env::remove_staged(&sess, &mut staged_files);
+
+        if let Some(check) = &config.ai_cli_check {
+            let problem = ai_cli::check_launch(&sess, check, &host_display);
+            startup.set_ai_cli_available(problem.is_none());
+            if let Some(message) = problem {
+                let _ = app_handle.emit(
+                    &ai_cli_unavailable_event,
+                    AiCliUnavailable {
+                        session_id: config.id.clone(),
+                        host: host_display.clone(),
+                        message,
+                    },
+                );
+            }
+        }
 
         sess.set_blocking(false);
+        // `cd`, startup steps and the AI CLI launch are typed from the main loop once the shell is ready.
+        let stage_on_ready = !refused_env.is_empty() || config.shell_integration;
+        startup.begin(Instant::now(), preamble, stage_on_ready);
+        let mut osc = OscScanner::new();
+        supervisor.reset();
+        let mut shell_pid: Option<u32> = None;
+        let mut restart: Option<AiCliRestart> = None;
+        let mut restart_actions: Vec<RestartAction> = Vec::new();
         let mut last_resource_emit = Instant::now()
-            .checked_sub(Duration::from_secs(5))
+            .checked_sub(RESOURCE_POLL_INTERVAL)
             .unwrap_or_else(Instant::now);
 
         // Connected main loop
@@ -978,22 +1162,25 @@ fn session_worker(
                     // Ignore while connected.
                 }
                 Ok(SessionCommand::RestartAiCli { reply_tx }) => {
-                    let cmd = match config.ai_cli_command.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+                    let cmd = config.ai_cli_restart_command.as_deref().or(config.ai_cli_command.as_deref());
+                    let cmd = match cmd.map(str::trim).filter(|s| !s.is_empty()) {
                         Some(v) => v,
                         None => {
                             let _ = reply_tx.send(Err("no AI CLI command configured".to_string()));
                             continue;
                         }
                     };
+                    if restart.is_some() {
+                        let _ = reply_tx.send(Err("an AI CLI restart is already in progress".to_string()));
+                        continue;
+                    }
 
-                    let write_bytes = format!("{}\n", cmd).into_bytes();
-                    let result = channel_write_all_nonblocking(
-                        &mut channel,
-                        &write_bytes,
-                        Duration::from_secs(5),
-                    )
-                    .map_err(|e| format!("write ai cli command: {e}"));
-                    let _ = reply_tx.send(result);
+                    // Phases are reported through `ai-cli-restart-{id}` as the sequence advances.
+                    supervisor.reset();
+                    let (started, actions) = AiCliRestart::start(supervise::wrap_command(cmd), Instant::now());
+                    restart = Some(started);
+                    restart_actions = actions;
+                    let _ = reply_tx.send(Ok(()));
                 }
                 Ok(SessionCommand::ListDirectory { path, reply_tx }) => {
                     let result = sftp_list_directory(&sess, &path);
@@ -1007,13 +1194,18 @@ fn session_worker(
                     let result = sftp_read_file(&sess, &path, max_bytes);
                     let _ = reply_tx.send(result);
                 }
+                Ok(SessionCommand::LeaseSession { reply_tx }) => {
+                    let _ = reply_tx.send(Ok(sess.clone()));
+                }
                 Ok(SessionCommand::Shutdown) => {
+                    shell_env::remove_staged(&sess, &mut staged_files);
                     let _ = channel.close();
                     let _ = channel.wait_close();
                     emit_status(SessionStatus::Disconnected);
                     break 'outer;
                 }
                 Err(TryRecvError::Disconnected) => {
+                    shell_env::remove_staged(&sess, &mut staged_files);
                     let _ = channel.close();
                     let _ = channel.wait_close();
                     emit_status(SessionStatus::Disconnected);
@@ -1025,10 +1217,77 @@ fn session_worker(
             let mut buf = [0u8; 4096];
             match channel.read(&mut buf) {
                 Ok(0) => {
-                    let _ = app_handle.emit(&ai_cli_exit_event, ());
+                    if agent.exited(now_epoch()) {
+                        publish_agent(&agent);
+                    }
                     break;
                 }
                 Ok(n) => {
+                    startup.observe(&buf[..n]);
+                    if agent.observe(&buf[..n], Instant::now(), now_epoch()) {
+                        publish_agent(&agent);
+                    }
+                    let mut shell_changed = false;
+                    let mut command_finished = false;
+                    let mut ai_cli_exited = false;
+                    for event in osc.feed(&buf[..n]) {
+                        let notification = match event {
+                            ScanEvent::Osc(payload) => {
+                                if let Some(pid) = restart::parse_shell_pid(&payload) {
+                                    shell_pid = Some(pid);
+                                }
+                                match supervise::parse_marker(&payload) {
+                                    Some(Marker::Started(pid)) => supervisor.started(pid, Instant::now()),
+                                    Some(Marker::Exited(code)) => {
+                                        // A manual restart stopped it on purpose and relaunches by itself.
+                                        let exit = supervisor.exited(Some(code), Instant::now(), now_epoch(), restart.is_none());
+                                        let _ = app_handle.emit(&ai_cli_exit_event, exit);
+                                        ai_cli_exited = true;
+                                    }
+                                    None => {}
+                                }
+                                let mut state = shell_state.lock().unwrap_or_else(|e| e.into_inner());
+                                let was_running = state.command_running;
+                                shell_changed |= state.apply_osc(&payload);
+                                command_finished |= was_running && !state.command_running;
+                                drop(state);
+                                if let Some(request) = osc52::parse_clipboard_set(&payload) {
+                                    let allowed = config.clipboard == ClipboardPolicy::Allow;
+                                    let (bytes, error) = match request {
+                                        Ok(text) if allowed => (text.len(), crate::clipboard::set_text(&text).err()),
+                                        Ok(text) => (text.len(), None),
+                                        Err(e) => (0, Some(e)),
+                                    };
+                                    let _ = app_handle.emit(&clipboard_event, ClipboardEvent { bytes, allowed, error });
+                                }
+                                notify::parse_osc_notification(&payload)
+                            }
+                            ScanEvent::Bell => bells
+                                .allow(Instant::now())
+                                .then(|| (NotificationSource::Bell, None, "Terminal bell".to_string())),
+                        };
+                        if let Some((source, title, message)) = notification {
+                            let _ = app_handle.emit(
+                                &notification_event,
+                                SessionNotification {
+                                    session_id: config.id.clone(),
+                                    host: host_display.clone(),
+                                    source,
+                                    title,
+                                    message,
+                                    epoch: now_epoch(),
+                                },
+                            );
+                        }
+                    }
+                    if shell_changed {
+                        let state = shell_state.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                        let _ = app_handle.emit(&shell_state_event, state);
+                    }
+                    // With shell integration, the prompt coming back means the agent process ended.
+                    if (command_finished || ai_cli_exited) && agent.exited(now_epoch()) {
+                        publish_agent(&agent);
+                    }
                     let _ = app_handle.emit(&output_event, buf[..n].to_vec());
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
@@ -1038,7 +1297,103 @@ fn session_worker(
                 }
             }
 
-            if last_resource_emit.elapsed() >= Duration::from_secs(5) {
+            if agent.tick(Instant::now(), now_epoch()) {
+                publish_agent(&agent);
+            }
+
+            let mut startup_error = None;
+            for action in startup.poll(Instant::now()) {
+                match action {
+                    StartupAction::ShellReady if stage_on_ready => {
+                        startup_error = stage_shell_files(&sess, &refused_env, config.shell_integration, &mut staged_files)
+                            .and_then(|lines| {
+                                channel_write_all_nonblocking(&mut channel, lines.as_bytes(), Duration::from_secs(5))
+                                    .map_err(|e| format!("write startup step: {e}"))
+                            })
+                            .err();
+                    }
+                    StartupAction::ShellReady => {}
+                    StartupAction::Send(line) if startup_error.is_none() => {
+                        startup_error = channel_write_all_nonblocking(&mut channel, line.as_bytes(), Duration::from_secs(5))
+                            .map_err(|e| format!("write startup step: {e}"))
+                            .err();
+                    }
+                    StartupAction::Send(_) => {}
+                    StartupAction::Progress(progress) => {
+                        let _ = app_handle.emit(&startup_event, progress);
+                    }
+                    StartupAction::ShellNotReady => {
+                        shell_env::remove_staged(&sess, &mut staged_files);
+                        emit_status(SessionStatus::ShellNotReady);
+                    }
+                }
+            }
+            if let Some(e) = startup_error {
+                emit_status(SessionStatus::Error(e));
+                break;
+            }
+
+            if let Some(r) = restart.as_mut() {
+                let at_prompt = || {
+                    let from_ps = shell_pid.and_then(|pid| {
+                        exec::run_checked(&sess, &restart::foreground_command(pid), Duration::from_secs(3))
+                            .ok()
+                            .and_then(|out| restart::parse_foreground(&out, pid))
+                    });
+                    from_ps.or_else(|| {
+                        let state = shell_state.lock().unwrap_or_else(|e| e.into_inner());
+                        state.integrated.then_some(!state.command_running)
+                    })
+                };
+                restart_actions.extend(r.poll(Instant::now(), at_prompt));
+            }
+            let mut restart_write_error = None;
+            for action in restart_actions.drain(..) {
+                match action {
+                    RestartAction::Send(bytes) if restart_write_error.is_none() => {
+                        restart_write_error =
+                            channel_write_all_nonblocking(&mut channel, &bytes, Duration::from_secs(5)).err();
+                    }
+                    RestartAction::Send(_) => {}
+                    RestartAction::Signal(signal) => {
+                        if let Some(pid) = shell_pid {
+                            let _ = exec::run_exec(
+                                &sess,
+                                &restart::signal_command(pid, signal),
+                                Duration::from_secs(5),
+                            );
+                        }
+                    }
+                    RestartAction::Progress(progress) => {
+                        let _ = app_handle.emit(&restart_event, progress);
+                    }
+                }
+            }
+            if restart.as_ref().is_some_and(AiCliRestart::is_finished) {
+                restart = None;
+            }
+            if let Some(e) = restart_write_error {
+                emit_status(SessionStatus::Error(format!("write ai cli restart: {e}")));
+                break;
+            }
+
+            if supervisor.restart_due(Instant::now()) {
+                // Don't type into something the user started at the prompt in the meantime.
+                let busy = {
+                    let state = shell_state.lock().unwrap_or_else(|e| e.into_inner());
+                    state.integrated && state.command_running
+                };
+                let cmd = config.ai_cli_restart_command.as_deref().or(config.ai_cli_command.as_deref());
+                if let Some(cmd) = cmd.map(str::trim).filter(|s| !s.is_empty() && !busy) {
+                    let line = format!("{}\n", supervise::wrap_command(cmd));
+                    if let Err(e) = channel_write_all_nonblocking(&mut channel, line.as_bytes(), Duration::from_secs(5)) {
+                        emit_status(SessionStatus::Error(format!("write ai cli relaunch: {e}")));
+                        break;
+                    }
+                }
+            }
+
+            if last_resource_emit.elapsed() >= RESOURCE_POLL_INTERVAL {
                 let snapshot = collect_resource_snapshot(&sess, &config.project_path, remote_os);
                 let _ = app_handle.emit(&resource_event, snapshot);
                 last_resource_emit = Instant::now();
@@ -1048,6 +1403,13 @@ fn session_worker(
         }
 
         // Cleanup on drop.
+        if supervisor.is_running() {
+            let exit = supervisor.exited(None, Instant::now(), now_epoch(), false);
+            let _ = app_handle.emit(&ai_cli_exit_event, exit);
+        }
+        if let Some(mut r) = restart.take() {
+            let _ = app_handle.emit(&restart_event, r.abort("connection lost during restart"));
+        }
         let _ = channel.close();
         let _ = channel.wait_close();
 
@@ -1058,4 +1420,6 @@ fn session_worker(
             + Duration::from_millis(jitter_ms);
         continue 'outer;
     }
+
+    dashboard.remove(&config.id);
 }
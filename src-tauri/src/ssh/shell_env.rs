@@ -0,0 +1,77 @@
+use ssh2::{Channel, Session};
+use std::path::Path;
+use uuid::Uuid;
+
+use super::session::shell_escape;
+use super::startup::UNLISTED_PREFIX;
+use super::transfer::{self, RemoteFs};
+
+/// Asks the server to set each variable on the shell channel. Returns the ones it refused;
+/// sshd only accepts names listed in its `AcceptEnv`, so refusals are the common case.
+pub(crate) fn request_env<'a>(channel: &mut Channel, vars: &'a [(String, String)]) -> Vec<&'a (String, String)> {
+    vars.iter()
+        .filter(|(name, value)| channel.setenv(name, value).is_err())
+        .collect()
+}
+
+fn export_script(vars: &[&(String, String)]) -> String {
+    vars.iter()
+        .map(|(name, value)| format!("export {name}={}\n", shell_escape(value)))
+        .collect()
+}
+
+/// A private file in the remote home directory and the line that sources and deletes it.
+/// If the line never runs, the file stays behind until `remove_staged` deletes it.
+pub(crate) struct StagedScript {
+    pub name: String,
+    pub line: String,
+}
+
+/// Writes refused variables to a private file the shell sources. Values never pass through the PTY.
+pub(crate) fn stage_env_file(sess: &Session, vars: &[&(String, String)]) -> Result<StagedScript, String> {
+    stage_script(sess, "env", &export_script(vars))
+}
+
+/// Writes `body` to a private (0600) file in the remote home directory.
+pub(crate) fn stage_script(sess: &Session, label: &str, body: &str) -> Result<StagedScript, String> {
+    let name = format!(".mvwt-{label}-{}", Uuid::new_v4());
+    let fs = RemoteFs::open(sess)?;
+    let mut file = fs.create(Path::new(&name), 0o600)?;
+    let written = transfer::write_all(&mut file, body.as_bytes(), &name);
+    transfer::close_file(&mut file);
+    if let Err(e) = written {
+        fs.unlink(Path::new(&name));
+        return Err(e);
+    }
+    let path = format!("\"$HOME\"/{name}");
+    let line = format!("{UNLISTED_PREFIX}. {path}; rm -f {path}\n");
+    Ok(StagedScript { name, line })
+}
+
+/// Deletes staged files whose line may not have run. Names are kept for a later attempt
+/// when SFTP is unavailable.
+pub(crate) fn remove_staged(sess: &Session, names: &mut Vec<String>) {
+    if names.is_empty() {
+        return;
+    }
+    if let Ok(fs) = RemoteFs::open(sess) {
+        for name in names.drain(..) {
+            fs.unlink(Path::new(&name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_script_quotes_values() {
+        let a = ("API_KEY".to_string(), "sk-'x' $y".to_string());
+        let b = ("EMPTY".to_string(), String::new());
+        assert_eq!(
+            export_script(&[&a, &b]),
+            "export API_KEY='sk-'\\''x'\\'' $y'\nexport EMPTY=''\n"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Serialize;
+
+use super::markdown::percent_decode;
+
+/// Sourced into bash or zsh on connect when shell integration is enabled. Emits OSC 7 (cwd),
+/// OSC 133 A/C/D (prompt, command start, exit code) and OSC 633;E (command line, with `\`,
+/// `;` and newlines escaped as in the VS Code protocol). Other shells are left untouched.
+pub const INTEGRATION_SCRIPT: &str = r#"if [ -z "${__MVWT_SI:-}" ] && { [ -n "${BASH_VERSION:-}" ] || [ -n "${ZSH_VERSION:-}" ]; }; then
+__MVWT_SI=1
+__mvwt_osc() { printf '\033]%s\007' "$1"; }
+__mvwt_preexec() {
+  local c="$1"
+  c="${c//\\/\\\\}"; c="${c//;/\\x3b}"; c="${c//$'\n'/\\x0a}"
+  __mvwt_osc "633;E;$c"
+  __mvwt_osc "133;C"
+}
+__mvwt_precmd() {
+  local s=$?
+  __mvwt_osc "133;D;$s"
+  __mvwt_osc "7;file://${HOSTNAME:-$HOST}$PWD"
+  __mvwt_osc "133;A"
+  return $s
+}
+if [ -n "${ZSH_VERSION:-}" ]; then
+  autoload -Uz add-zsh-hook
+  add-zsh-hook precmd __mvwt_precmd
+  add-zsh-hook preexec __mvwt_preexec
+else
+  PS0='$(__mvwt_preexec "$(HISTTIMEFORMAT= builtin history 1 | sed "s/^ *[0-9]* *//")")'
+  PROMPT_COMMAND="__mvwt_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+fi
+"#;
+
+/// What the shell has reported about itself through OSC 7 / 133 / 633.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShellState {
+    pub cwd: Option<String>,
+    pub last_command: Option<String>,
+    pub last_exit_code: Option<i32>,
+    /// A command has started and has not reported its exit yet.
+    pub command_running: bool,
+    /// Command boundary sequences (133/633) have been seen, so the fields above are meaningful.
+    pub integrated: bool,
+}
+
+impl ShellState {
+    /// Applies one OSC payload (without the `ESC ]` and terminator). Returns true if anything changed.
+    pub fn apply_osc(&mut self, payload: &str) -> bool {
+        let before = self.clone();
+        let (code, rest) = payload.split_once(';').unwrap_or((payload, ""));
+        match code {
+            "7" => {
+                if let Some(cwd) = parse_file_url(rest) {
+                    self.cwd = Some(cwd);
+                }
+            }
+            "133" | "633" => {
+                self.integrated = true;
+                let (kind, arg) = rest.split_once(';').unwrap_or((rest, ""));
+                match kind {
+                    "C" => self.command_running = true,
+                    // Shells also send D for the very first prompt; only a started command has an exit code.
+                    "D" if self.command_running => {
+                        self.command_running = false;
+                        self.last_exit_code = arg.split(';').next().and_then(|s| s.trim().parse().ok());
+                    }
+                    "D" => {}
+                    "E" if code == "633" => {
+                        // A trailing `;nonce` may follow; the command itself has `;` escaped.
+                        let command = arg.split(';').next().unwrap_or_default();
+                        self.last_command = Some(unescape_633(command));
+                    }
+                    "P" if code == "633" => {
+                        if let Some(cwd) = arg.strip_prefix("Cwd=") {
+                            self.cwd = Some(unescape_633(cwd));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        *self != before
+    }
+}
+
+/// `file://host/path` → `/path`, percent-decoded. The host part is ignored.
+fn parse_file_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+/// Reverses VS Code's OSC 633 escaping: `\\` and `\xAB`.
+fn unescape_633(s: &str) -> String {
+    let mut out = Vec::with_capacity(s.len());
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if bytes.get(i + 1) == Some(&b'\\') {
+                out.push(b'\\');
+                i += 2;
+                continue;
+            }
+            if bytes.get(i + 1) == Some(&b'x') {
+                if let Some(byte) = s.get(i + 2..i + 4).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    out.push(byte);
+                    i += 4;
+                    continue;
+                }
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_lifecycle() {
+        let mut state = ShellState::default();
+        // First prompt: D without a command changes nothing but marks integration.
+        assert!(state.apply_osc("133;D;0"));
+        assert_eq!(state.last_exit_code, None);
+        assert!(state.apply_osc("7;file://vm-1/home/me/my%20app"));
+        assert_eq!(state.cwd.as_deref(), Some("/home/me/my app"));
+
+        assert!(state.apply_osc("633;E;make test\\x3b echo done"));
+        assert!(state.apply_osc("133;C"));
+        assert!(state.command_running);
+        assert!(state.apply_osc("133;D;2"));
+        assert_eq!(state.last_command.as_deref(), Some("make test; echo done"));
+        assert_eq!(state.last_exit_code, Some(2));
+        assert!(!state.command_running);
+
+        assert!(!state.apply_osc("0;window title"));
+        assert!(state.apply_osc("633;P;Cwd=/srv/x\\\\y"));
+        assert_eq!(state.cwd.as_deref(), Some("/srv/x\\y"));
+    }
+}
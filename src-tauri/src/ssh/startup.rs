@@ -0,0 +1,516 @@
+use regex::Regex;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use super::osc::strip_escapes;
+use crate::workset::{RerunPolicy, StartupStep, StepWait};
+
+const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(300);
+
+const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The probe is typed again if the first copy was swallowed by a slow login (MOTD, rc files).
+const READY_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Starts every line this app types into a shell for its own bookkeeping. Bash leaves such
+/// lines out of history only when `HISTCONTROL` includes `ignorespace` (or `ignoreboth`), and
+/// zsh only with `HIST_IGNORE_SPACE` set; other setups record them like any other command.
+pub(crate) const UNLISTED_PREFIX: &str = " ";
+
+/// Typed into a fresh shell until it answers.
+fn ready_probe() -> String {
+    format!("{UNLISTED_PREFIX}printf '\\033]6973;ready\\007'\n")
+}
+
+const READY_MARKER: &[u8] = b"\x1b]6973;ready\x07";
+
+/// Output kept while a step is waiting; older bytes are dropped.
+const MAX_WAIT_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Private OSC the shell prints after an `exit`-waiting step: `ESC ] 6973;step-done;<status> BEL`.
+/// The echoed command line contains the escaped form (`\033`), so it never matches itself.
+const STEP_DONE_MARKER: &[u8] = b"\x1b]6973;step-done;";
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepState {
+    Running,
+    Done,
+    Failed,
+    TimedOut,
+    Skipped,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StartupProgress {
+    pub index: usize,
+    pub total: usize,
+    pub command: String,
+    pub state: StepState,
+    pub exit_status: Option<i32>,
+}
+
+pub enum StartupAction {
+    /// The shell is reading commands; comes just before the preamble is sent.
+    ShellReady,
+    /// Bytes to type into the shell.
+    Send(String),
+    Progress(StartupProgress),
+    /// The shell never showed the readiness marker or prompt; nothing further is sent.
+    ShellNotReady,
+}
+
+/// Nothing is typed into a new shell until it has proven it is reading commands.
+enum ShellGate {
+    Waiting {
+        deadline: Instant,
+        /// `None` when waiting for a prompt pattern instead of the injected probe.
+        next_probe: Option<Instant>,
+        seen: Vec<u8>,
+    },
+    Open,
+    NotReady,
+}
+
+enum Wait {
+    None,
+    Exit,
+    Pattern(Regex),
+}
+
+struct PlannedStep {
+    command: String,
+    /// Typed instead of `command` when set; progress still shows `command`.
+    line: Option<String>,
+    wait: Wait,
+    rerun: RerunPolicy,
+    timeout: Duration,
+}
+
+struct Waiting {
+    index: usize,
+    deadline: Instant,
+    seen: Vec<u8>,
+    /// Pattern steps ignore output up to the end of the echoed command line.
+    echo_pending: bool,
+    exit_status: Option<i32>,
+    matched: bool,
+}
+
+/// Drives a connection's startup steps through the interactive shell. Lives for the whole
+/// session worker so rerun policies can see what happened on earlier connections.
+pub struct StartupSequence {
+    ready_pattern: Option<Regex>,
+    ready_timeout: Duration,
+    gate: ShellGate,
+    /// Lines sent as soon as the shell is ready, ahead of the steps (env load, `cd`).
+    preamble: Vec<String>,
+    steps: Vec<PlannedStep>,
+    attempted: Vec<bool>,
+    succeeded: Vec<bool>,
+    next: usize,
+    waiting: Option<Waiting>,
+    /// Index of the AI CLI launch step, when there is one.
+    ai_cli_step: Option<usize>,
+    ai_cli_available: bool,
+}
+
+impl StartupSequence {
+    /// The AI CLI command, when set, runs as a final no-wait step on every connection.
+    /// Readiness is detected with `ready_pattern` against the shell output when given,
+    /// otherwise with an injected marker probe.
+    pub fn new(
+        steps: &[StartupStep],
+        ai_cli_command: Option<&str>,
+        ready_pattern: Option<&str>,
+        ready_timeout: Option<Duration>,
+    ) -> Self {
+        let mut planned: Vec<PlannedStep> = steps
+            .iter()
+            .map(|step| PlannedStep {
+                command: step.command.trim().to_string(),
+                line: None,
+                wait: match &step.wait {
+                    StepWait::None => Wait::None,
+                    StepWait::Exit => Wait::Exit,
+                    // Patterns are validated on save; fall back to a literal match for older files.
+                    StepWait::Pattern { pattern } => Wait::Pattern(
+                        Regex::new(pattern).unwrap_or_else(|_| Regex::new(&regex::escape(pattern)).expect("escaped regex")),
+                    ),
+                },
+                rerun: step.rerun,
+                timeout: step.timeout_secs.map(Duration::from_secs).unwrap_or(DEFAULT_STEP_TIMEOUT),
+            })
+            .collect();
+        let mut ai_cli_step = None;
+        if let Some(cmd) = ai_cli_command.map(str::trim).filter(|c| !c.is_empty()) {
+            ai_cli_step = Some(planned.len());
+            planned.push(PlannedStep {
+                command: cmd.to_string(),
+                line: None,
+                wait: Wait::None,
+                rerun: RerunPolicy::Always,
+                timeout: DEFAULT_STEP_TIMEOUT,
+            });
+        }
+        let len = planned.len();
+        Self {
+            ready_pattern: ready_pattern
+                .filter(|p| !p.is_empty())
+                .map(|p| Regex::new(p).unwrap_or_else(|_| Regex::new(&regex::escape(p)).expect("escaped regex"))),
+            ready_timeout: ready_timeout.unwrap_or(DEFAULT_READY_TIMEOUT),
+            gate: ShellGate::Open,
+            preamble: Vec::new(),
+            steps: planned,
+            attempted: vec![false; len],
+            succeeded: vec![false; len],
+            next: len,
+            waiting: None,
+            ai_cli_step,
+            ai_cli_available: true,
+        }
+    }
+
+    /// Types the AI CLI step through `wrap` (e.g. the supervisor's exit-reporting wrapper).
+    pub fn wrap_ai_cli(&mut self, wrap: impl Fn(&str) -> String) {
+        if let Some(step) = self.ai_cli_step.map(|index| &mut self.steps[index]) {
+            step.line = Some(wrap(&step.command));
+        }
+    }
+
+    /// Set per connection from the pre-launch check; an unavailable AI CLI is reported as skipped.
+    pub fn set_ai_cli_available(&mut self, available: bool) {
+        self.ai_cli_available = available;
+    }
+
+    /// Starts the sequence over for a fresh shell; `preamble` goes out once the shell is ready.
+    /// `wait_for_shell` runs the readiness gate even when nothing else is queued, so the caller
+    /// still gets `ShellReady`.
+    pub fn begin(&mut self, now: Instant, preamble: Vec<String>, wait_for_shell: bool) {
+        self.next = 0;
+        self.waiting = None;
+        self.gate = if !wait_for_shell && preamble.is_empty() && self.steps.is_empty() {
+            ShellGate::Open
+        } else {
+            ShellGate::Waiting {
+                deadline: now + self.ready_timeout,
+                next_probe: self.ready_pattern.is_none().then_some(now),
+                seen: Vec::new(),
+            }
+        };
+        self.preamble = preamble;
+    }
+
+    /// Advances the readiness gate; returns true once lines may be typed into the shell.
+    fn pass_gate(&mut self, now: Instant, actions: &mut Vec<StartupAction>) -> bool {
+        let ShellGate::Waiting { deadline, next_probe, seen } = &mut self.gate else {
+            return matches!(self.gate, ShellGate::Open);
+        };
+        let ready = match &self.ready_pattern {
+            Some(re) => re.is_match(&strip_escapes(&String::from_utf8_lossy(seen))),
+            None => seen.windows(READY_MARKER.len()).any(|w| w == READY_MARKER),
+        };
+        if ready {
+            self.gate = ShellGate::Open;
+            actions.push(StartupAction::ShellReady);
+            actions.extend(self.preamble.drain(..).map(StartupAction::Send));
+            return true;
+        }
+        if now >= *deadline {
+            self.gate = ShellGate::NotReady;
+            actions.push(StartupAction::ShellNotReady);
+            for index in 0..self.steps.len() {
+                actions.push(self.progress(index, StepState::Skipped, None));
+            }
+            self.next = self.steps.len();
+            return false;
+        }
+        if let Some(at) = next_probe.filter(|at| now >= *at) {
+            *next_probe = Some(at + READY_PROBE_INTERVAL);
+            actions.push(StartupAction::Send(ready_probe()));
+        }
+        false
+    }
+
+    fn should_run(&self, index: usize) -> bool {
+        if self.ai_cli_step == Some(index) && !self.ai_cli_available {
+            return false;
+        }
+        match self.steps[index].rerun {
+            RerunPolicy::Always => true,
+            RerunPolicy::Once => !self.attempted[index],
+            RerunPolicy::UntilSuccess => !self.succeeded[index],
+        }
+    }
+
+    fn progress(&self, index: usize, state: StepState, exit_status: Option<i32>) -> StartupAction {
+        StartupAction::Progress(StartupProgress {
+            index,
+            total: self.steps.len(),
+            command: self.steps[index].command.clone(),
+            state,
+            exit_status,
+        })
+    }
+
+    /// Feeds shell output to the readiness gate or the step currently waiting, if any.
+    pub fn observe(&mut self, data: &[u8]) {
+        if let ShellGate::Waiting { seen, .. } = &mut self.gate {
+            push_capped(seen, data);
+            return;
+        }
+        let Some(waiting) = self.waiting.as_mut() else {
+            return;
+        };
+        let mut data = data;
+        if waiting.echo_pending {
+            match data.iter().position(|&b| b == b'\n') {
+                Some(pos) => {
+                    waiting.echo_pending = false;
+                    data = &data[pos + 1..];
+                }
+                None => return,
+            }
+        }
+        push_capped(&mut waiting.seen, data);
+        match &self.steps[waiting.index].wait {
+            Wait::None => {}
+            Wait::Exit => waiting.exit_status = find_exit_marker(&waiting.seen),
+            Wait::Pattern(re) => waiting.matched = re.is_match(&strip_escapes(&String::from_utf8_lossy(&waiting.seen))),
+        }
+    }
+
+    /// Settles the waiting step and sends further steps until one has to wait.
+    pub fn poll(&mut self, now: Instant) -> Vec<StartupAction> {
+        let mut actions = Vec::new();
+        if !self.pass_gate(now, &mut actions) {
+            return actions;
+        }
+
+        if let Some(waiting) = &self.waiting {
+            let state = match waiting.exit_status {
+                Some(0) => StepState::Done,
+                Some(_) => StepState::Failed,
+                None if waiting.matched => StepState::Done,
+                None if now >= waiting.deadline => StepState::TimedOut,
+                None => return actions,
+            };
+            let (index, exit_status) = (waiting.index, waiting.exit_status);
+            self.waiting = None;
+            actions.push(self.progress(index, state, exit_status));
+            if state == StepState::Done {
+                self.succeeded[index] = true;
+            } else {
+                // A failed step leaves the shell in an unknown state; don't build on it.
+                for rest in self.next..self.steps.len() {
+                    actions.push(self.progress(rest, StepState::Skipped, None));
+                }
+                self.next = self.steps.len();
+                return actions;
+            }
+        }
+
+        while self.next < self.steps.len() {
+            let index = self.next;
+            self.next += 1;
+            if !self.should_run(index) {
+                actions.push(self.progress(index, StepState::Skipped, None));
+                continue;
+            }
+            self.attempted[index] = true;
+
+            let step = &self.steps[index];
+            match step.wait {
+                Wait::None => {
+                    actions.push(StartupAction::Send(format!("{}\n", step.line.as_ref().unwrap_or(&step.command))));
+                    self.succeeded[index] = true;
+                    actions.push(self.progress(index, StepState::Done, None));
+                }
+                Wait::Exit | Wait::Pattern(_) => {
+                    let line = match step.wait {
+                        Wait::Exit => format!("{}; printf '\\033]6973;step-done;%d\\007' \"$?\"\n", step.command),
+                        _ => format!("{}\n", step.command),
+                    };
+                    self.waiting = Some(Waiting {
+                        index,
+                        deadline: now + step.timeout,
+                        seen: Vec::new(),
+                        echo_pending: matches!(step.wait, Wait::Pattern(_)),
+                        exit_status: None,
+                        matched: false,
+                    });
+                    actions.push(StartupAction::Send(line));
+                    actions.push(self.progress(index, StepState::Running, None));
+                    break;
+                }
+            }
+        }
+        actions
+    }
+}
+
+fn push_capped(seen: &mut Vec<u8>, data: &[u8]) {
+    seen.extend_from_slice(data);
+    if seen.len() > MAX_WAIT_OUTPUT_BYTES {
+        let excess = seen.len() - MAX_WAIT_OUTPUT_BYTES;
+        seen.drain(..excess);
+    }
+}
+
+fn find_exit_marker(seen: &[u8]) -> Option<i32> {
+    let start = seen.windows(STEP_DONE_MARKER.len()).position(|w| w == STEP_DONE_MARKER)? + STEP_DONE_MARKER.len();
+    let rest = &seen[start..];
+    let end = rest.iter().position(|&b| b == 0x07)?;
+    std::str::from_utf8(&rest[..end]).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(command: &str, wait: StepWait, rerun: RerunPolicy) -> StartupStep {
+        StartupStep {
+            command: command.to_string(),
+            wait,
+            rerun,
+            timeout_secs: None,
+        }
+    }
+
+    /// Begins a connection whose shell has already answered the readiness probe.
+    fn ready(seq: &mut StartupSequence) {
+        seq.begin(Instant::now(), Vec::new(), false);
+        seq.observe(READY_MARKER);
+    }
+
+    fn sent(actions: &[StartupAction]) -> Vec<String> {
+        actions
+            .iter()
+            .filter_map(|a| match a {
+                StartupAction::Send(s) => Some(s.clone()),
+                StartupAction::ShellReady | StartupAction::Progress(_) | StartupAction::ShellNotReady => None,
+            })
+            .collect()
+    }
+
+    fn states(actions: &[StartupAction]) -> Vec<(usize, StepState)> {
+        actions
+            .iter()
+            .filter_map(|a| match a {
+                StartupAction::Progress(p) => Some((p.index, p.state)),
+                StartupAction::ShellReady | StartupAction::Send(_) | StartupAction::ShellNotReady => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_exit_wait_gates_next_step() {
+        let steps = [
+            step("git pull", StepWait::Exit, RerunPolicy::Always),
+            step("nvm use", StepWait::None, RerunPolicy::Always),
+        ];
+        let mut seq = StartupSequence::new(&steps, Some("claude"), None, None);
+        ready(&mut seq);
+        let now = Instant::now();
+        let first = seq.poll(now);
+        assert_eq!(sent(&first), ["git pull; printf '\\033]6973;step-done;%d\\007' \"$?\"\n"]);
+        assert!(seq.poll(now).is_empty());
+
+        seq.observe(b"Already up to date.\r\n\x1b]6973;step-d");
+        assert!(seq.poll(now).is_empty());
+        seq.observe(b"one;0\x07$ ");
+        let rest = seq.poll(now);
+        assert_eq!(sent(&rest), ["nvm use\n", "claude\n"]);
+        assert_eq!(states(&rest), [(0, StepState::Done), (1, StepState::Done), (2, StepState::Done)]);
+    }
+
+    #[test]
+    fn test_failure_skips_rest() {
+        let steps = [step("make setup", StepWait::Exit, RerunPolicy::Always)];
+        let mut seq = StartupSequence::new(&steps, Some("claude"), None, None);
+        ready(&mut seq);
+        seq.poll(Instant::now());
+        seq.observe(b"\x1b]6973;step-done;2\x07");
+        let actions = seq.poll(Instant::now());
+        assert!(sent(&actions).is_empty());
+        assert_eq!(states(&actions), [(0, StepState::Failed), (1, StepState::Skipped)]);
+    }
+
+    #[test]
+    fn test_pattern_ignores_echo_and_escapes() {
+        let steps = [step("npm run dev", StepWait::Pattern { pattern: "ready in \\d+".into() }, RerunPolicy::Always)];
+        let mut seq = StartupSequence::new(&steps, None, None, None);
+        ready(&mut seq);
+        seq.poll(Instant::now());
+        seq.observe(b"npm run dev # ready in 5\r\n");
+        assert!(states(&seq.poll(Instant::now())).is_empty());
+        seq.observe(b"\x1b[32mready\x1b[0m in 312 ms\r\n");
+        assert_eq!(states(&seq.poll(Instant::now())), [(0, StepState::Done)]);
+    }
+
+    #[test]
+    fn test_timeout_and_rerun_policies() {
+        let mut timed = step("./wait-for-db", StepWait::Exit, RerunPolicy::UntilSuccess);
+        timed.timeout_secs = Some(5);
+        let steps = [
+            step("pip install -r requirements.txt", StepWait::None, RerunPolicy::Once),
+            timed,
+        ];
+        let mut seq = StartupSequence::new(&steps, None, None, None);
+        let now = Instant::now();
+        ready(&mut seq);
+        seq.poll(now);
+        assert_eq!(states(&seq.poll(now + Duration::from_secs(6))), [(1, StepState::TimedOut)]);
+
+        // Reconnect: the run-once step is skipped, the timed-out step tries again.
+        ready(&mut seq);
+        let actions = seq.poll(now);
+        assert_eq!(states(&actions), [(0, StepState::Skipped), (1, StepState::Running)]);
+        seq.observe(b"\x1b]6973;step-done;0\x07");
+        seq.poll(now);
+
+        ready(&mut seq);
+        assert_eq!(states(&seq.poll(now)), [(0, StepState::Skipped), (1, StepState::Skipped)]);
+    }
+
+    #[test]
+    fn test_ready_probe_gates_preamble() {
+        let steps = [step("git pull", StepWait::None, RerunPolicy::Always)];
+        let mut seq = StartupSequence::new(&steps, None, None, None);
+        let now = Instant::now();
+        seq.begin(now, vec!["cd '/srv/app'\n".to_string()], false);
+        assert_eq!(sent(&seq.poll(now)), [ready_probe()]);
+        assert!(seq.poll(now + Duration::from_secs(1)).is_empty());
+        assert_eq!(sent(&seq.poll(now + READY_PROBE_INTERVAL)), [ready_probe()]);
+
+        seq.observe(b"Welcome to Ubuntu\r\n\x1b]6973;rea");
+        seq.observe(b"dy\x07$ ");
+        let actions = seq.poll(now + READY_PROBE_INTERVAL);
+        assert!(matches!(actions.first(), Some(StartupAction::ShellReady)));
+        assert_eq!(sent(&actions), ["cd '/srv/app'\n", "git pull\n"]);
+    }
+
+    #[test]
+    fn test_wait_for_shell_gates_empty_sequence() {
+        let mut seq = StartupSequence::new(&[], None, None, None);
+        let now = Instant::now();
+        seq.begin(now, Vec::new(), true);
+        assert_eq!(sent(&seq.poll(now)), [ready_probe()]);
+        seq.observe(READY_MARKER);
+        assert!(matches!(seq.poll(now).as_slice(), [StartupAction::ShellReady]));
+    }
+
+    #[test]
+    fn test_prompt_pattern_timeout_reports_not_ready() {
+        let steps = [step("git pull", StepWait::None, RerunPolicy::Always)];
+        let mut seq = StartupSequence::new(&steps, None, Some(r"\$ $"), Some(Duration::from_secs(10)));
+        let now = Instant::now();
+        seq.begin(now, Vec::new(), false);
+        seq.observe(b"Last login: Mon\r\n");
+        assert!(seq.poll(now).is_empty());
+        let actions = seq.poll(now + Duration::from_secs(10));
+        assert!(matches!(actions.first(), Some(StartupAction::ShellNotReady)));
+        assert_eq!(states(&actions), [(0, StepState::Skipped)]);
+        assert!(seq.poll(now + Duration::from_secs(11)).is_empty());
+    }
+}
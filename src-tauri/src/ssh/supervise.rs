@@ -0,0 +1,178 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use super::startup::UNLISTED_PREFIX;
+use crate::workset::AutoRestart;
+
+/// Delay before the first automatic relaunch; doubles with each quick failure.
+const BASE_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A run at least this long resets the backoff.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// Consecutive short-lived runs after which auto-restart stops.
+const MAX_QUICK_RESTARTS: u32 = 5;
+
+/// Runs `command` in a subshell that reports its pid (`ESC ] 6973;ai-start;<pid> BEL`) before
+/// starting and the exit status (`ESC ] 6973;ai-exit;<status> BEL`) afterwards. The subshell is
+/// the job's process group leader, so its pid identifies the whole AI CLI job. As with startup
+/// steps, the echoed line only contains the escaped `\033` form and never matches itself.
+pub fn wrap_command(command: &str) -> String {
+    format!(
+        "{UNLISTED_PREFIX}( printf '\\033]6973;ai-start;%s\\007' \"$(exec sh -c 'echo $PPID')\"; {command} ); \
+         printf '\\033]6973;ai-exit;%d\\007' \"$?\""
+    )
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Marker {
+    Started(u32),
+    Exited(i32),
+}
+
+pub fn parse_marker(payload: &str) -> Option<Marker> {
+    let rest = payload.strip_prefix("6973;ai-")?;
+    if let Some(pid) = rest.strip_prefix("start;") {
+        return pid.trim().parse().ok().map(Marker::Started);
+    }
+    rest.strip_prefix("exit;")?.trim().parse().ok().map(Marker::Exited)
+}
+
+/// Payload of `ai-cli-exited-{id}`.
+#[derive(Serialize, Clone, Debug)]
+pub struct AiCliExit {
+    pub pid: Option<u32>,
+    /// `None` when the shell or connection went away before the wrapper reported.
+    pub exit_code: Option<i32>,
+    /// Set when auto-restart will relaunch the AI CLI.
+    pub restart_in_secs: Option<u64>,
+    /// Auto-restart stopped after too many quick failures.
+    pub gave_up: bool,
+    pub epoch: u64,
+}
+
+/// Tracks the wrapped AI CLI across runs and schedules relaunches per the connection's policy.
+pub struct AiCliSupervisor {
+    policy: AutoRestart,
+    pid: Option<u32>,
+    started_at: Option<Instant>,
+    quick_restarts: u32,
+    pending_restart: Option<Instant>,
+}
+
+impl AiCliSupervisor {
+    pub fn new(policy: AutoRestart) -> Self {
+        Self {
+            policy,
+            pid: None,
+            started_at: None,
+            quick_restarts: 0,
+            pending_restart: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn started(&mut self, pid: u32, now: Instant) {
+        self.pid = Some(pid);
+        self.started_at = Some(now);
+        self.pending_restart = None;
+    }
+
+    /// Records an exit and decides whether to relaunch. `allow_restart` is false when the exit
+    /// was caused on purpose (a manual restart) or can't be acted on (connection lost).
+    pub fn exited(&mut self, exit_code: Option<i32>, now: Instant, epoch: u64, allow_restart: bool) -> AiCliExit {
+        let ran_for = self.started_at.take().map(|at| now.duration_since(at));
+        if ran_for.is_some_and(|d| d >= STABLE_RUN) {
+            self.quick_restarts = 0;
+        }
+        let wanted = allow_restart
+            && match self.policy {
+                AutoRestart::Never => false,
+                AutoRestart::OnFailure => exit_code != Some(0),
+                AutoRestart::Always => true,
+            };
+        let gave_up = wanted && self.quick_restarts >= MAX_QUICK_RESTARTS;
+        let restart_in = (wanted && !gave_up).then(|| {
+            let delay = BASE_BACKOFF
+                .saturating_mul(1 << self.quick_restarts.min(16))
+                .min(MAX_BACKOFF);
+            self.quick_restarts += 1;
+            self.pending_restart = Some(now + delay);
+            delay
+        });
+        AiCliExit {
+            pid: self.pid.take(),
+            exit_code,
+            restart_in_secs: restart_in.map(|d| d.as_secs()),
+            gave_up,
+            epoch,
+        }
+    }
+
+    /// True once when a scheduled relaunch is due.
+    pub fn restart_due(&mut self, now: Instant) -> bool {
+        if self.pending_restart.is_some_and(|at| now >= at) {
+            self.pending_restart = None;
+            return true;
+        }
+        false
+    }
+
+    /// Drops a scheduled relaunch and forgets the current run (manual restart, reconnect).
+    pub fn reset(&mut self) {
+        self.pid = None;
+        self.started_at = None;
+        self.pending_restart = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers() {
+        let line = wrap_command("claude --continue");
+        assert!(line.starts_with(" ( printf '\\033]6973;ai-start;"));
+        assert!(line.contains("; claude --continue ); printf '\\033]6973;ai-exit;%d\\007' \"$?\""));
+        assert_eq!(parse_marker("6973;ai-start;812"), Some(Marker::Started(812)));
+        assert_eq!(parse_marker("6973;ai-exit;130"), Some(Marker::Exited(130)));
+        assert_eq!(parse_marker("6973;step-done;0"), None);
+    }
+
+    #[test]
+    fn test_policy_and_backoff() {
+        let now = Instant::now();
+        let mut clean = AiCliSupervisor::new(AutoRestart::OnFailure);
+        clean.started(10, now);
+        let exit = clean.exited(Some(0), now, 1, true);
+        assert_eq!((exit.pid, exit.restart_in_secs), (Some(10), None));
+
+        let mut sup = AiCliSupervisor::new(AutoRestart::OnFailure);
+        let mut delays = Vec::new();
+        for _ in 0..6 {
+            sup.started(11, now);
+            let exit = sup.exited(Some(1), now, 1, true);
+            delays.push(exit.restart_in_secs);
+            if exit.gave_up {
+                break;
+            }
+        }
+        assert_eq!(delays, [Some(2), Some(4), Some(8), Some(16), Some(32), None]);
+        assert!(!sup.restart_due(now + MAX_BACKOFF), "nothing scheduled after giving up");
+
+        // A long, stable run resets the backoff; a deliberate exit never schedules one.
+        sup.started(12, now);
+        let end = now + STABLE_RUN;
+        assert_eq!(sup.exited(Some(1), end, 2, true).restart_in_secs, Some(2));
+        assert!(!sup.restart_due(end + Duration::from_secs(1)));
+        assert!(sup.restart_due(end + BASE_BACKOFF));
+        assert!(!sup.restart_due(end + BASE_BACKOFF), "fires once");
+        sup.started(13, now);
+        assert_eq!(sup.exited(Some(130), now, 3, false).restart_in_secs, None);
+    }
+}
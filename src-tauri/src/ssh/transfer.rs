@@ -0,0 +1,653 @@
+use serde::Serialize;
+use ssh2::{FileStat, FileType, OpenFlags, OpenType, Session, Sftp};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use uuid::Uuid;
+
+use super::session::SessionClient;
+
+/// Longest a single SFTP operation may stay blocked before the transfer is failed.
+const OP_TIMEOUT: Duration = Duration::from_secs(30);
+const CHUNK_SIZE: usize = 32 * 1024;
+const PROGRESS_EMIT_INTERVAL: Duration = Duration::from_millis(250);
+
+// ── Non-blocking SFTP helpers ──
+//
+// Sessions run in non-blocking mode once connected, so every libssh2 call can
+// return WouldBlock and has to be retried until it completes or times out.
+
+pub(crate) fn retry_would_block<T>(
+    timeout: Duration,
+    mut op: impl FnMut() -> Result<T, ssh2::Error>,
+) -> Result<T, std::io::Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match op() {
+            Ok(v) => return Ok(v),
+            Err(e) => {
+                let io_err: std::io::Error = e.into();
+                if io_err.kind() == std::io::ErrorKind::WouldBlock {
+                    if Instant::now() >= deadline {
+                        return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out"));
+                    }
+                    thread::sleep(Duration::from_millis(5));
+                    continue;
+                }
+                return Err(io_err);
+            }
+        }
+    }
+}
+
+pub(crate) fn read_chunk(reader: &mut impl Read, buf: &mut [u8], what: &str) -> Result<usize, String> {
+    let deadline = Instant::now() + OP_TIMEOUT;
+    loop {
+        match reader.read(buf) {
+            Ok(n) => return Ok(n),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(format!("read {what}: timed out"));
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            Err(e) => return Err(format!("read {what}: {e}")),
+        }
+    }
+}
+
+pub(crate) fn write_all(writer: &mut impl Write, data: &[u8], what: &str) -> Result<(), String> {
+    let deadline = Instant::now() + OP_TIMEOUT;
+    let mut offset = 0;
+    while offset < data.len() {
+        match writer.write(&data[offset..]) {
+            Ok(0) => return Err(format!("write {what}: wrote 0 bytes")),
+            Ok(n) => offset += n,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(format!("write {what}: timed out"));
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            Err(e) => return Err(format!("write {what}: {e}")),
+        }
+    }
+    Ok(())
+}
+
+/// SFTP handle on a leased session with WouldBlock handling baked in.
+pub(crate) struct RemoteFs {
+    sftp: Sftp,
+}
+
+impl RemoteFs {
+    pub fn open(sess: &Session) -> Result<Self, String> {
+        let sftp = retry_would_block(OP_TIMEOUT, || sess.sftp()).map_err(|e| format!("sftp init: {e}"))?;
+        Ok(Self { sftp })
+    }
+
+    /// Returns None when the path does not exist.
+    pub fn stat(&self, path: &Path) -> Result<Option<FileStat>, String> {
+        match retry_would_block(OP_TIMEOUT, || self.sftp.stat(path)) {
+            Ok(s) => Ok(Some(s)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("sftp stat {}: {e}", path.display())),
+        }
+    }
+
+    pub fn readdir(&self, path: &Path) -> Result<Vec<(PathBuf, FileStat)>, String> {
+        retry_would_block(OP_TIMEOUT, || self.sftp.readdir(path))
+            .map_err(|e| format!("sftp readdir {}: {e}", path.display()))
+    }
+
+    /// Creates a directory, succeeding if it already exists as a directory.
+    pub fn ensure_dir(&self, path: &Path, mode: i32) -> Result<(), String> {
+        if let Some(existing) = self.stat(path)? {
+            if existing.is_dir() {
+                return Ok(());
+            }
+            return Err(format!("{} exists and is not a directory", path.display()));
+        }
+        retry_would_block(OP_TIMEOUT, || self.sftp.mkdir(path, mode))
+            .map_err(|e| format!("sftp mkdir {}: {e}", path.display()))
+    }
+
+    pub fn open_read(&self, path: &Path) -> Result<ssh2::File, String> {
+        retry_would_block(OP_TIMEOUT, || self.sftp.open(path))
+            .map_err(|e| format!("sftp open {}: {e}", path.display()))
+    }
+
+    pub fn create(&self, path: &Path, mode: i32) -> Result<ssh2::File, String> {
+        retry_would_block(OP_TIMEOUT, || {
+            self.sftp
+                .open_mode(path, OpenFlags::WRITE | OpenFlags::TRUNCATE, mode, OpenType::File)
+        })
+        .map_err(|e| format!("sftp create {}: {e}", path.display()))
+    }
+
+    pub fn unlink(&self, path: &Path) {
+        let _ = retry_would_block(OP_TIMEOUT, || self.sftp.unlink(path));
+    }
+}
+
+pub(crate) fn close_file(file: &mut ssh2::File) {
+    let _ = retry_would_block(OP_TIMEOUT, || file.close());
+}
+
+pub(crate) fn mode_bits(stat: &FileStat, default: i32) -> i32 {
+    stat.perm.map(|p| (p & 0o777) as i32).unwrap_or(default)
+}
+
+// ── Remote tree walking ──
+
+#[derive(Clone, Debug)]
+pub(crate) struct TreeEntry {
+    /// Path relative to the walked root, '/'-separated; empty for the root itself.
+    pub rel: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub mode: i32,
+}
+
+/// Lists `root` and, if it is a directory, everything below it (parents before children).
+/// Symlinks and special files are skipped; their count is returned alongside the entries.
+pub(crate) fn walk_remote(fs: &RemoteFs, root: &Path) -> Result<(Vec<TreeEntry>, u32), String> {
+    let stat = fs
+        .stat(root)?
+        .ok_or_else(|| format!("{}: no such file or directory", root.display()))?;
+    walk_tree(root, &stat, |dir| fs.readdir(dir))
+}
+
+/// The walk behind `walk_remote`, with directory listing passed in.
+fn walk_tree(
+    root: &Path,
+    stat: &FileStat,
+    mut readdir: impl FnMut(&Path) -> Result<Vec<(PathBuf, FileStat)>, String>,
+) -> Result<(Vec<TreeEntry>, u32), String> {
+    if !stat.is_dir() {
+        let entry = TreeEntry {
+            rel: String::new(),
+            is_dir: false,
+            size: stat.size.unwrap_or(0),
+            mode: mode_bits(stat, 0o644),
+        };
+        return Ok((vec![entry], 0));
+    }
+
+    let mut out = vec![TreeEntry {
+        rel: String::new(),
+        is_dir: true,
+        size: 0,
+        mode: mode_bits(stat, 0o755),
+    }];
+    let mut skipped = 0u32;
+    let mut pending: Vec<String> = vec![String::new()];
+
+    while let Some(rel_dir) = pending.pop() {
+        let dir = remote_join(root, &rel_dir);
+        let mut children = readdir(&dir)?;
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (child, child_stat) in children {
+            let name = match child.file_name() {
+                Some(n) => n.to_string_lossy().to_string(),
+                None => continue,
+            };
+            if name == "." || name == ".." {
+                continue;
+            }
+            let rel = if rel_dir.is_empty() {
+                name
+            } else {
+                format!("{rel_dir}/{name}")
+            };
+
+            match child_stat.file_type() {
+                FileType::Directory => {
+                    out.push(TreeEntry {
+                        rel: rel.clone(),
+                        is_dir: true,
+                        size: 0,
+                        mode: mode_bits(&child_stat, 0o755),
+                    });
+                    pending.push(rel);
+                }
+                FileType::RegularFile => out.push(TreeEntry {
+                    rel,
+                    is_dir: false,
+                    size: child_stat.size.unwrap_or(0),
+                    mode: mode_bits(&child_stat, 0o644),
+                }),
+                _ => skipped += 1,
+            }
+        }
+    }
+
+    Ok((out, skipped))
+}
+
+/// Joins a `/`-separated relative path onto a local path.
+pub(crate) fn join_rel(root: &Path, rel: &str) -> PathBuf {
+    if rel.is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(rel)
+    }
+}
+
+/// Joins `rel` onto a remote path with `/`. `Path::join` uses the local separator,
+/// which is `\` on Windows.
+pub(crate) fn remote_join(base: &Path, rel: &str) -> PathBuf {
+    let base = base.to_string_lossy();
+    let rel = rel.trim_start_matches('/');
+    if rel.is_empty() {
+        return PathBuf::from(base.as_ref());
+    }
+    match base.trim_end_matches('/') {
+        "" if base.starts_with('/') => PathBuf::from(format!("/{rel}")),
+        "" => PathBuf::from(rel),
+        b => PathBuf::from(format!("{b}/{rel}")),
+    }
+}
+
+// ── Progress reporting ──
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TransferProgress {
+    pub transfer_id: String,
+    pub state: TransferState,
+    pub bytes_done: u64,
+    /// None when the size is not known up front (e.g. streamed archives).
+    pub bytes_total: Option<u64>,
+    pub files_done: u32,
+    pub files_total: Option<u32>,
+    pub skipped: u32,
+    pub current_path: Option<String>,
+    pub error: Option<String>,
+}
+
+impl TransferProgress {
+    /// Records the final state; an error caused by cancellation counts as cancelled, not failed.
+    fn settle(&mut self, result: Result<(), String>, cancelled: bool) {
+        self.current_path = None;
+        match result {
+            Ok(()) => self.state = TransferState::Completed,
+            Err(_) if cancelled => self.state = TransferState::Cancelled,
+            Err(e) => {
+                self.state = TransferState::Failed;
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+/// Lets an event through at most once per interval.
+struct Throttle {
+    interval: Duration,
+    last: Instant,
+}
+
+impl Throttle {
+    fn new(interval: Duration, now: Instant) -> Self {
+        Self { interval, last: now }
+    }
+
+    fn ready(&self, now: Instant) -> bool {
+        now.duration_since(self.last) >= self.interval
+    }
+
+    fn mark(&mut self, now: Instant) {
+        self.last = now;
+    }
+}
+
+/// Emits `transfer-progress-{id}` events, throttled while running.
+pub(crate) struct ProgressReporter {
+    app_handle: tauri::AppHandle,
+    event: String,
+    throttle: Throttle,
+    pub progress: TransferProgress,
+}
+
+impl ProgressReporter {
+    pub fn new(app_handle: tauri::AppHandle, transfer_id: &str) -> Self {
+        Self {
+            app_handle,
+            event: format!("transfer-progress-{transfer_id}"),
+            throttle: Throttle::new(PROGRESS_EMIT_INTERVAL, Instant::now()),
+            progress: TransferProgress {
+                transfer_id: transfer_id.to_string(),
+                state: TransferState::Running,
+                bytes_done: 0,
+                bytes_total: None,
+                files_done: 0,
+                files_total: None,
+                skipped: 0,
+                current_path: None,
+                error: None,
+            },
+        }
+    }
+
+    pub fn emit_now(&mut self) {
+        let _ = self.app_handle.emit(&self.event, self.progress.clone());
+        self.throttle.mark(Instant::now());
+    }
+
+    pub fn add_bytes(&mut self, n: u64) {
+        self.progress.bytes_done += n;
+        if self.throttle.ready(Instant::now()) {
+            self.emit_now();
+        }
+    }
+
+    pub fn start_file(&mut self, path: &str) {
+        self.progress.current_path = Some(path.to_string());
+        if self.throttle.ready(Instant::now()) {
+            self.emit_now();
+        }
+    }
+
+    pub fn finish(&mut self, result: Result<(), String>, cancel: &AtomicBool) {
+        self.progress.settle(result, cancel.load(Ordering::SeqCst));
+        self.emit_now();
+    }
+}
+
+pub(crate) fn check_cancelled(cancel: &AtomicBool) -> Result<(), String> {
+    if cancel.load(Ordering::SeqCst) {
+        Err("cancelled".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// ── Registry ──
+
+/// Cancellation flags for in-flight transfers, keyed by transfer id.
+#[derive(Clone, Default)]
+pub struct TransferRegistry {
+    active: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl TransferRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn register(&self) -> (String, Arc<AtomicBool>) {
+        let id = Uuid::new_v4().to_string();
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        active.insert(id.clone(), flag.clone());
+        (id, flag)
+    }
+
+    pub(crate) fn finish(&self, id: &str) {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        active.remove(id);
+    }
+
+    pub fn cancel(&self, id: &str) -> bool {
+        let active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        match active.get(id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        let active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        for flag in active.values() {
+            flag.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+// ── VM-to-VM copy ──
+
+/// Resolves the destination like `cp -r`: copying into an existing directory
+/// places the source under its own name, otherwise the destination path is used as-is.
+fn resolve_copy_target(dst_fs: &RemoteFs, src: &Path, dst: &Path) -> Result<PathBuf, String> {
+    match dst_fs.stat(dst)? {
+        Some(s) if s.is_dir() => {
+            let name = src
+                .file_name()
+                .ok_or_else(|| format!("cannot derive a file name from {}", src.display()))?;
+            Ok(remote_join(dst, &name.to_string_lossy()))
+        }
+        _ => Ok(dst.to_path_buf()),
+    }
+}
+
+fn copy_remote_file(
+    src_fs: &RemoteFs,
+    dst_fs: &RemoteFs,
+    src: &Path,
+    dst: &Path,
+    mode: i32,
+    reporter: &mut ProgressReporter,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let mut reader = src_fs.open_read(src)?;
+    let mut writer = dst_fs.create(dst, mode)?;
+    let src_label = src.display().to_string();
+    let dst_label = dst.display().to_string();
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let result = (|| loop {
+        check_cancelled(cancel)?;
+        let n = read_chunk(&mut reader, &mut buf, &src_label)?;
+        if n == 0 {
+            return Ok(());
+        }
+        write_all(&mut writer, &buf[..n], &dst_label)?;
+        reporter.add_bytes(n as u64);
+    })();
+
+    close_file(&mut reader);
+    close_file(&mut writer);
+    if result.is_err() {
+        // Do not leave a partially written file behind.
+        dst_fs.unlink(dst);
+    }
+    result
+}
+
+fn run_remote_copy(
+    src_sess: &Session,
+    src_path: &Path,
+    dst_sess: &Session,
+    dst_path: &Path,
+    reporter: &mut ProgressReporter,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let src_fs = RemoteFs::open(src_sess)?;
+    let dst_fs = RemoteFs::open(dst_sess)?;
+
+    let (entries, skipped) = walk_remote(&src_fs, src_path)?;
+    let target = resolve_copy_target(&dst_fs, src_path, dst_path)?;
+
+    reporter.progress.bytes_total = Some(entries.iter().map(|e| e.size).sum());
+    reporter.progress.files_total = Some(entries.iter().filter(|e| !e.is_dir).count() as u32);
+    reporter.progress.skipped = skipped;
+    reporter.emit_now();
+
+    for entry in &entries {
+        check_cancelled(cancel)?;
+        let src = remote_join(src_path, &entry.rel);
+        let dst = remote_join(&target, &entry.rel);
+        if entry.is_dir {
+            dst_fs.ensure_dir(&dst, entry.mode)?;
+        } else {
+            reporter.start_file(&entry.rel);
+            copy_remote_file(&src_fs, &dst_fs, &src, &dst, entry.mode, reporter, cancel)?;
+            reporter.progress.files_done += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Starts a background copy between two sessions and returns its transfer id.
+/// Progress and the final state are reported on `transfer-progress-{id}`.
+pub fn start_remote_copy(
+    registry: &TransferRegistry,
+    src: &SessionClient,
+    src_path: String,
+    dst: &SessionClient,
+    dst_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    if src_path.trim().is_empty() || dst_path.trim().is_empty() {
+        return Err("source and destination paths must not be empty".to_string());
+    }
+    let src_sess = src.lease_session().map_err(|e| e.to_string())?;
+    let dst_sess = dst.lease_session().map_err(|e| e.to_string())?;
+
+    let (transfer_id, cancel) = registry.register();
+    let registry = registry.clone();
+    let id = transfer_id.clone();
+
+    thread::spawn(move || {
+        let mut reporter = ProgressReporter::new(app_handle, &id);
+        let result = run_remote_copy(
+            &src_sess,
+            Path::new(&src_path),
+            &dst_sess,
+            Path::new(&dst_path),
+            &mut reporter,
+            &cancel,
+        );
+        reporter.finish(result, &cancel);
+        registry.finish(&id);
+    });
+
+    Ok(transfer_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(perm: u32, size: u64) -> FileStat {
+        FileStat {
+            size: Some(size),
+            uid: None,
+            gid: None,
+            perm: Some(perm),
+            atime: None,
+            mtime: None,
+        }
+    }
+
+    #[test]
+    fn test_remote_join() {
+        assert_eq!(remote_join(Path::new("/srv/app"), "src/main.rs"), PathBuf::from("/srv/app/src/main.rs"));
+        assert_eq!(remote_join(Path::new("/srv/app/"), ""), PathBuf::from("/srv/app/"));
+        assert_eq!(remote_join(Path::new("/"), "etc"), PathBuf::from("/etc"));
+        assert_eq!(remote_join(Path::new(""), "app"), PathBuf::from("app"));
+        assert_eq!(remote_join(Path::new("."), "app"), PathBuf::from("./app"));
+    }
+
+    #[test]
+    fn test_walk_tree() {
+        let root = Path::new("/srv/app");
+        let (entries, skipped) = walk_tree(root, &stat(0o040750, 0), |dir| {
+            Ok(match dir.to_str().unwrap() {
+                "/srv/app" => vec![
+                    (root.join("src"), stat(0o040755, 0)),
+                    (root.join("README.md"), stat(0o100644, 12)),
+                    (root.join("link"), stat(0o120777, 0)),
+                ],
+                "/srv/app/src" => vec![(root.join("src/main.rs"), stat(0o100600, 40))],
+                other => return Err(format!("unexpected readdir {other}")),
+            })
+        })
+        .unwrap();
+        let listed: Vec<(&str, bool, u64, i32)> = entries
+            .iter()
+            .map(|e| (e.rel.as_str(), e.is_dir, e.size, e.mode))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("", true, 0, 0o750),
+                ("README.md", false, 12, 0o644),
+                ("src", true, 0, 0o755),
+                ("src/main.rs", false, 40, 0o600),
+            ]
+        );
+        assert_eq!(skipped, 1, "the symlink is skipped");
+
+        let (single, _) = walk_tree(Path::new("/srv/app/a.txt"), &stat(0o100640, 5), |_| {
+            Err("a file is never listed".to_string())
+        })
+        .unwrap();
+        assert_eq!((single.len(), single[0].rel.as_str(), single[0].mode), (1, "", 0o640));
+    }
+
+    #[test]
+    fn test_progress_throttle_and_final_state() {
+        let now = Instant::now();
+        let mut throttle = Throttle::new(PROGRESS_EMIT_INTERVAL, now);
+        assert!(!throttle.ready(now + Duration::from_millis(100)));
+        assert!(throttle.ready(now + PROGRESS_EMIT_INTERVAL));
+        throttle.mark(now + PROGRESS_EMIT_INTERVAL);
+        assert!(!throttle.ready(now + PROGRESS_EMIT_INTERVAL + Duration::from_millis(100)));
+
+        let progress = || TransferProgress {
+            transfer_id: "t".into(),
+            state: TransferState::Running,
+            bytes_done: 0,
+            bytes_total: None,
+            files_done: 0,
+            files_total: None,
+            skipped: 0,
+            current_path: Some("a".into()),
+            error: None,
+        };
+        let mut cancelled = progress();
+        cancelled.settle(Err("cancelled".into()), true);
+        assert_eq!((cancelled.state, cancelled.error), (TransferState::Cancelled, None));
+        let mut failed = progress();
+        failed.settle(Err("disk full".into()), false);
+        assert_eq!(failed.state, TransferState::Failed);
+        assert_eq!(failed.error.as_deref(), Some("disk full"));
+        let mut done = progress();
+        done.settle(Ok(()), false);
+        assert_eq!((done.state, done.current_path), (TransferState::Completed, None));
+    }
+
+    #[test]
+    fn test_registry_cancel_and_finish() {
+        let registry = TransferRegistry::new();
+        let (a, flag_a) = registry.register();
+        let (b, flag_b) = registry.register();
+        assert_ne!(a, b);
+        assert!(registry.cancel(&a));
+        assert!(flag_a.load(Ordering::SeqCst));
+        assert!(!flag_b.load(Ordering::SeqCst));
+
+        registry.finish(&a);
+        assert!(!registry.cancel(&a), "finished transfers are forgotten");
+        registry.cancel_all();
+        assert!(flag_b.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,503 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+use tauri::{Emitter, Manager};
+use uuid::Uuid;
+
+use crate::persist::{self, now_iso8601};
+use crate::ssh::agent_state::{AgentState, AgentStatus};
+use crate::ssh::session::now_epoch;
+use crate::ssh::SshConnectionManager;
+
+pub const TASK_QUEUE_EVENT: &str = "task-queue-updated";
+
+/// How often the scheduler looks for idle agents.
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A dispatched task whose agent never settles is failed after this long, freeing its session.
+const DISPATCH_TIMEOUT_SECS: u64 = 2 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Queued,
+    Dispatched,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Task {
+    pub id: String,
+    pub workset_id: String,
+    pub prompt: String,
+    pub status: TaskStatus,
+    pub created_at: String,
+    pub dispatched_at: Option<String>,
+    /// Unix seconds of the dispatch, compared with the agent's `since_epoch`.
+    #[serde(default)]
+    pub dispatched_epoch: Option<u64>,
+    pub finished_at: Option<String>,
+    pub session_id: Option<String>,
+    pub host: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Payload of `task-queue-updated`.
+#[derive(Serialize, Clone, Debug)]
+pub struct TaskQueueUpdate {
+    pub workset_id: String,
+    pub tasks: Vec<Task>,
+}
+
+/// An agent session of the active workset, as seen by the scheduler.
+pub struct AgentSession {
+    pub session_id: String,
+    pub host: String,
+    pub status: AgentStatus,
+}
+
+/// Outcome of pasting a task's prompt into a session.
+pub enum Delivery {
+    Delivered,
+    /// The agent stopped being idle in the meantime; the task stays queued.
+    NotIdle,
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub enum TaskError {
+    ConfigDirUnavailable,
+    Io(io::Error),
+    Json(serde_json::Error),
+    LockPoisoned,
+    NotFound(String),
+    Validation(String),
+}
+
+impl std::fmt::Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskError::ConfigDirUnavailable => write!(f, "platform config directory unavailable"),
+            TaskError::Io(e) => write!(f, "io error: {e}"),
+            TaskError::Json(e) => write!(f, "json error: {e}"),
+            TaskError::LockPoisoned => write!(f, "task store lock poisoned"),
+            TaskError::NotFound(id) => write!(f, "task not found: {id}"),
+            TaskError::Validation(msg) => write!(f, "validation error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+impl From<io::Error> for TaskError {
+    fn from(value: io::Error) -> Self {
+        TaskError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for TaskError {
+    fn from(value: serde_json::Error) -> Self {
+        TaskError::Json(value)
+    }
+}
+
+fn finish(task: &mut Task, status: TaskStatus, error: Option<String>) {
+    task.status = status;
+    task.finished_at = Some(now_iso8601());
+    task.error = error;
+}
+
+/// Moves dispatched tasks on from what their agents are doing now. Only an idle state entered
+/// after the dispatch counts as done, so the idle prompt the task was pasted into never does.
+/// Works from persisted epochs alone, so tasks still settle after an app restart.
+fn settle(tasks: &mut [Task], workset_id: &str, sessions: &[AgentSession], now: u64) -> bool {
+    let mut changed = false;
+    for task in tasks
+        .iter_mut()
+        .filter(|t| t.workset_id == workset_id && t.status == TaskStatus::Dispatched)
+    {
+        let Some(session) = sessions.iter().find(|s| Some(&s.session_id) == task.session_id.as_ref()) else {
+            finish(task, TaskStatus::Failed, Some("session ended before the task finished".to_string()));
+            changed = true;
+            continue;
+        };
+        let dispatched = task.dispatched_epoch.unwrap_or(0);
+        let outcome = match session.status.state {
+            AgentState::Idle if session.status.since_epoch > dispatched => Some((TaskStatus::Done, None)),
+            AgentState::Errored => {
+                let detail = session.status.matched.clone().unwrap_or_else(|| "agent reported an error".to_string());
+                Some((TaskStatus::Failed, Some(detail)))
+            }
+            AgentState::Exited => Some((TaskStatus::Failed, Some("agent exited".to_string()))),
+            _ if now.saturating_sub(dispatched) >= DISPATCH_TIMEOUT_SECS => Some((
+                TaskStatus::Failed,
+                Some(format!("no result after {} minutes", DISPATCH_TIMEOUT_SECS / 60)),
+            )),
+            _ => None,
+        };
+        if let Some((status, error)) = outcome {
+            finish(task, status, error);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Pairs the oldest queued tasks with idle sessions that have nothing dispatched.
+/// Sessions without state detection never count as idle.
+fn assignments(tasks: &[Task], workset_id: &str, sessions: &[AgentSession]) -> Vec<(usize, usize)> {
+    let mut free = sessions.iter().enumerate().filter(|(_, s)| {
+        s.status.detecting
+            && s.status.state == AgentState::Idle
+            && !tasks
+                .iter()
+                .any(|t| t.status == TaskStatus::Dispatched && t.session_id.as_ref() == Some(&s.session_id))
+    });
+    tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.workset_id == workset_id && t.status == TaskStatus::Queued)
+        .map_while(|(task_idx, _)| free.next().map(|(session_idx, _)| (task_idx, session_idx)))
+        .collect()
+}
+
+/// Persisted queue of prompts per workset, plus the scheduler that feeds them to idle agents.
+pub struct TaskStore {
+    base_dir: Option<PathBuf>,
+    lock: Mutex<()>,
+    scheduler: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl TaskStore {
+    pub fn new() -> Self {
+        let base_dir = dirs::config_dir().map(|p| p.join("multivm-workspace"));
+        Self {
+            base_dir,
+            lock: Mutex::new(()),
+            scheduler: Mutex::new(None),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self {
+            base_dir: Some(dir),
+            lock: Mutex::new(()),
+            scheduler: Mutex::new(None),
+        }
+    }
+
+    pub fn list(&self, workset_id: &str) -> Result<Vec<Task>, TaskError> {
+        let _guard = self.lock_guard()?;
+        Ok(Self::for_workset(self.read_tasks()?, workset_id))
+    }
+
+    pub fn enqueue(&self, workset_id: &str, prompts: Vec<String>) -> Result<Vec<Task>, TaskError> {
+        if prompts.iter().any(|p| p.trim().is_empty()) {
+            return Err(TaskError::Validation("task prompts must not be empty".to_string()));
+        }
+        let _guard = self.lock_guard()?;
+        let mut tasks = self.read_tasks()?;
+        let created_at = now_iso8601();
+        tasks.extend(prompts.into_iter().map(|prompt| Task {
+            id: Uuid::new_v4().to_string(),
+            workset_id: workset_id.to_string(),
+            prompt,
+            status: TaskStatus::Queued,
+            created_at: created_at.clone(),
+            dispatched_at: None,
+            dispatched_epoch: None,
+            finished_at: None,
+            session_id: None,
+            host: None,
+            error: None,
+        }));
+        self.write_tasks(&tasks)?;
+        Ok(Self::for_workset(tasks, workset_id))
+    }
+
+    /// Drops a task that is not currently running on an agent.
+    pub fn remove(&self, task_id: &str) -> Result<Vec<Task>, TaskError> {
+        let _guard = self.lock_guard()?;
+        let mut tasks = self.read_tasks()?;
+        let idx = tasks
+            .iter()
+            .position(|t| t.id == task_id)
+            .ok_or_else(|| TaskError::NotFound(task_id.to_string()))?;
+        if tasks[idx].status == TaskStatus::Dispatched {
+            return Err(TaskError::Validation("a dispatched task can't be removed".to_string()));
+        }
+        let workset_id = tasks.remove(idx).workset_id;
+        self.write_tasks(&tasks)?;
+        Ok(Self::for_workset(tasks, &workset_id))
+    }
+
+    /// Gives up on a dispatched task: it is marked failed and its session takes new work again.
+    /// The agent itself is left alone.
+    pub fn cancel(&self, task_id: &str) -> Result<Vec<Task>, TaskError> {
+        let _guard = self.lock_guard()?;
+        let mut tasks = self.read_tasks()?;
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.id == task_id)
+            .ok_or_else(|| TaskError::NotFound(task_id.to_string()))?;
+        if task.status != TaskStatus::Dispatched {
+            return Err(TaskError::Validation("only dispatched tasks can be cancelled".to_string()));
+        }
+        finish(task, TaskStatus::Failed, Some("cancelled".to_string()));
+        let workset_id = task.workset_id.clone();
+        self.write_tasks(&tasks)?;
+        Ok(Self::for_workset(tasks, &workset_id))
+    }
+
+    /// Puts a finished task back at the end of the queue.
+    pub fn retry(&self, task_id: &str) -> Result<Vec<Task>, TaskError> {
+        let _guard = self.lock_guard()?;
+        let mut tasks = self.read_tasks()?;
+        let idx = tasks
+            .iter()
+            .position(|t| t.id == task_id)
+            .ok_or_else(|| TaskError::NotFound(task_id.to_string()))?;
+        if matches!(tasks[idx].status, TaskStatus::Queued | TaskStatus::Dispatched) {
+            return Err(TaskError::Validation("only finished tasks can be retried".to_string()));
+        }
+        let mut task = tasks.remove(idx);
+        task.status = TaskStatus::Queued;
+        task.created_at = now_iso8601();
+        task.dispatched_at = None;
+        task.dispatched_epoch = None;
+        task.finished_at = None;
+        task.session_id = None;
+        task.host = None;
+        task.error = None;
+        let workset_id = task.workset_id.clone();
+        tasks.push(task);
+        self.write_tasks(&tasks)?;
+        Ok(Self::for_workset(tasks, &workset_id))
+    }
+
+    pub fn clear_finished(&self, workset_id: &str) -> Result<Vec<Task>, TaskError> {
+        let _guard = self.lock_guard()?;
+        let mut tasks = self.read_tasks()?;
+        tasks.retain(|t| t.workset_id != workset_id || matches!(t.status, TaskStatus::Queued | TaskStatus::Dispatched));
+        self.write_tasks(&tasks)?;
+        Ok(Self::for_workset(tasks, workset_id))
+    }
+
+    /// One scheduling pass at `epoch` (Unix seconds): settles dispatched tasks, then hands
+    /// queued ones to idle agents. Returns the workset's tasks when anything changed.
+    pub fn tick(
+        &self,
+        workset_id: &str,
+        sessions: &[AgentSession],
+        epoch: u64,
+        mut deliver: impl FnMut(&AgentSession, &str) -> Delivery,
+    ) -> Result<Option<Vec<Task>>, TaskError> {
+        let _guard = self.lock_guard()?;
+        let mut tasks = self.read_tasks()?;
+        let mut changed = settle(&mut tasks, workset_id, sessions, epoch);
+        for (task_idx, session_idx) in assignments(&tasks, workset_id, sessions) {
+            let session = &sessions[session_idx];
+            let task = &mut tasks[task_idx];
+            match deliver(session, &task.prompt) {
+                Delivery::Delivered => {
+                    task.status = TaskStatus::Dispatched;
+                    task.dispatched_at = Some(now_iso8601());
+                    task.dispatched_epoch = Some(epoch);
+                    task.session_id = Some(session.session_id.clone());
+                    task.host = Some(session.host.clone());
+                }
+                Delivery::NotIdle => continue,
+                Delivery::Failed(e) => {
+                    task.session_id = Some(session.session_id.clone());
+                    task.host = Some(session.host.clone());
+                    finish(task, TaskStatus::Failed, Some(e));
+                }
+            }
+            changed = true;
+        }
+        if !changed {
+            return Ok(None);
+        }
+        self.write_tasks(&tasks)?;
+        Ok(Some(Self::for_workset(tasks, workset_id)))
+    }
+
+    /// Starts assigning `workset_id`'s queue to the connected sessions, replacing any earlier run.
+    pub fn start_scheduler(&self, workset_id: String, app: tauri::AppHandle) {
+        self.stop_scheduler();
+        let stop = Arc::new(AtomicBool::new(false));
+        *self.scheduler.lock().unwrap_or_else(|e| e.into_inner()) = Some(stop.clone());
+        std::thread::spawn(move || loop {
+            std::thread::sleep(SCHEDULER_INTERVAL);
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            let manager = app.state::<SshConnectionManager>();
+            let store = app.state::<TaskStore>();
+            let sessions: Vec<AgentSession> = manager
+                .agent_statuses()
+                .into_iter()
+                .map(|(session_id, host, status)| AgentSession { session_id, host, status })
+                .collect();
+            let result = store.tick(&workset_id, &sessions, now_epoch(), |session, prompt| {
+                let ids = [session.session_id.clone()];
+                match manager.dispatch_prompt(&ids, prompt, true).pop() {
+                    Some(r) if r.delivered => Delivery::Delivered,
+                    Some(r) if r.agent_state.is_some_and(|s| s != AgentState::Idle) => Delivery::NotIdle,
+                    Some(r) => Delivery::Failed(r.error.unwrap_or_else(|| "not delivered".to_string())),
+                    None => Delivery::Failed("not delivered".to_string()),
+                }
+            });
+            if let Ok(Some(tasks)) = result {
+                let update = TaskQueueUpdate {
+                    workset_id: workset_id.clone(),
+                    tasks,
+                };
+                let _ = app.emit(TASK_QUEUE_EVENT, update);
+            }
+        });
+    }
+
+    pub fn stop_scheduler(&self) {
+        if let Some(stop) = self.scheduler.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn for_workset(tasks: Vec<Task>, workset_id: &str) -> Vec<Task> {
+        tasks.into_iter().filter(|t| t.workset_id == workset_id).collect()
+    }
+
+    fn lock_guard(&self) -> Result<MutexGuard<'_, ()>, TaskError> {
+        self.lock.lock().map_err(|_| TaskError::LockPoisoned)
+    }
+
+    fn ensure_dir(&self) -> Result<PathBuf, TaskError> {
+        let base = self.base_dir.clone().ok_or(TaskError::ConfigDirUnavailable)?;
+        fs::create_dir_all(&base)?;
+        Ok(base)
+    }
+
+    fn tasks_path(&self) -> Result<PathBuf, TaskError> {
+        Ok(self.ensure_dir()?.join("tasks.json"))
+    }
+
+    /// Callers must hold the store lock.
+    fn read_tasks(&self) -> Result<Vec<Task>, TaskError> {
+        let path = self.tasks_path()?;
+        match fs::read_to_string(&path) {
+            Ok(s) => Ok(serde_json::from_str(&s)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(TaskError::Io(e)),
+        }
+    }
+
+    fn write_tasks(&self, tasks: &[Task]) -> Result<(), TaskError> {
+        let path = self.tasks_path()?;
+        let json = serde_json::to_string_pretty(tasks)?;
+        persist::write_atomically(&path, json.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn session(id: &str, state: AgentState, since_epoch: u64) -> AgentSession {
+        AgentSession {
+            session_id: id.to_string(),
+            host: format!("me@{id}:22"),
+            status: AgentStatus {
+                state,
+                since_epoch,
+                detecting: true,
+                ..AgentStatus::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_queue_assigns_idle_agents_and_tracks_completion() {
+        let dir = tempdir().expect("tempdir");
+        let store = TaskStore::with_dir(dir.path().to_path_buf());
+        store
+            .enqueue("ws", vec!["fix lint".into(), "add tests".into(), "update docs".into()])
+            .expect("enqueue");
+
+        let mut sent = Vec::new();
+        let sessions = [session("a", AgentState::Idle, 90), session("b", AgentState::Working, 95)];
+        let tasks = store
+            .tick("ws", &sessions, 100, |s, prompt| {
+                sent.push((s.session_id.clone(), prompt.to_string()));
+                Delivery::Delivered
+            })
+            .expect("tick")
+            .expect("changed");
+        assert_eq!(sent, [("a".to_string(), "fix lint".to_string())]);
+        assert_eq!(tasks[0].status, TaskStatus::Dispatched);
+        assert_eq!(tasks[0].dispatched_epoch, Some(100));
+        assert_eq!(tasks[1].status, TaskStatus::Queued);
+
+        // The idle prompt the task was pasted into doesn't count; idle entered afterwards does,
+        // even if the busy phase in between was never sampled.
+        let busy = |_: &AgentSession, _: &str| Delivery::NotIdle;
+        assert!(store.tick("ws", &sessions, 101, busy).expect("tick").is_none());
+        let tasks = store
+            .tick("ws", &[session("a", AgentState::Idle, 130)], 131, busy)
+            .expect("tick")
+            .expect("changed");
+        assert_eq!(tasks[0].status, TaskStatus::Done);
+        assert!(tasks[0].finished_at.is_some());
+        assert_eq!(tasks[1].status, TaskStatus::Queued, "a refused paste keeps the task queued");
+
+        // The queue survives a restart; a session that's gone fails its task.
+        let reopened = TaskStore::with_dir(dir.path().to_path_buf());
+        reopened
+            .tick("ws", &[session("a", AgentState::Idle, 130)], 140, |_, _| Delivery::Delivered)
+            .expect("tick");
+        let tasks = reopened.tick("ws", &[], 141, busy).expect("tick").expect("changed");
+        assert_eq!(tasks[1].status, TaskStatus::Failed);
+        assert_eq!(tasks[1].session_id.as_deref(), Some("a"));
+        assert_eq!(tasks[2].status, TaskStatus::Queued);
+    }
+
+    #[test]
+    fn test_stuck_dispatch_times_out_or_is_cancelled() {
+        let dir = tempdir().expect("tempdir");
+        let store = TaskStore::with_dir(dir.path().to_path_buf());
+        store.enqueue("ws", vec!["migrate".into(), "refactor".into()]).expect("enqueue");
+        let idle = [session("a", AgentState::Idle, 10), session("b", AgentState::Idle, 10)];
+        let tasks = store
+            .tick("ws", &idle, 100, |_, _| Delivery::Delivered)
+            .expect("tick")
+            .expect("changed");
+        assert!(store.remove(&tasks[0].id).is_err(), "dispatched tasks are cancelled, not removed");
+
+        let tasks = store.cancel(&tasks[0].id).expect("cancel");
+        assert_eq!((tasks[0].status, tasks[0].error.as_deref()), (TaskStatus::Failed, Some("cancelled")));
+        assert!(store.retry(&tasks[0].id).is_ok());
+
+        let working = [session("b", AgentState::Working, 120)];
+        assert!(store
+            .tick("ws", &working, 100 + DISPATCH_TIMEOUT_SECS - 1, |_, _| Delivery::NotIdle)
+            .expect("tick")
+            .is_none());
+        let tasks = store
+            .tick("ws", &working, 100 + DISPATCH_TIMEOUT_SECS, |_, _| Delivery::NotIdle)
+            .expect("tick")
+            .expect("changed");
+        let timed_out = tasks.iter().find(|t| t.session_id.as_deref() == Some("b")).expect("task on b");
+        assert_eq!(timed_out.status, TaskStatus::Failed);
+    }
+}
@@ -1,4 +1,3 @@
-use chrono::{SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
@@ -8,14 +7,12 @@ use std::{
 };
 use uuid::Uuid;
 
+use crate::persist::{self, now_iso8601};
+
 fn default_ssh_port() -> u16 {
     22
 }
 
-fn now_iso8601() -> String {
-    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
-}
-
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Workset {
     pub id: String,
@@ -36,10 +33,148 @@ pub struct ConnectionConfig {
     pub key_path: Option<String>,
     pub project_path: String,
     pub ai_cli_command: Option<String>,
+    /// Id of an AI CLI profile from the app settings; takes precedence over `ai_cli_command`.
+    #[serde(default)]
+    pub ai_cli_profile: Option<String>,
     #[serde(default)]
     pub keepalive_interval_secs: Option<u32>,
     #[serde(default)]
     pub reconnect_max_retries: Option<u32>,
+    /// Extra environment for the shell; secret values are looked up in the OS keystore at connect time.
+    #[serde(default)]
+    pub env: Vec<EnvVar>,
+    /// Commands typed into the shell after `cd project_path`, before `ai_cli_command`.
+    #[serde(default)]
+    pub startup_steps: Vec<StartupStep>,
+    /// Regex matched against login output to detect the prompt; unset uses an injected marker.
+    #[serde(default)]
+    pub shell_ready_pattern: Option<String>,
+    /// How long to wait for the shell before giving up on startup commands (default 20s).
+    #[serde(default)]
+    pub shell_ready_timeout_secs: Option<u64>,
+    /// Install a bash/zsh hook that reports cwd and command exit codes (OSC 7 / OSC 133).
+    #[serde(default)]
+    pub shell_integration: bool,
+    /// Whether remote programs (tmux, vim) may set the local clipboard via OSC 52.
+    #[serde(default)]
+    pub clipboard: ClipboardPolicy,
+    /// How the AI CLI's state is read from terminal output.
+    #[serde(default)]
+    pub agent_rules: AgentRules,
+    /// Whether the AI CLI is relaunched after it exits (with backoff).
+    #[serde(default)]
+    pub auto_restart: AutoRestart,
+}
+
+/// Built-in state detection rules for known AI CLIs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentPreset {
+    ClaudeCode,
+    Opencode,
+    Aider,
+    /// Only the custom patterns apply. Accepts the earlier `none` spelling.
+    #[serde(alias = "none")]
+    Custom,
+}
+
+/// Regexes matched against recent (escape-stripped) terminal output. Custom patterns are
+/// checked alongside the preset's; the most recent match decides the state.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AgentRules {
+    /// Unset picks a preset from the AI CLI command's program name.
+    #[serde(default)]
+    pub preset: Option<AgentPreset>,
+    #[serde(default)]
+    pub working: Vec<String>,
+    #[serde(default)]
+    pub idle: Vec<String>,
+    #[serde(default)]
+    pub waiting_for_approval: Vec<String>,
+    #[serde(default)]
+    pub errored: Vec<String>,
+}
+
+impl AgentRules {
+    pub fn patterns(&self) -> impl Iterator<Item = &String> {
+        self.working
+            .iter()
+            .chain(&self.idle)
+            .chain(&self.waiting_for_approval)
+            .chain(&self.errored)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardPolicy {
+    #[default]
+    Deny,
+    Allow,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoRestart {
+    #[default]
+    Never,
+    /// Only after a non-zero exit status.
+    #[serde(alias = "on-failure")]
+    OnFailure,
+    Always,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StartupStep {
+    pub command: String,
+    #[serde(default)]
+    pub wait: StepWait,
+    #[serde(default)]
+    pub rerun: RerunPolicy,
+    /// How long a waiting step may take before the sequence is abandoned (default 300s).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// What must happen before the next step is sent.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum StepWait {
+    /// Send the next step straight away.
+    #[default]
+    None,
+    /// Wait for the command to finish; a non-zero exit stops the sequence.
+    Exit,
+    /// Wait until the command's output matches a regular expression.
+    Pattern { pattern: String },
+}
+
+/// Whether a step runs again when the session reconnects.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RerunPolicy {
+    #[default]
+    Always,
+    /// Only on the first successful connection.
+    Once,
+    /// On reconnect only if it has not completed successfully yet.
+    UntilSuccess,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum EnvValue {
+    /// Stored as-is in the workset file.
+    Literal { value: String },
+    /// Name of a secret in the OS keystore; only the name is stored in the workset file.
+    Secret { secret: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EnvVar {
+    pub name: String,
+    #[serde(flatten)]
+    pub value: EnvValue,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -324,7 +459,9 @@ impl WorksetStore {
 
     fn write_workset(&self, workset: &Workset) -> Result<(), StoreError> {
         let path = self.path_for_id(&workset.id)?;
-        write_json_atomically(&path, workset)
+        let bytes = serde_json::to_vec_pretty(workset)?;
+        persist::write_atomically(&path, &bytes)?;
+        Ok(())
     }
 }
 
@@ -332,35 +469,6 @@ fn is_workset_json_path(path: &Path) -> bool {
     path.extension().and_then(|s| s.to_str()) == Some("json")
 }
 
-fn write_json_atomically<T: Serialize>(path: &Path, value: &T) -> Result<(), StoreError> {
-    let bytes = serde_json::to_vec_pretty(value)?;
-
-    let tmp_path = match path.file_name().and_then(|s| s.to_str()) {
-        Some(name) => path.with_file_name(format!("{name}.tmp")),
-        None => return Err(StoreError::Validation("invalid workset filename".to_string())),
-    };
-
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    fs::write(&tmp_path, bytes)?;
-
-    match fs::remove_file(path) {
-        Ok(()) => {}
-        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
-        Err(e) => return Err(StoreError::Io(e)),
-    }
-
-    match fs::rename(&tmp_path, path) {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            let _ = fs::remove_file(&tmp_path);
-            Err(StoreError::Io(e))
-        }
-    }
-}
-
 fn validate_create(input: &CreateWorksetInput) -> Result<(), StoreError> {
     if input.name.trim().is_empty() {
         return Err(StoreError::Validation("name must not be empty".to_string()));
@@ -370,6 +478,12 @@ fn validate_create(input: &CreateWorksetInput) -> Result<(), StoreError> {
     Ok(())
 }
 
+pub(crate) fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(ch) if ch.is_ascii_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
 fn validate_connections(connections: &[ConnectionConfig]) -> Result<(), StoreError> {
     if connections.is_empty() {
         return Err(StoreError::Validation(
@@ -399,6 +513,55 @@ fn validate_connections(connections: &[ConnectionConfig]) -> Result<(), StoreErr
             )));
         }
 
+        for (env_idx, var) in c.env.iter().enumerate() {
+            if !is_valid_env_name(&var.name) {
+                return Err(StoreError::Validation(format!(
+                    "connections[{idx}].env[{env_idx}].name is not a valid variable name"
+                )));
+            }
+            if c.env[..env_idx].iter().any(|other| other.name == var.name) {
+                return Err(StoreError::Validation(format!(
+                    "connections[{idx}].env[{env_idx}].name is duplicated"
+                )));
+            }
+            if matches!(&var.value, EnvValue::Secret { secret } if secret.trim().is_empty()) {
+                return Err(StoreError::Validation(format!(
+                    "connections[{idx}].env[{env_idx}].secret must not be empty"
+                )));
+            }
+        }
+
+        if let Some(pattern) = &c.shell_ready_pattern {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(StoreError::Validation(format!(
+                    "connections[{idx}].shell_ready_pattern is invalid: {e}"
+                )));
+            }
+        }
+
+        for pattern in c.agent_rules.patterns() {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(StoreError::Validation(format!(
+                    "connections[{idx}].agent_rules pattern '{pattern}' is invalid: {e}"
+                )));
+            }
+        }
+
+        for (step_idx, step) in c.startup_steps.iter().enumerate() {
+            if step.command.trim().is_empty() {
+                return Err(StoreError::Validation(format!(
+                    "connections[{idx}].startup_steps[{step_idx}].command must not be empty"
+                )));
+            }
+            if let StepWait::Pattern { pattern } = &step.wait {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    return Err(StoreError::Validation(format!(
+                        "connections[{idx}].startup_steps[{step_idx}].wait.pattern is invalid: {e}"
+                    )));
+                }
+            }
+        }
+
         if matches!(c.auth_method, AuthMethod::Key) {
             let key_ok = c
                 .key_path
@@ -445,8 +608,17 @@ mod tests {
             key_path: Some("/home/user/.ssh/id_rsa".into()),
             project_path: "/home/user/project".into(),
             ai_cli_command: None,
+            ai_cli_profile: None,
             keepalive_interval_secs: None,
             reconnect_max_retries: None,
+            env: Vec::new(),
+            startup_steps: Vec::new(),
+            shell_ready_pattern: None,
+            shell_ready_timeout_secs: None,
+            shell_integration: false,
+            clipboard: ClipboardPolicy::Deny,
+            auto_restart: AutoRestart::Never,
+            agent_rules: AgentRules::default(),
         }
     }
 
@@ -561,6 +733,69 @@ mod tests {
         assert!(matches!(res, Err(StoreError::Validation(_))));
     }
 
+    #[test]
+    fn test_validate_connection_env_names() {
+        let mut c = test_connection();
+        c.env = vec![EnvVar {
+            name: "ANTHROPIC_API_KEY".into(),
+            value: EnvValue::Secret { secret: "anthropic".into() },
+        }];
+        assert!(validate_connections(std::slice::from_ref(&c)).is_ok());
+
+        c.env.push(EnvVar {
+            name: "ANTHROPIC_API_KEY".into(),
+            value: EnvValue::Literal { value: "x".into() },
+        });
+        assert!(matches!(validate_connections(std::slice::from_ref(&c)), Err(StoreError::Validation(_))));
+
+        c.env = vec![EnvVar {
+            name: "BAD-NAME".into(),
+            value: EnvValue::Literal { value: "x".into() },
+        }];
+        assert!(matches!(validate_connections(&[c]), Err(StoreError::Validation(_))));
+    }
+
+    #[test]
+    fn test_env_var_json_shape() {
+        let var = EnvVar {
+            name: "TOKEN".into(),
+            value: EnvValue::Secret { secret: "gh".into() },
+        };
+        let json = serde_json::to_value(&var).unwrap();
+        assert_eq!(json, serde_json::json!({ "name": "TOKEN", "source": "secret", "secret": "gh" }));
+
+        // Worksets saved before env support still load.
+        let c: ConnectionConfig = serde_json::from_value(serde_json::json!({
+            "host": "h", "user": "u", "auth_method": "key", "key_path": "/k",
+            "project_path": "/p", "ai_cli_command": null
+        }))
+        .unwrap();
+        assert!(c.env.is_empty());
+        assert!(c.startup_steps.is_empty());
+    }
+
+    #[test]
+    fn test_validate_startup_steps() {
+        let mut c = test_connection();
+        c.startup_steps = vec![StartupStep {
+            command: "source .venv/bin/activate".into(),
+            wait: StepWait::Pattern { pattern: "(unclosed".into() },
+            rerun: RerunPolicy::Always,
+            timeout_secs: None,
+        }];
+        assert!(matches!(validate_connections(&[c.clone()]), Err(StoreError::Validation(_))));
+
+        c.startup_steps[0].wait = StepWait::Exit;
+        assert!(validate_connections(&[c.clone()]).is_ok());
+
+        let step: StartupStep = serde_json::from_value(serde_json::json!({
+            "command": "npm run dev", "wait": { "mode": "pattern", "pattern": "ready in" }, "rerun": "until_success"
+        }))
+        .unwrap();
+        assert_eq!(step.wait, StepWait::Pattern { pattern: "ready in".into() });
+        assert_eq!(step.rerun, RerunPolicy::UntilSuccess);
+    }
+
     #[test]
     fn test_validate_grid_layout_zero_rows_fails() {
         let grid = GridLayout {
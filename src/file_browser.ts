@@ -4,6 +4,7 @@ export interface FileEntry {
   is_dir: boolean;
   size_bytes: number;
   mtime_epoch: number | null;
+  git_status?: string | null;
 }
 
 interface FileNode {
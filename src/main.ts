@@ -4,9 +4,9 @@ import { getCurrentWindow } from '@tauri-apps/api/window';
 import { openUrl } from '@tauri-apps/plugin-opener';
 import { createLayoutToolbar } from './grid.ts';
 import type { PaneState } from './workspace.ts';
-import { createWorkspace, attachTerminal, destroyWorkspace, getActivePaneIndex, setPaneHostLabel, setPaneMarkdownPath, writeToPaneBuffer, updatePaneStatus } from './workspace.ts';
+import { createWorkspace, attachTerminal, destroyWorkspace, getActivePaneIndex, setPaneAgentState, setPaneAttention, setPaneBroadcast, setPaneHostLabel, setPaneMarkdownPath, writeToPaneBuffer, updatePaneStatus } from './workspace.ts';
 import { FileBrowser, type FileEntry } from './file_browser.ts';
-import { installMarkdownLinkHandler, renderMarkdownToHtml } from './markdown.ts';
+import { applyRemoteMarkdownAssets, installMarkdownLinkHandler, renderMarkdownToHtml } from './markdown.ts';
 import { applyTerminalTheme, type ThemeName } from './terminal.ts';
 
 interface WorksetSummary {
@@ -24,8 +24,168 @@ interface ConnectionConfig {
   key_path: string | null;
   project_path: string;
   ai_cli_command: string | null;
+  ai_cli_profile?: string | null;
   keepalive_interval_secs?: number | null;
   reconnect_max_retries?: number | null;
+  env?: EnvVar[];
+  startup_steps?: StartupStep[];
+  shell_ready_pattern?: string | null;
+  shell_ready_timeout_secs?: number | null;
+  shell_integration?: boolean;
+  clipboard?: "deny" | "allow";
+  agent_rules?: AgentRules;
+  auto_restart?: AutoRestart;
+}
+
+type AutoRestart = "never" | "on_failure" | "always";
+
+interface AiCliExit {
+  pid: number | null;
+  exit_code: number | null;
+  restart_in_secs: number | null;
+  gave_up: boolean;
+  epoch: number;
+}
+
+type AgentPreset = "claude_code" | "opencode" | "aider" | "custom";
+
+interface AgentRules {
+  preset: AgentPreset | null;
+  working: string[];
+  idle: string[];
+  waiting_for_approval: string[];
+  errored: string[];
+}
+
+const AGENT_RULE_KEYS = ["working", "idle", "waiting_for_approval", "errored"] as const;
+
+/** One `state: regex` per line, e.g. `waiting_for_approval: Proceed\?`. */
+function parseAgentRuleLines(preset: AgentPreset | null, text: string): AgentRules | null {
+  const rules: AgentRules = { preset, working: [], idle: [], waiting_for_approval: [], errored: [] };
+  for (const raw of text.split("\n")) {
+    const line = raw.trim();
+    if (!line || line.startsWith("#")) continue;
+    const colon = line.indexOf(":");
+    const key = line.slice(0, colon).trim() as (typeof AGENT_RULE_KEYS)[number];
+    if (colon <= 0 || !AGENT_RULE_KEYS.includes(key)) return null;
+    const pattern = line.slice(colon + 1).trim();
+    if (!pattern) return null;
+    rules[key].push(pattern);
+  }
+  return rules;
+}
+
+function formatAgentRuleLines(rules?: AgentRules): string {
+  if (!rules) return "";
+  return AGENT_RULE_KEYS.flatMap((key) => rules[key].map((p) => `${key}: ${p}`)).join("\n");
+}
+
+interface ClipboardEvent {
+  bytes: number;
+  allowed: boolean;
+  error: string | null;
+}
+
+interface SessionNotification {
+  session_id: string;
+  host: string;
+  source: "bell" | "osc9" | "osc777";
+  title: string | null;
+  message: string;
+  epoch: number;
+}
+
+type AgentState = "unknown" | "working" | "idle" | "waiting_for_approval" | "errored" | "exited";
+
+interface AgentStatus {
+  state: AgentState;
+  since_epoch: number;
+  preset: AgentPreset | null;
+  matched: string | null;
+  detecting: boolean;
+}
+
+interface DispatchResult {
+  session_id: string;
+  host: string | null;
+  delivered: boolean;
+  agent_state: AgentState | null;
+  error: string | null;
+}
+
+type TaskStatus = "queued" | "dispatched" | "done" | "failed";
+
+interface Task {
+  id: string;
+  workset_id: string;
+  prompt: string;
+  status: TaskStatus;
+  created_at: string;
+  dispatched_at: string | null;
+  dispatched_epoch: number | null;
+  finished_at: string | null;
+  session_id: string | null;
+  host: string | null;
+  error: string | null;
+}
+
+interface TaskQueueUpdate {
+  workset_id: string;
+  tasks: Task[];
+}
+
+interface ShellState {
+  cwd: string | null;
+  last_command: string | null;
+  last_exit_code: number | null;
+  command_running: boolean;
+  integrated: boolean;
+}
+
+interface StartupStep {
+  command: string;
+  wait: { mode: "none" } | { mode: "exit" } | { mode: "pattern"; pattern: string };
+  rerun: "always" | "once" | "until_success";
+  timeout_secs?: number | null;
+}
+
+interface StartupProgress {
+  index: number;
+  total: number;
+  command: string;
+  state: "running" | "done" | "failed" | "timed_out" | "skipped";
+  exit_status: number | null;
+}
+
+type EnvVar =
+  | { name: string; source: "literal"; value: string }
+  | { name: string; source: "secret"; secret: string };
+
+const SECRET_PREFIX = "secret:";
+
+/** One `NAME=value` or `NAME=secret:KEY` per line; blank lines and `#` comments are skipped. */
+function parseEnvLines(text: string): EnvVar[] | null {
+  const vars: EnvVar[] = [];
+  for (const raw of text.split("\n")) {
+    const line = raw.trim();
+    if (!line || line.startsWith("#")) continue;
+    const eq = line.indexOf("=");
+    if (eq <= 0) return null;
+    const name = line.slice(0, eq).trim();
+    const value = line.slice(eq + 1);
+    if (value.startsWith(SECRET_PREFIX)) {
+      vars.push({ name, source: "secret", secret: value.slice(SECRET_PREFIX.length).trim() });
+    } else {
+      vars.push({ name, source: "literal", value });
+    }
+  }
+  return vars;
+}
+
+function formatEnvLines(vars: EnvVar[]): string {
+  return vars
+    .map((v) => (v.source === "secret" ? `${v.name}=${SECRET_PREFIX}${v.secret}` : `${v.name}=${v.value}`))
+    .join("\n");
 }
 
 interface GridLayout {
@@ -62,16 +222,66 @@ interface SessionInfo {
   status: string;
 }
 
+interface ContentInfo {
+  binary: boolean;
+  encoding: 'utf8' | 'utf16le' | 'utf16be' | 'latin1' | null;
+  has_bom: boolean;
+  line_ending: 'lf' | 'crlf' | 'cr' | 'mixed' | 'none' | null;
+  mime: string;
+}
+
 interface ReadFileResult {
   path: string;
   bytes: number[];
   truncated: boolean;
+  content: ContentInfo;
+  text: string | null;
+}
+
+interface BroadcastState {
+  enabled: boolean;
+  all: boolean;
+  session_ids: string[];
 }
 
 interface AppSettings {
   theme: ThemeName;
+  ai_cli_profiles?: AiCliProfile[];
 }
 
+interface AiCliProfile {
+  id: string;
+  name: string;
+  command: string;
+  args: string[];
+  required_env: string[];
+  binary: string | null;
+  agent_rules: AgentRules;
+  restart_command: string | null;
+}
+
+interface AiCliRestartProgress {
+  phase: "interrupting" | "terminating" | "killing" | "relaunched" | "failed";
+  message: string | null;
+}
+
+const RESTART_PHASE_LABELS: Record<AiCliRestartProgress["phase"], string> = {
+  interrupting: "Restarting AI CLI: interrupting…",
+  terminating: "Restarting AI CLI: sending SIGTERM…",
+  killing: "Restarting AI CLI: sending SIGKILL…",
+  relaunched: "AI CLI relaunched",
+  failed: "AI CLI restart failed",
+};
+
+interface AiCliUnavailable {
+  session_id: string;
+  host: string;
+  message: string;
+}
+
+/** Profiles from the app settings, offered in the connection form. */
+let aiCliProfiles: AiCliProfile[] = [];
+
 interface ResourceSnapshot {
   cpu_percent: number | null;
   ram_percent: number | null;
@@ -94,6 +304,15 @@ let eventUnlisteners: UnlistenFn[] = [];
 
 let resourceSnapshots = new Map<string, ResourceSnapshot>();
 
+/** Last cwd each shell reported (OSC 7); the file browser follows it. */
+const shellCwds = new Map<string, string>();
+
+/** Latest detected AI CLI state per session, shown when picking prompt targets. */
+const agentStates = new Map<string, AgentStatus>();
+
+/** Task queue of the active workset, as last reported by the backend. */
+let queuedTasks: Task[] = [];
+
 let panelOpen = false;
 let panelMode: 'files' | 'docs' = 'files';
 let fileBrowser: FileBrowser | null = null;
@@ -184,6 +403,7 @@ async function initTheme(): Promise<void> {
   try {
     const settings = await invoke<AppSettings>('get_settings');
     const theme = settings?.theme === 'light' ? 'light' : 'dark';
+    aiCliProfiles = settings?.ai_cli_profiles ?? [];
     applyTheme(theme);
   } catch {
     applyTheme('dark');
@@ -475,7 +695,7 @@ function getActiveSessionContext(): { sessionId: string | null; rootPath: string
   const idx = getActivePaneIndex();
   const pane = activeWorkspace.panes[idx];
   const sessionId = pane?.sessionId ?? null;
-  const rootPath = activeWorkspace.rootPaths[idx] ?? null;
+  const rootPath = (sessionId ? shellCwds.get(sessionId) : undefined) ?? activeWorkspace.rootPaths[idx] ?? null;
   return { sessionId, rootPath };
 }
 
@@ -519,10 +739,17 @@ async function refreshMarkdown(force: boolean): Promise<void> {
       sessionId: mdState.sessionId,
       path: mdState.path,
       maxBytes: 1024 * 1024,
+      decodeText: true,
     });
 
-    const bytes = new Uint8Array(result.bytes);
-    const text = new TextDecoder('utf-8').decode(bytes);
+    if (result.content.binary || result.text === null) {
+      mdPathEl.textContent = mdState.path;
+      mdNoteEl.textContent = `Binary file (${result.content.mime})`;
+      mdContentEl.innerHTML = '';
+      mdState.lastText = null;
+      return;
+    }
+    const text = result.text;
 
     mdPathEl.textContent = mdState.path;
     mdNoteEl.textContent = result.truncated
@@ -533,11 +760,275 @@ async function refreshMarkdown(force: boolean): Promise<void> {
     mdState.lastText = text;
 
     mdContentEl.innerHTML = renderMarkdownToHtml(text);
+    void applyRemoteMarkdownAssets(mdContentEl, mdState.sessionId, mdState.path).catch(() => {
+      // Preview still renders without remote assets.
+    });
   } catch (err) {
     showToast(`Failed to read file: ${String(err)}`, 'error');
   }
 }
 
+function insertBroadcastToggleButton(toolbarContainer: HTMLElement): void {
+  if (toolbarContainer.querySelector('#btn-broadcast')) return;
+  const btn = document.createElement('button');
+  btn.id = 'btn-broadcast';
+  btn.className = 'layout-toolbar-btn';
+  btn.textContent = 'Broadcast';
+  btn.title = 'Type into every pane at once';
+
+  const disconnect = toolbarContainer.querySelector('#btn-disconnect-all');
+  if (disconnect && disconnect.parentElement === toolbarContainer) {
+    toolbarContainer.insertBefore(btn, disconnect);
+  } else {
+    toolbarContainer.appendChild(btn);
+  }
+}
+
+function toggleBroadcast(): void {
+  const btn = document.getElementById('btn-broadcast');
+  const enabled = btn?.classList.contains('active') ?? false;
+  const request = enabled
+    ? invoke<BroadcastState>('clear_broadcast')
+    : invoke<BroadcastState>('set_broadcast', { target: { mode: 'all' } });
+  request.catch((err) => showToast(`Broadcast failed: ${String(err)}`, 'error'));
+}
+
+function renderBroadcastState(state: BroadcastState): void {
+  document.getElementById('btn-broadcast')?.classList.toggle('active', state.enabled);
+  if (!activeWorkspace) return;
+  const linked = new Set(state.session_ids);
+  for (const pane of activeWorkspace.panes) {
+    setPaneBroadcast(pane, !!pane.sessionId && linked.has(pane.sessionId));
+  }
+}
+
+function insertDispatchButton(toolbarContainer: HTMLElement): void {
+  if (toolbarContainer.querySelector('#btn-dispatch-prompt')) return;
+  const btn = document.createElement('button');
+  btn.id = 'btn-dispatch-prompt';
+  btn.className = 'layout-toolbar-btn';
+  btn.textContent = 'Prompt';
+  btn.title = 'Send one prompt to several agents';
+
+  const disconnect = toolbarContainer.querySelector('#btn-disconnect-all');
+  if (disconnect && disconnect.parentElement === toolbarContainer) {
+    toolbarContainer.insertBefore(btn, disconnect);
+  } else {
+    toolbarContainer.appendChild(btn);
+  }
+}
+
+function toggleDispatchPopover(): void {
+  const existing = document.getElementById('dispatch-popover');
+  const btn = document.getElementById('btn-dispatch-prompt');
+  if (existing) {
+    existing.remove();
+    btn?.classList.remove('active');
+    return;
+  }
+  if (!activeWorkspace || !btn) return;
+
+  const targets = activeWorkspace.panes
+    .filter((pane) => pane.sessionId)
+    .map((pane) => {
+      const status = agentStates.get(pane.sessionId!);
+      const state = !status ? 'state unknown' : status.detecting ? status.state.replace(/_/g, ' ') : 'no detection';
+      return `
+        <label class="form-check">
+          <input type="checkbox" name="dispatch-target" value="${escapeHtml(pane.sessionId!)}" checked />
+          ${escapeHtml(pane.hostLabel)} <span class="dispatch-state">${escapeHtml(state)}</span>
+        </label>`;
+    })
+    .join('');
+
+  const popover = document.createElement('div');
+  popover.id = 'dispatch-popover';
+  popover.className = 'dispatch-popover';
+  popover.innerHTML = `
+    <textarea class="form-input form-textarea" name="dispatch-text" rows="6" placeholder="Prompt for every selected agent"></textarea>
+    <div class="dispatch-targets">${targets}</div>
+    <label class="form-check">
+      <input type="checkbox" name="dispatch-submit" checked />
+      Press Enter after pasting
+    </label>
+    <div class="dispatch-results"></div>
+    <div class="form-actions">
+      <button type="button" class="btn btn-primary btn-sm" id="btn-dispatch-send">Send</button>
+    </div>`;
+  const rect = btn.getBoundingClientRect();
+  popover.style.top = `${rect.bottom + 6}px`;
+  popover.style.left = `${Math.max(8, rect.left - 200)}px`;
+  document.body.appendChild(popover);
+  btn.classList.add('active');
+  popover.querySelector<HTMLTextAreaElement>('[name="dispatch-text"]')?.focus();
+
+  popover.querySelector('#btn-dispatch-send')?.addEventListener('click', async () => {
+    const text = popover.querySelector<HTMLTextAreaElement>('[name="dispatch-text"]')?.value ?? '';
+    const submit = popover.querySelector<HTMLInputElement>('[name="dispatch-submit"]')?.checked ?? true;
+    const sessionIds = Array.from(popover.querySelectorAll<HTMLInputElement>('[name="dispatch-target"]:checked'))
+      .map((input) => input.value);
+    if (!text.trim() || sessionIds.length === 0) {
+      showToast('Enter a prompt and pick at least one session', 'error');
+      return;
+    }
+    try {
+      const results = await invoke<DispatchResult[]>('dispatch_prompt', { sessionIds, text, submit });
+      const delivered = results.filter((r) => r.delivered).length;
+      const resultsEl = popover.querySelector<HTMLElement>('.dispatch-results');
+      if (resultsEl) {
+        resultsEl.innerHTML = results
+          .filter((r) => !r.delivered)
+          .map((r) => `<div class="dispatch-skipped">${escapeHtml(r.host ?? r.session_id)}: ${escapeHtml(r.error ?? 'not delivered')}</div>`)
+          .join('');
+      }
+      showToast(`Prompt sent to ${delivered}/${results.length} sessions`, delivered === results.length ? 'success' : 'error');
+    } catch (err) {
+      showToast(`Dispatch failed: ${String(err)}`, 'error');
+    }
+  });
+}
+
+function insertTasksButton(toolbarContainer: HTMLElement): void {
+  if (toolbarContainer.querySelector('#btn-task-queue')) return;
+  const btn = document.createElement('button');
+  btn.id = 'btn-task-queue';
+  btn.className = 'layout-toolbar-btn';
+  btn.textContent = 'Tasks';
+  btn.title = 'Queue prompts for whichever agent goes idle next';
+
+  const disconnect = toolbarContainer.querySelector('#btn-disconnect-all');
+  if (disconnect && disconnect.parentElement === toolbarContainer) {
+    toolbarContainer.insertBefore(btn, disconnect);
+  } else {
+    toolbarContainer.appendChild(btn);
+  }
+}
+
+function formatTaskTime(iso: string | null): string {
+  if (!iso) return '';
+  const date = new Date(iso);
+  return Number.isNaN(date.getTime()) ? iso : date.toLocaleTimeString();
+}
+
+function renderTaskList(): void {
+  const list = document.querySelector<HTMLElement>('#task-popover .task-list');
+  if (!list) return;
+  const btn = document.getElementById('btn-task-queue');
+  const pending = queuedTasks.filter((t) => t.status === 'queued' || t.status === 'dispatched').length;
+  if (btn) btn.textContent = pending > 0 ? `Tasks (${pending})` : 'Tasks';
+  if (queuedTasks.length === 0) {
+    list.innerHTML = '<div class="task-empty">No tasks queued</div>';
+    return;
+  }
+  list.innerHTML = queuedTasks
+    .map((task) => {
+      const times = [
+        `queued ${formatTaskTime(task.created_at)}`,
+        task.dispatched_at ? `sent ${formatTaskTime(task.dispatched_at)}` : '',
+        task.finished_at ? `finished ${formatTaskTime(task.finished_at)}` : '',
+      ].filter(Boolean).join(' · ');
+      const action = task.status === 'queued'
+        ? `<button type="button" class="btn btn-ghost btn-sm" data-task-remove="${escapeHtml(task.id)}">Remove</button>`
+        : task.status === 'dispatched'
+          ? `<button type="button" class="btn btn-ghost btn-sm" data-task-cancel="${escapeHtml(task.id)}" title="Stop waiting for this task; the agent is not interrupted">Cancel</button>`
+          : `<button type="button" class="btn btn-ghost btn-sm" data-task-retry="${escapeHtml(task.id)}">Retry</button>`;
+      return `
+        <div class="task-item task-${task.status}">
+          <div class="task-head">
+            <span class="task-status">${escapeHtml(task.status)}</span>
+            ${task.host ? `<span class="task-host">${escapeHtml(task.host)}</span>` : ''}
+            ${action}
+          </div>
+          <div class="task-prompt">${escapeHtml(task.prompt)}</div>
+          <div class="task-times">${escapeHtml(times)}</div>
+          ${task.error ? `<div class="dispatch-skipped">${escapeHtml(task.error)}</div>` : ''}
+        </div>`;
+    })
+    .join('');
+}
+
+async function refreshTasks(worksetId: string): Promise<void> {
+  try {
+    queuedTasks = await invoke<Task[]>('list_tasks', { worksetId });
+    renderTaskList();
+  } catch (err) {
+    showToast(`Failed to load tasks: ${String(err)}`, 'error');
+  }
+}
+
+function toggleTaskPopover(): void {
+  const existing = document.getElementById('task-popover');
+  const btn = document.getElementById('btn-task-queue');
+  if (existing) {
+    existing.remove();
+    btn?.classList.remove('active');
+    return;
+  }
+  if (!activeWorkspace || !btn) return;
+  const worksetId = activeWorkspace.worksetId;
+
+  const popover = document.createElement('div');
+  popover.id = 'task-popover';
+  popover.className = 'dispatch-popover task-popover';
+  popover.innerHTML = `
+    <textarea class="form-input form-textarea" name="task-prompts" rows="5" placeholder="One task per paragraph; separate tasks with a blank line"></textarea>
+    <div class="form-actions">
+      <button type="button" class="btn btn-ghost btn-sm" id="btn-task-clear">Clear finished</button>
+      <button type="button" class="btn btn-primary btn-sm" id="btn-task-enqueue">Queue</button>
+    </div>
+    <div class="task-list"></div>`;
+  const rect = btn.getBoundingClientRect();
+  popover.style.top = `${rect.bottom + 6}px`;
+  popover.style.left = `${Math.max(8, rect.left - 200)}px`;
+  document.body.appendChild(popover);
+  btn.classList.add('active');
+  renderTaskList();
+  void refreshTasks(worksetId);
+  popover.querySelector<HTMLTextAreaElement>('[name="task-prompts"]')?.focus();
+
+  popover.querySelector('#btn-task-enqueue')?.addEventListener('click', async () => {
+    const textarea = popover.querySelector<HTMLTextAreaElement>('[name="task-prompts"]');
+    const prompts = (textarea?.value ?? '')
+      .split(/\n\s*\n/)
+      .map((p) => p.trim())
+      .filter(Boolean);
+    if (prompts.length === 0) {
+      showToast('Enter at least one task', 'error');
+      return;
+    }
+    try {
+      queuedTasks = await invoke<Task[]>('enqueue_tasks', { worksetId, prompts });
+      renderTaskList();
+      if (textarea) textarea.value = '';
+      showToast(`Queued ${prompts.length} task${prompts.length === 1 ? '' : 's'}`, 'success');
+    } catch (err) {
+      showToast(`Failed to queue tasks: ${String(err)}`, 'error');
+    }
+  });
+
+  popover.querySelector('#btn-task-clear')?.addEventListener('click', async () => {
+    try {
+      queuedTasks = await invoke<Task[]>('clear_finished_tasks', { worksetId });
+      renderTaskList();
+    } catch (err) {
+      showToast(`Failed to clear tasks: ${String(err)}`, 'error');
+    }
+  });
+
+  popover.querySelector('.task-list')?.addEventListener('click', async (e) => {
+    const target = e.target as HTMLElement;
+    const { taskRemove, taskCancel, taskRetry } = target.dataset;
+    const command = taskRemove ? 'remove_task' : taskCancel ? 'cancel_task' : taskRetry ? 'retry_task' : null;
+    if (!command) return;
+    try {
+      queuedTasks = await invoke<Task[]>(command, { taskId: taskRemove ?? taskCancel ?? taskRetry });
+      renderTaskList();
+    } catch (err) {
+      showToast(`Task update failed: ${String(err)}`, 'error');
+    }
+  });
+}
+
 function insertPanelToggleButton(toolbarContainer: HTMLElement): void {
   if (toolbarContainer.querySelector('#btn-workspace-panel')) return;
   const btn = document.createElement('button');
@@ -698,6 +1189,7 @@ function extractFormData(
     const authMethod = (authSelect?.value ?? "ssh_config") as ConnectionConfig["auth_method"];
     const keyPath = authMethod === "key" ? (keyInput?.value.trim() || null) : null;
     const aiCmd = aiInput?.value.trim() || null;
+    const aiProfile = card.querySelector<HTMLSelectElement>('[name="conn-ai-profile"]')?.value || null;
     const port = parseInt(portInput?.value ?? "22", 10) || 22;
 
     const keepaliveInput = card.querySelector<HTMLInputElement>('[name="conn-keepalive"]');
@@ -707,6 +1199,47 @@ function extractFormData(
     const keepalive_interval_secs = keepaliveVal ? parseInt(keepaliveVal, 10) : null;
     const reconnect_max_retries = maxRetriesVal ? parseInt(maxRetriesVal, 10) : null;
 
+    const envInput = card.querySelector<HTMLTextAreaElement>('[name="conn-env"]');
+    envInput?.classList.remove("form-input-error");
+    const env = parseEnvLines(envInput?.value ?? "");
+    if (!env) { envInput?.classList.add("form-input-error"); hasError = true; }
+
+    const readyPatternVal = card.querySelector<HTMLInputElement>('[name="conn-ready-pattern"]')?.value ?? "";
+    const readyTimeoutVal = card.querySelector<HTMLInputElement>('[name="conn-ready-timeout"]')?.value.trim();
+    const shell_ready_timeout_secs = readyTimeoutVal ? parseInt(readyTimeoutVal, 10) : null;
+
+    const shellIntegration = card.querySelector<HTMLInputElement>('[name="conn-shell-integration"]')?.checked ?? false;
+    const clipboard = card.querySelector<HTMLSelectElement>('[name="conn-clipboard"]')?.value === "allow" ? "allow" : "deny";
+    const autoRestart = (card.querySelector<HTMLSelectElement>('[name="conn-auto-restart"]')?.value ?? "never") as AutoRestart;
+
+    const presetVal = card.querySelector<HTMLSelectElement>('[name="conn-agent-preset"]')?.value ?? "";
+    const agentRulesInput = card.querySelector<HTMLTextAreaElement>('[name="conn-agent-rules"]');
+    agentRulesInput?.classList.remove("form-input-error");
+    const agentRules = parseAgentRuleLines(presetVal ? (presetVal as AgentPreset) : null, agentRulesInput?.value ?? "");
+    if (!agentRules) { agentRulesInput?.classList.add("form-input-error"); hasError = true; }
+
+    const startupSteps: StartupStep[] = [];
+    card.querySelectorAll<HTMLElement>(".startup-step-row").forEach((row) => {
+      const commandInput = row.querySelector<HTMLInputElement>('[name="step-command"]');
+      const patternInput = row.querySelector<HTMLInputElement>('[name="step-pattern"]');
+      const mode = row.querySelector<HTMLSelectElement>('[name="step-wait"]')?.value ?? "none";
+      const rerun = (row.querySelector<HTMLSelectElement>('[name="step-rerun"]')?.value ?? "always") as StartupStep["rerun"];
+      const timeoutVal = row.querySelector<HTMLInputElement>('[name="step-timeout"]')?.value.trim();
+      const command = commandInput?.value.trim() ?? "";
+      const pattern = patternInput?.value ?? "";
+      commandInput?.classList.remove("form-input-error");
+      patternInput?.classList.remove("form-input-error");
+      if (!command) { commandInput?.classList.add("form-input-error"); hasError = true; return; }
+      if (mode === "pattern" && !pattern) { patternInput?.classList.add("form-input-error"); hasError = true; return; }
+      const timeout = timeoutVal ? parseInt(timeoutVal, 10) : NaN;
+      startupSteps.push({
+        command,
+        wait: mode === "pattern" ? { mode: "pattern", pattern } : { mode: mode === "exit" ? "exit" : "none" },
+        rerun,
+        timeout_secs: Number.isFinite(timeout) ? timeout : null,
+      });
+    });
+
     connections.push({
       host,
       port,
@@ -715,8 +1248,17 @@ function extractFormData(
       key_path: keyPath,
       project_path: projectPath,
       ai_cli_command: aiCmd,
+      ai_cli_profile: aiProfile,
       keepalive_interval_secs: Number.isFinite(keepalive_interval_secs) ? keepalive_interval_secs : null,
       reconnect_max_retries: Number.isFinite(reconnect_max_retries) ? reconnect_max_retries : null,
+      env: env ?? [],
+      startup_steps: startupSteps,
+      shell_ready_pattern: readyPatternVal || null,
+      shell_ready_timeout_secs: Number.isFinite(shell_ready_timeout_secs) ? shell_ready_timeout_secs : null,
+      shell_integration: shellIntegration,
+      clipboard,
+      agent_rules: agentRules ?? undefined,
+      auto_restart: autoRestart,
     });
   });
 
@@ -826,7 +1368,9 @@ function renderWorksetDetail(workset: Workset): void {
           <span><span class="connection-detail-label">Auth:</span> ${authLabel}</span>
           ${conn.key_path ? `<span><span class="connection-detail-label">Key:</span> ${escapeHtml(conn.key_path)}</span>` : ""}
           <span><span class="connection-detail-label">Path:</span> ${escapeHtml(conn.project_path)}</span>
-          ${conn.ai_cli_command ? `<span><span class="connection-detail-label">AI CLI:</span> ${escapeHtml(conn.ai_cli_command)}</span>` : ""}
+          ${conn.ai_cli_profile
+            ? `<span><span class="connection-detail-label">AI CLI:</span> ${escapeHtml(aiCliProfiles.find((p) => p.id === conn.ai_cli_profile)?.name ?? conn.ai_cli_profile)}</span>`
+            : conn.ai_cli_command ? `<span><span class="connection-detail-label">AI CLI:</span> ${escapeHtml(conn.ai_cli_command)}</span>` : ""}
           ${conn.keepalive_interval_secs ? `<span><span class="connection-detail-label">Keepalive:</span> ${conn.keepalive_interval_secs}s</span>` : ""}
           ${conn.reconnect_max_retries ? `<span><span class="connection-detail-label">Max Retries:</span> ${conn.reconnect_max_retries}</span>` : ""}
         </div>
@@ -875,6 +1419,33 @@ function renderWorksetDetail(workset: Workset): void {
   });
 }
 
+function renderStartupStepRow(step?: StartupStep): string {
+  const mode = step?.wait.mode ?? "none";
+  const pattern = step?.wait.mode === "pattern" ? step.wait.pattern : "";
+  const rerun = step?.rerun ?? "always";
+  const timeout = step?.timeout_secs ?? "";
+  const option = (value: string, label: string, current: string) =>
+    `<option value="${value}"${value === current ? " selected" : ""}>${label}</option>`;
+
+  return `
+    <div class="startup-step-row">
+      <input type="text" name="step-command" class="form-input" placeholder="source .venv/bin/activate" value="${escapeHtml(step?.command ?? "")}" />
+      <select name="step-wait" class="form-select" title="Before the next step">
+        ${option("none", "Don't wait", mode)}
+        ${option("exit", "Wait for exit", mode)}
+        ${option("pattern", "Wait for output", mode)}
+      </select>
+      <input type="text" name="step-pattern" class="form-input" placeholder="regex, e.g. ready in \\d+" value="${escapeHtml(pattern)}" style="${mode === "pattern" ? "" : "display:none;"}" />
+      <select name="step-rerun" class="form-select" title="On reconnect">
+        ${option("always", "Every connect", rerun)}
+        ${option("once", "First connect only", rerun)}
+        ${option("until_success", "Until it succeeds", rerun)}
+      </select>
+      <input type="number" name="step-timeout" class="form-input" min="1" placeholder="300s" title="Wait timeout (seconds)" value="${timeout}" />
+      <button type="button" class="btn-ghost-danger btn-remove-step">&times;</button>
+    </div>`;
+}
+
 function renderConnectionFormCard(index: number, conn?: ConnectionConfig): string {
   const host = conn?.host ?? "";
   const port = conn?.port ?? 22;
@@ -883,8 +1454,26 @@ function renderConnectionFormCard(index: number, conn?: ConnectionConfig): strin
   const keyPath = conn?.key_path ?? "";
   const projPath = conn?.project_path ?? "";
   const aiCmd = conn?.ai_cli_command ?? "";
+  const aiProfile = conn?.ai_cli_profile ?? "";
+  // Keep a reference to a profile that was deleted so saving doesn't silently drop it.
+  const profileOptions = [
+    ...aiCliProfiles.map((p) => ({ id: p.id, name: p.name })),
+    ...(aiProfile && !aiCliProfiles.some((p) => p.id === aiProfile) ? [{ id: aiProfile, name: `${aiProfile} (missing)` }] : []),
+  ]
+    .map((p) => `<option value="${escapeHtml(p.id)}"${p.id === aiProfile ? " selected" : ""}>${escapeHtml(p.name)}</option>`)
+    .join("");
   const keepalive = conn?.keepalive_interval_secs ?? '';
   const maxRetries = conn?.reconnect_max_retries ?? '';
+  const envText = formatEnvLines(conn?.env ?? []);
+  const readyPattern = conn?.shell_ready_pattern ?? "";
+  const readyTimeout = conn?.shell_ready_timeout_secs ?? "";
+  const shellIntegration = conn?.shell_integration ?? false;
+  const clipboard = conn?.clipboard ?? "deny";
+  const autoRestart = conn?.auto_restart ?? "never";
+  const agentPreset = conn?.agent_rules?.preset ?? "";
+  const agentRulesText = formatAgentRuleLines(conn?.agent_rules);
+  const presetOption = (value: string, label: string) =>
+    `<option value="${value}"${agentPreset === value ? " selected" : ""}>${label}</option>`;
   const keyDisplay = auth === "key" ? "" : "display:none;";
 
   return `
@@ -928,9 +1517,13 @@ function renderConnectionFormCard(index: number, conn?: ConnectionConfig): strin
           <input type="text" name="conn-project" class="form-input" placeholder="/home/user/project" value="${escapeHtml(projPath)}" />
         </div>
         <div class="form-group">
-          <label class="form-label">AI CLI Command</label>
+          <label class="form-label">AI CLI</label>
+          <select name="conn-ai-profile" class="form-select">
+            <option value="">Custom command</option>
+            ${profileOptions}
+          </select>
           <input type="text" name="conn-ai-cmd" class="form-input" placeholder="claude" value="${escapeHtml(aiCmd)}" />
-          <div class="form-hint">Auto-launched in project directory</div>
+          <div class="form-hint">Auto-launched in project directory; a profile overrides the command</div>
         </div>
       </div>
       <details class="conn-advanced">
@@ -947,6 +1540,79 @@ function renderConnectionFormCard(index: number, conn?: ConnectionConfig): strin
             <div class="form-hint">Max auto-reconnect attempts (default: 3)</div>
           </div>
         </div>
+        <div class="form-row">
+          <div class="form-group">
+            <label class="form-label">Prompt Pattern</label>
+            <input type="text" name="conn-ready-pattern" class="form-input" placeholder="[$#] $" value="${escapeHtml(readyPattern)}" />
+            <div class="form-hint">Regex marking the shell as ready (default: injected marker)</div>
+          </div>
+          <div class="form-group">
+            <label class="form-label">Shell Ready Timeout (sec)</label>
+            <input type="number" name="conn-ready-timeout" class="form-input" min="1" max="600" placeholder="20" value="${readyTimeout}" />
+            <div class="form-hint">Startup commands are skipped if the shell isn't ready in time</div>
+          </div>
+        </div>
+        <div class="form-group">
+          <label class="form-check">
+            <input type="checkbox" name="conn-shell-integration"${shellIntegration ? " checked" : ""} />
+            Shell integration (bash/zsh)
+          </label>
+          <div class="form-hint">Tracks cwd and exit codes so the file browser follows the terminal and reconnects restore the directory</div>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Remote Clipboard (OSC 52)</label>
+          <select name="conn-clipboard" class="form-select">
+            <option value="deny"${clipboard === "deny" ? " selected" : ""}>Deny</option>
+            <option value="allow"${clipboard === "allow" ? " selected" : ""}>Allow</option>
+          </select>
+          <div class="form-hint">Lets copies in remote tmux/vim reach this machine's clipboard</div>
+        </div>
+        <div class="form-group">
+          <label class="form-label">AI CLI Auto-Restart</label>
+          <select name="conn-auto-restart" class="form-select">
+            <option value="never"${autoRestart === "never" ? " selected" : ""}>Never</option>
+            <option value="on_failure"${autoRestart === "on_failure" ? " selected" : ""}>On failure</option>
+            <option value="always"${autoRestart === "always" ? " selected" : ""}>Always</option>
+          </select>
+          <div class="form-hint">Relaunches with increasing delay; stops after repeated quick crashes</div>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Agent State Detection</label>
+          <select name="conn-agent-preset" class="form-select">
+            ${presetOption("", "Auto (from AI CLI command)")}
+            ${presetOption("claude_code", "Claude Code")}
+            ${presetOption("opencode", "OpenCode")}
+            ${presetOption("aider", "Aider")}
+            ${presetOption("custom", "Custom patterns only")}
+          </select>
+          <textarea name="conn-agent-rules" class="form-input form-textarea" rows="2" spellcheck="false" placeholder="waiting_for_approval: Proceed\\?&#10;errored: FATAL">${escapeHtml(agentRulesText)}</textarea>
+          <div class="form-hint">Extra patterns, one "state: regex" per line (working, idle, waiting_for_approval, errored)</div>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Environment Variables</label>
+          <textarea name="conn-env" class="form-input form-textarea" rows="3" spellcheck="false" placeholder="NODE_ENV=development&#10;ANTHROPIC_API_KEY=secret:anthropic">${escapeHtml(envText)}</textarea>
+          <div class="form-hint">One NAME=value per line; NAME=secret:KEY reads KEY from the OS keychain</div>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Startup Steps</label>
+          <div class="startup-steps-list">${(conn?.startup_steps ?? []).map((s) => renderStartupStepRow(s)).join("")}</div>
+          <button type="button" class="btn btn-ghost btn-sm btn-add-step">+ Add Step</button>
+          <div class="form-hint">Typed in order after cd into the project path, before the AI CLI command</div>
+        </div>
+        <div class="form-row">
+          <div class="form-group">
+            <label class="form-label">Secret Name</label>
+            <input type="text" name="conn-secret-name" class="form-input" placeholder="anthropic" />
+          </div>
+          <div class="form-group">
+            <label class="form-label">Secret Value</label>
+            <div class="conn-secret-row">
+              <input type="password" name="conn-secret-value" class="form-input" autocomplete="off" />
+              <button type="button" class="btn btn-ghost btn-sm btn-store-secret">Save</button>
+            </div>
+            <div class="form-hint">Stored in the OS keychain, never in the workset file</div>
+          </div>
+        </div>
       </details>
     </div>`;
 }
@@ -1018,6 +1684,92 @@ function renderWorksetForm(workset?: Workset): void {
   wireFormEvents();
 }
 
+// ── AI CLI Profiles ──
+
+async function showAiProfilesForm(): Promise<void> {
+  let presets: AiCliProfile[] = [];
+  try {
+    presets = await invoke<AiCliProfile[]>("list_ai_cli_presets");
+  } catch (err) {
+    console.error(err);
+  }
+  const container = $("workset-form");
+  const presetButtons = presets
+    .map((p) => `<button type="button" class="btn btn-ghost btn-sm btn-add-ai-preset" data-preset-id="${escapeHtml(p.id)}">+ ${escapeHtml(p.name)}</button>`)
+    .join("");
+
+  container.innerHTML = `
+    <form id="ai-profiles-form">
+      <h3 class="form-title">AI CLI Profiles</h3>
+      <div class="form-group">
+        <label class="form-label">Profiles (JSON)</label>
+        <textarea name="ai-profiles" class="form-input form-textarea ai-profiles-json" rows="18" spellcheck="false">${escapeHtml(JSON.stringify(aiCliProfiles, null, 2))}</textarea>
+        <div class="form-hint">Each profile: id, name, command, args, required_env, binary, agent_rules, restart_command. The binary and required env are checked on the VM before launch.</div>
+      </div>
+      <div class="ai-preset-row">${presetButtons}</div>
+      <div class="form-actions">
+        <button type="submit" class="btn btn-primary">Save Profiles</button>
+        <button type="button" class="btn btn-ghost" id="btn-cancel-ai-profiles">Cancel</button>
+      </div>
+    </form>`;
+  showFormView();
+
+  const form = document.getElementById("ai-profiles-form") as HTMLFormElement;
+  const textarea = form.querySelector<HTMLTextAreaElement>('[name="ai-profiles"]')!;
+  const parse = (): AiCliProfile[] | null => {
+    try {
+      const value = JSON.parse(textarea.value || "[]");
+      return Array.isArray(value) ? value : null;
+    } catch {
+      return null;
+    }
+  };
+
+  form.querySelectorAll<HTMLButtonElement>(".btn-add-ai-preset").forEach((btn) => {
+    btn.addEventListener("click", () => {
+      const preset = presets.find((p) => p.id === btn.dataset.presetId);
+      const current = parse();
+      if (!preset || !current) {
+        showToast("Fix the JSON before adding a preset", "error");
+        return;
+      }
+      if (current.some((p) => p.id === preset.id)) {
+        showToast(`A profile with id '${preset.id}' already exists`, "error");
+        return;
+      }
+      textarea.value = JSON.stringify([...current, preset], null, 2);
+    });
+  });
+
+  document.getElementById("btn-cancel-ai-profiles")?.addEventListener("click", () => {
+    if (selectedWorksetId) {
+      selectWorkset(selectedWorksetId);
+    } else {
+      showEmptyState();
+    }
+  });
+
+  form.addEventListener("submit", async (e: Event) => {
+    e.preventDefault();
+    textarea.classList.remove("form-input-error");
+    const profiles = parse();
+    if (!profiles) {
+      textarea.classList.add("form-input-error");
+      showToast("Profiles must be a JSON array", "error");
+      return;
+    }
+    try {
+      const updated = await invoke<AppSettings>("set_ai_cli_profiles", { profiles });
+      aiCliProfiles = updated.ai_cli_profiles ?? [];
+      textarea.value = JSON.stringify(aiCliProfiles, null, 2);
+      showToast("AI CLI profiles saved", "success");
+    } catch (err) {
+      textarea.classList.add("form-input-error");
+      showToast(`Failed to save profiles: ${String(err)}`, "error");
+    }
+  });
+}
+
 function showCreateForm(): void {
   selectedWorksetId = null;
   highlightSelectedCard();
@@ -1099,6 +1851,38 @@ function wireConnectionCard(card: HTMLElement): void {
       keyGroup.style.display = authSelect.value === "key" ? "" : "none";
     }
   });
+
+  const stepsList = card.querySelector<HTMLElement>(".startup-steps-list");
+  card.querySelector<HTMLButtonElement>(".btn-add-step")?.addEventListener("click", () => {
+    stepsList?.insertAdjacentHTML("beforeend", renderStartupStepRow());
+  });
+  stepsList?.addEventListener("click", (e) => {
+    const btn = (e.target as HTMLElement).closest(".btn-remove-step");
+    btn?.closest(".startup-step-row")?.remove();
+  });
+  stepsList?.addEventListener("change", (e) => {
+    const select = e.target as HTMLSelectElement;
+    if (select.name !== "step-wait") return;
+    const pattern = select.closest(".startup-step-row")?.querySelector<HTMLElement>('[name="step-pattern"]');
+    if (pattern) pattern.style.display = select.value === "pattern" ? "" : "none";
+  });
+
+  const secretName = card.querySelector<HTMLInputElement>('[name="conn-secret-name"]');
+  const secretValue = card.querySelector<HTMLInputElement>('[name="conn-secret-value"]');
+  card.querySelector<HTMLButtonElement>(".btn-store-secret")?.addEventListener("click", async () => {
+    const name = secretName?.value.trim() ?? "";
+    if (!name || !secretValue?.value) {
+      showToast("Enter a secret name and value", "error");
+      return;
+    }
+    try {
+      await invoke("store_secret", { name, value: secretValue.value });
+      secretValue.value = "";
+      showToast(`Secret "${name}" saved`, "success");
+    } catch (err) {
+      showToast(`Failed to save secret: ${err}`, "error");
+    }
+  });
 }
 
 function reindexConnections(): void {
@@ -1124,6 +1908,11 @@ async function cleanupWorkspace(): Promise<void> {
     unlisten();
   }
   eventUnlisteners = [];
+  shellCwds.clear();
+  agentStates.clear();
+  queuedTasks = [];
+  document.getElementById('dispatch-popover')?.remove();
+  document.getElementById('task-popover')?.remove();
 
   // Stop any workspace-panel timers and close panel.
   stopPanelTimers();
@@ -1233,6 +2022,9 @@ async function cleanupWorkspace(): Promise<void> {
     });
 
     insertPanelToggleButton(toolbarContainer);
+    insertBroadcastToggleButton(toolbarContainer);
+    insertDispatchButton(toolbarContainer);
+    insertTasksButton(toolbarContainer);
 
     // Set host labels and attach terminals
     for (let i = 0; i < Math.min(panes.length, workset.connections.length); i++) {
@@ -1264,6 +2056,18 @@ async function cleanupWorkspace(): Promise<void> {
 
     wsState.sessionInfos = sessions;
 
+    const unlistenBroadcast = await listen<BroadcastState>('broadcast-state', (event) => {
+      renderBroadcastState(event.payload);
+    });
+    eventUnlisteners.push(unlistenBroadcast);
+
+    const unlistenTasks = await listen<TaskQueueUpdate>('task-queue-updated', (event) => {
+      if (event.payload.workset_id !== worksetId) return;
+      queuedTasks = event.payload.tasks;
+      renderTaskList();
+    });
+    eventUnlisteners.push(unlistenTasks);
+
     // Persist prompted passwords (opt-in) only after successful session creation.
     if (pendingKeystoreSaves.length > 0) {
       const ok = new Set(
@@ -1353,15 +2157,130 @@ async function cleanupWorkspace(): Promise<void> {
       );
       eventUnlisteners.push(unlisten3);
 
-      // AI CLI exit → notify user
-      const unlisten4 = await listen<void>(
+      // AI CLI exit (reported by the launch wrapper) → notify user, with the auto-restart plan
+      const unlisten4 = await listen<AiCliExit>(
         `ai-cli-exited-${session.session_id}`,
-        () => {
-          showToast('AI CLI process exited', 'error');
+        (event) => {
+          const e = event.payload;
+          let message = e.exit_code === null
+            ? `AI CLI on ${pane.hostLabel} ended with the shell`
+            : e.exit_code === 0
+              ? `AI CLI on ${pane.hostLabel} exited`
+              : `AI CLI on ${pane.hostLabel} crashed (exit ${e.exit_code})`;
+          if (e.restart_in_secs !== null) message += `; restarting in ${e.restart_in_secs}s`;
+          if (e.gave_up) message += '; auto-restart stopped after repeated failures';
+          showToast(message, e.exit_code === 0 && !e.gave_up ? 'success' : 'error');
         }
       );
       eventUnlisteners.push(unlisten4);
 
+      // Startup steps → progress in the pane status bar
+      const unlisten5 = await listen<StartupProgress>(
+        `startup-progress-${session.session_id}`,
+        (event) => {
+          const p = event.payload;
+          const label = `Step ${p.index + 1}/${p.total}: ${p.command}`;
+          if (p.state === 'running') {
+            updatePaneStatus(pane, 'connected', label);
+          } else if (p.state === 'failed' || p.state === 'timed_out') {
+            const why = p.state === 'failed' ? `exit ${p.exit_status}` : 'timed out';
+            updatePaneStatus(pane, 'connected', `${label} (${why})`);
+            showToast(`Startup step failed on ${pane.hostLabel}: ${p.command} (${why})`, 'error');
+          } else if (p.index + 1 === p.total && p.state !== 'skipped') {
+            updatePaneStatus(pane, 'connected');
+          }
+        }
+      );
+      eventUnlisteners.push(unlisten5);
+
+      // Shell integration → file browser follows the terminal's cwd
+      const unlisten6 = await listen<ShellState>(
+        `shell-state-${session.session_id}`,
+        (event) => {
+          const cwd = event.payload.cwd;
+          if (!cwd || shellCwds.get(session.session_id) === cwd) return;
+          shellCwds.set(session.session_id, cwd);
+          if (getActiveSessionContext().sessionId === session.session_id) {
+            syncPanelToActivePane();
+          }
+        }
+      );
+      eventUnlisteners.push(unlisten6);
+
+      // Bell / OSC 9 / OSC 777 → desktop notification for panes the user isn't looking at
+      const unlisten7 = await listen<SessionNotification>(
+        `session-notification-${session.session_id}`,
+        (event) => {
+          const isActive = getActiveSessionContext().sessionId === session.session_id;
+          if (isActive && document.hasFocus()) return;
+          if (!isActive) setPaneAttention(pane, true);
+          void showDesktopNotification(event.payload);
+        }
+      );
+      eventUnlisteners.push(unlisten7);
+
+      // OSC 52 remote copy → result toast (blocked copies are reported once per session)
+      let clipboardBlockedShown = false;
+      const unlisten8 = await listen<ClipboardEvent>(
+        `clipboard-${session.session_id}`,
+        (event) => {
+          const c = event.payload;
+          if (c.error) {
+            showToast(`Remote copy failed: ${c.error}`, 'error');
+          } else if (!c.allowed) {
+            if (clipboardBlockedShown) return;
+            clipboardBlockedShown = true;
+            showToast(`${pane.hostLabel} tried to copy to the clipboard; allow it in the connection's advanced settings`, 'error');
+          } else {
+            showToast(`Copied ${c.bytes} bytes from ${pane.hostLabel}`, 'success');
+          }
+        }
+      );
+      eventUnlisteners.push(unlisten8);
+
+      // AI CLI state from output rules → pane badge; approval requests also flag background panes
+      const unlisten9 = await listen<AgentStatus>(
+        `agent-state-${session.session_id}`,
+        (event) => {
+          agentStates.set(session.session_id, event.payload);
+          setPaneAgentState(pane, event.payload.state);
+          if (event.payload.state === 'waiting_for_approval'
+            && getActiveSessionContext().sessionId !== session.session_id) {
+            setPaneAttention(pane, true);
+          }
+        }
+      );
+      eventUnlisteners.push(unlisten9);
+
+      // Profile pre-launch check failed → explain in the pane instead of "command not found"
+      const unlisten10 = await listen<AiCliUnavailable>(
+        `ai-cli-unavailable-${session.session_id}`,
+        (event) => {
+          updatePaneStatus(pane, 'connected', event.payload.message);
+          showToast(event.payload.message, 'error');
+        }
+      );
+      eventUnlisteners.push(unlisten10);
+
+      // AI CLI restart phases → pane status; the final outcome also as a toast
+      const unlisten11 = await listen<AiCliRestartProgress>(
+        `ai-cli-restart-${session.session_id}`,
+        (event) => {
+          const { phase, message } = event.payload;
+          const label = message ? `${RESTART_PHASE_LABELS[phase]} (${message})` : RESTART_PHASE_LABELS[phase];
+          if (phase === 'relaunched') {
+            updatePaneStatus(pane, 'connected');
+            showToast(label, 'success');
+          } else if (phase === 'failed') {
+            updatePaneStatus(pane, 'connected', label);
+            showToast(`${pane.hostLabel}: ${label}`, 'error');
+          } else {
+            updatePaneStatus(pane, 'connected', label);
+          }
+        }
+      );
+      eventUnlisteners.push(unlisten11);
+
       // Terminal input → send to SSH via IPC
       if (pane.terminal) {
         const inputDisposable = pane.terminal.terminal.onData((data: string) => {
@@ -1389,7 +2308,6 @@ async function cleanupWorkspace(): Promise<void> {
           restartBtn.style.display = '';
           restartBtn.addEventListener('click', () => {
             invoke('restart_ai_cli', { sessionId: session.session_id })
-              .then(() => showToast('AI CLI restarted', 'success'))
               .catch((err) => showToast(`Restart failed: ${String(err)}`, 'error'));
           });
         }
@@ -1406,6 +2324,22 @@ async function cleanupWorkspace(): Promise<void> {
   }
 }
 
+async function showDesktopNotification(n: SessionNotification): Promise<void> {
+  const title = n.title ? `${n.host}: ${n.title}` : n.host;
+  if (typeof Notification === 'undefined') {
+    showToast(`${title} — ${n.message}`, 'success');
+    return;
+  }
+  if (Notification.permission === 'default') {
+    await Notification.requestPermission();
+  }
+  if (Notification.permission === 'granted') {
+    new Notification(title, { body: n.message, tag: n.session_id });
+  } else {
+    showToast(`${title} — ${n.message}`, 'success');
+  }
+}
+
 async function handleDisconnectAll(): Promise<void> {
   if (!activeWorkspace) return;
 
@@ -1468,6 +2402,10 @@ window.addEventListener("DOMContentLoaded", () => {
     showCreateForm();
   });
 
+  $("btn-ai-profiles").addEventListener("click", () => {
+    void showAiProfilesForm();
+  });
+
   const emptyCreateBtn = document.getElementById("btn-empty-create");
   emptyCreateBtn?.addEventListener("click", () => {
     showCreateForm();
@@ -1481,6 +2419,15 @@ window.addEventListener("DOMContentLoaded", () => {
     if (target.id === 'btn-workspace-panel') {
       togglePanel();
     }
+    if (target.id === 'btn-broadcast') {
+      toggleBroadcast();
+    }
+    if (target.id === 'btn-dispatch-prompt') {
+      toggleDispatchPopover();
+    }
+    if (target.id === 'btn-task-queue') {
+      toggleTaskPopover();
+    }
 
     const reconnectBtn = target.closest('.btn-pane-reconnect') as HTMLButtonElement | null;
     if (reconnectBtn) {
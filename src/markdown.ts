@@ -1,3 +1,4 @@
+import { invoke } from '@tauri-apps/api/core';
 import { marked } from 'marked';
 import { markedHighlight } from 'marked-highlight';
 import hljs from 'highlight.js/lib/core';
@@ -178,6 +179,65 @@ export function renderMarkdownToHtml(markdown: string): string {
   return sanitizeHtml(raw);
 }
 
+interface MarkdownAsset {
+  target: string;
+  kind: 'image' | 'link';
+  remote_path: string;
+  fragment: string | null;
+  exists: boolean;
+  is_dir: boolean;
+  size_bytes: number | null;
+  data_uri: string | null;
+  error: string | null;
+}
+
+interface MarkdownAssets {
+  path: string;
+  assets: MarkdownAsset[];
+  truncated: boolean;
+}
+
+/** The renderer may percent-encode src/href (spaces, non-ASCII); compare targets in decoded form. */
+function normalizeAssetTarget(target: string): string {
+  try {
+    return decodeURI(target);
+  } catch {
+    return target;
+  }
+}
+
+/**
+ * Swaps relative image sources for data URIs fetched from the VM and tags relative links
+ * with the remote path they resolve to (data-remote-path), marking missing targets.
+ */
+export async function applyRemoteMarkdownAssets(
+  container: HTMLElement,
+  sessionId: string,
+  path: string,
+): Promise<void> {
+  const result = await invoke<MarkdownAssets>('resolve_markdown_assets', { sessionId, path });
+  const byTarget = new Map(result.assets.map((a) => [normalizeAssetTarget(a.target), a]));
+
+  for (const img of Array.from(container.querySelectorAll<HTMLImageElement>('img[src]'))) {
+    const asset = byTarget.get(normalizeAssetTarget(img.getAttribute('src') ?? ''));
+    if (!asset) continue;
+    if (asset.data_uri) {
+      img.setAttribute('src', asset.data_uri);
+    } else {
+      img.removeAttribute('src');
+      img.title = asset.error ?? (asset.exists ? asset.remote_path : `Not found: ${asset.remote_path}`);
+    }
+  }
+
+  for (const link of Array.from(container.querySelectorAll<HTMLAnchorElement>('a[href]'))) {
+    const asset = byTarget.get(normalizeAssetTarget(link.getAttribute('href') ?? ''));
+    if (!asset) continue;
+    link.dataset.remotePath = asset.remote_path;
+    link.title = asset.remote_path;
+    link.classList.toggle('md-link-missing', !asset.exists);
+  }
+}
+
 export function installMarkdownLinkHandler(
   container: HTMLElement,
   openExternalUrl: (url: string) => Promise<void>,
@@ -5,7 +5,7 @@ import { invoke } from '@tauri-apps/api/core';
 import { openUrl } from '@tauri-apps/plugin-opener';
 
 import { FileBrowser, type FileEntry } from './file_browser.ts';
-import { installMarkdownLinkHandler, renderMarkdownToHtml } from './markdown.ts';
+import { applyRemoteMarkdownAssets, installMarkdownLinkHandler, renderMarkdownToHtml } from './markdown.ts';
 
 // ── Types ──
 
@@ -99,6 +99,7 @@ export function createWorkspace(
       container.addEventListener('click', () => {
         activePaneIndex = i;
         setActivePane(gridContainer, i);
+        setPaneAttention(pane, false);
         if (pane.terminal) {
           pane.terminal.terminal.focus();
         }
@@ -320,6 +321,7 @@ export function attachTerminal(pane: PaneState): void {
     <span class="pane-status-dot connecting"></span>
     <span class="pane-host-label">${escapeText(pane.hostLabel)}</span>
     <span class="pane-status-text"></span>
+    <span class="pane-agent-state" style="display:none;"></span>
     <span class="pane-status-spacer"></span>
     <select class="pane-content-type-select">
       <option value="terminal">Terminal</option>
@@ -532,14 +534,26 @@ async function renderMarkdownInPane(pane: PaneState, path: string, force: boolea
   }
 
   try {
-    const result = await invoke<{ path: string; bytes: number[]; truncated: boolean }>('read_file', {
+    const result = await invoke<{
+      path: string;
+      truncated: boolean;
+      content: { binary: boolean; mime: string };
+      text: string | null;
+    }>('read_file', {
       sessionId: pane.sessionId,
       path,
       maxBytes: 1024 * 1024,
+      decodeText: true,
     });
 
-    const bytes = new Uint8Array(result.bytes);
-    const text = new TextDecoder('utf-8').decode(bytes);
+    if (result.content.binary || result.text === null) {
+      mdPathEl.textContent = path;
+      mdNoteEl.textContent = '';
+      mdBody.innerHTML = `<p class="pane-markdown-placeholder">Binary file (${escapeText(result.content.mime)})</p>`;
+      delete mdBody.dataset.lastText;
+      return;
+    }
+    const text = result.text;
 
     mdPathEl.textContent = path;
     mdNoteEl.textContent = result.truncated ? 'Showing first 1 MiB (truncated)' : '';
@@ -548,6 +562,9 @@ async function renderMarkdownInPane(pane: PaneState, path: string, force: boolea
     if (!force && prev === text) return;
     mdBody.dataset.lastText = text;
     mdBody.innerHTML = renderMarkdownToHtml(text);
+    void applyRemoteMarkdownAssets(mdBody, pane.sessionId, path).catch(() => {
+      // Preview still renders without remote assets.
+    });
   } catch (err) {
     mdBody.innerHTML = `<p class="pane-markdown-placeholder">Failed to read file: ${escapeText(String(err))}</p>`;
   }
@@ -619,11 +636,38 @@ export function updatePaneStatus(pane: PaneState, status: string, statusText?: s
   const btn = pane.statusEl.querySelector<HTMLButtonElement>('.btn-pane-reconnect');
   if (btn) {
     const s = status.toLowerCase();
-    const show = s === 'reconnect_failed' || s === 'error';
+    const show = s === 'reconnect_failed' || s === 'error' || s === 'shell_not_ready';
     btn.style.display = show ? '' : 'none';
   }
 }
 
+export function setPaneBroadcast(pane: PaneState, linked: boolean): void {
+  pane.container.classList.toggle('broadcast-linked', linked);
+}
+
+const AGENT_STATE_LABELS: Record<string, string> = {
+  working: 'Working',
+  idle: 'Idle',
+  waiting_for_approval: 'Needs approval',
+  errored: 'Error',
+  exited: 'Exited',
+};
+
+/** Shows the AI CLI state badge; unknown hides it. */
+export function setPaneAgentState(pane: PaneState, state: string): void {
+  const el = pane.statusEl?.querySelector<HTMLElement>('.pane-agent-state');
+  if (!el) return;
+  const label = AGENT_STATE_LABELS[state];
+  el.style.display = label ? '' : 'none';
+  el.textContent = label ?? '';
+  el.className = `pane-agent-state ${state}`;
+}
+
+/** Highlights a pane whose session asked for attention; cleared when the pane is clicked. */
+export function setPaneAttention(pane: PaneState, on: boolean): void {
+  pane.container.classList.toggle('attention', on);
+}
+
 export function setPaneHostLabel(pane: PaneState, label: string): void {
   pane.hostLabel = label;
   if (pane.statusEl) {
@@ -650,5 +694,6 @@ function defaultStatusText(status: string): string {
   if (s === 'reconnect_failed') return 'Connection lost. Click to reconnect manually.';
   if (s === 'disconnected') return 'Disconnected';
   if (s === 'error') return 'Error';
+  if (s === 'shell_not_ready') return 'Shell did not become ready; startup commands skipped';
   return '';
 }